@@ -1,12 +1,18 @@
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use clap::{arg, Parser};
+use clap::Parser;
 use redis_starter_rust::server::replicate::info::Role;
-use tokio::net::TcpListener;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, UnixListener};
 use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinSet;
 
-use redis_starter_rust::server::replicate::command::do_repl_handshake;
+use redis_starter_rust::server::listen::{self, BindAddr, DEFAULT_BIND};
+use redis_starter_rust::server::replicate::command::follow_master;
+use redis_starter_rust::server::store::expire::{
+    active_expire, ActiveExpire, DEFAULT_EFFORT, DEFAULT_HZ,
+};
 use redis_starter_rust::server::{handle_connection, init_on_startup, Server};
 
 #[derive(Parser, Debug)]
@@ -14,43 +20,118 @@ use redis_starter_rust::server::{handle_connection, init_on_startup, Server};
 struct Args {
     #[arg(long, short)]
     port: Option<u16>,
-    #[arg(short, long, num_args = 2, value_names = ["MASTER_HOST", "MASTER_PORT"])]
+    #[arg(short, long, num_args = 1..=2, value_names = ["MASTER_HOST", "MASTER_PORT"])]
     replicaof: Option<Vec<String>>,
+    // e.g. --bind 0.0.0.0 ::1 -> prefix an address with '-' if failing to bind it shouldn't be fatal
+    #[arg(long, num_args = 1.., value_name = "ADDR", allow_hyphen_values = true)]
+    bind: Option<Vec<String>>,
+    // Also accept connections on a unix socket. Pass --port 0 to only use the socket.
+    #[arg(long, value_name = "PATH")]
+    unixsocket: Option<PathBuf>,
+    // octal, e.g. 700
+    #[arg(long, value_name = "PERM", requires = "unixsocket")]
+    unixsocketperm: Option<String>,
+    // How many times a second background tasks (e.g. removing expired keys) run
+    #[arg(long, default_value_t = DEFAULT_HZ)]
+    hz: u32,
+    // 1-10 -> how hard to work at removing expired keys nobody's touched
+    #[arg(long, default_value_t = DEFAULT_EFFORT)]
+    active_expire_effort: u32,
+}
+
+fn spawn_connection<S>(stream: S, server: &Arc<RwLock<Server>>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let arc_server = Arc::clone(server);
+    let arc_stream = Arc::new(Mutex::new(stream));
+    tokio::spawn(async move {
+        // a client going away mid-command (e.g. resetting the connection) only ends its own task
+        if let Err(e) = handle_connection(&arc_stream, &arc_server).await {
+            eprintln!("Error handling client connection: {}", e);
+        }
+    });
+}
+
+async fn accept_loop(listener: TcpListener, server: Arc<RwLock<Server>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, client_connection)) => {
+                println!("Received connection from client: {}", client_connection);
+                spawn_connection(stream, &server);
+            }
+            Err(e) => {
+                eprintln!("Error accepting client connection: {}", e);
+            }
+        }
+    }
+}
+
+async fn accept_unix_loop(listener: UnixListener, server: Arc<RwLock<Server>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                println!("Received connection from client on the unix socket");
+                spawn_connection(stream, &server);
+            }
+            Err(e) => {
+                eprintln!("Error accepting client connection: {}", e);
+            }
+        }
+    }
+}
+
+fn exit_with(e: anyhow::Error) -> ! {
+    eprintln!("{:#}", e);
+    std::process::exit(1)
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
-    let server: Arc<RwLock<Server>> = init_on_startup(args.port, args.replicaof);
+    let server: Arc<RwLock<Server>> =
+        init_on_startup(args.port, args.replicaof).unwrap_or_else(|e| exit_with(e));
 
     // Move me
     if server.read().await.replica_info.role == Role::Slave {
         let follower_ref = Arc::clone(&server);
         tokio::spawn(async move {
-            do_repl_handshake(follower_ref)
+            follow_master(follower_ref)
                 .await
-                .expect("Handshake failed!");
+                .expect("Replication failed!");
         });
     }
-    // TODO -> un-hardcode localhost
-    let socket = SocketAddrV4::new(Ipv4Addr::LOCALHOST, server.read().await.port);
-    let listener = TcpListener::bind(socket)
-        .await
-        .expect("Failed to bind to socket!");
-    loop {
-        match listener.accept().await {
-            Ok((stream, client_connection)) => {
-                println!("Received connection from client: {}", client_connection);
-                let arc_server = Arc::clone(&server);
-                let arc_stream = Arc::new(Mutex::new(stream));
-                tokio::spawn(async move {
-                    handle_connection(&arc_stream, &arc_server).await.unwrap();
-                });
-            }
-            Err(e) => {
-                eprintln!("Error accepting client connection: {}", e);
-            }
+    let expire_config = ActiveExpire::new(args.hz, args.active_expire_effort);
+    tokio::spawn(active_expire(Arc::clone(&server), expire_config));
+
+    let mut accept_loops = JoinSet::new();
+    if let Some(path) = args.unixsocket {
+        let perm = args
+            .unixsocketperm
+            .map(|perm| listen::parse_perm(&perm).unwrap_or_else(|e| exit_with(e)));
+        let listener = listen::bind_unix(&path, perm).unwrap_or_else(|e| exit_with(e));
+        accept_loops.spawn(accept_unix_loop(listener, Arc::clone(&server)));
+    }
+    // Like redis, port 0 turns TCP off
+    let port = server.read().await.port;
+    if port != 0 {
+        let binds = match args.bind {
+            Some(binds) => binds,
+            None => DEFAULT_BIND.iter().map(|addr| addr.to_string()).collect(),
+        };
+        let mut addrs = Vec::with_capacity(binds.len());
+        for bind in binds {
+            addrs.extend(BindAddr::parse(&bind, port).unwrap_or_else(|e| exit_with(e)));
+        }
+        let listeners = listen::bind(&addrs).await.unwrap_or_else(|e| exit_with(e));
+        for listener in listeners {
+            accept_loops.spawn(accept_loop(listener, Arc::clone(&server)));
         }
+    } else if accept_loops.is_empty() {
+        exit_with(anyhow::anyhow!(
+            "Nothing to listen on -> set a --port or a --unixsocket"
+        ));
     }
+    while accept_loops.join_next().await.is_some() {}
 }
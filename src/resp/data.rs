@@ -1,17 +1,50 @@
 use std::collections::VecDeque;
 
+use bytes::Bytes;
+
 use super::errors::RESPError;
 
 type Vec<T> = VecDeque<T>;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// The protocol version a connection has negotiated (-> HELLO). Replies are built using the RESP3 types
+// and downgraded when serialized for a RESP2 connection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl Protocol {
+    pub fn version(&self) -> i64 {
+        match self {
+            Self::Resp2 => 2,
+            Self::Resp3 => 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
-    SimpleString(String),
-    SimpleError(String),
-    BulkString(String),
-    BulkError(String),
+    SimpleString(Bytes),
+    SimpleError(Bytes),
+    Integer(i64),
+    BulkString(Bytes),
+    NullBulkString,
+    BulkError(Bytes),
     Array(Vec<DataType>),
-    StoreFile(String),
+    NullArray,
+    StoreFile(Bytes),
+    // RESP3
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(Bytes),
+    VerbatimString(Bytes, Bytes), // (format, text) -> the format is always 3 bytes, e.g. 'txt'
+    Map(Vec<(DataType, DataType)>),
+    Set(Vec<DataType>),
+    Push(Vec<DataType>),
+    Attribute(Vec<(DataType, DataType)>, Box<DataType>), // (attributes, the reply they describe)
 }
 
 impl DataType {
@@ -21,17 +54,31 @@ impl DataType {
             DataType::SimpleError(s) => s,
             DataType::BulkString(s) => s,
             DataType::BulkError(s) => s,
+            DataType::VerbatimString(_, s) => s,
             _ => return false,
         };
 
-        a == cmp
+        a == cmp.as_bytes()
     }
 
-    pub fn try_to_string(&self) -> anyhow::Result<String, RESPError> {
+    pub fn try_to_bytes(&self) -> anyhow::Result<Bytes, RESPError> {
         match self {
-            DataType::SimpleString(s) => Ok(s.to_string()),
-            DataType::BulkString(s) => Ok(s.to_string()),
+            DataType::SimpleString(s) => Ok(s.clone()),
+            DataType::BulkString(s) => Ok(s.clone()),
             _ => Err(RESPError::InvalidType),
         }
     }
+
+    pub fn try_to_string(&self) -> anyhow::Result<String, RESPError> {
+        let bytes = self.try_to_bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| RESPError::InvalidData)
+    }
+
+    pub fn bulk_str(s: &str) -> Self {
+        DataType::BulkString(Bytes::copy_from_slice(s.as_bytes()))
+    }
+
+    pub fn simple_str(s: &str) -> Self {
+        DataType::SimpleString(Bytes::copy_from_slice(s.as_bytes()))
+    }
 }
@@ -2,6 +2,7 @@
 pub enum RESPError {
     InvalidType,
     InvalidData,
+    Incomplete,
 }
 
 impl std::fmt::Display for RESPError {
@@ -13,6 +14,9 @@ impl std::fmt::Display for RESPError {
             Self::InvalidData => {
                 write!(f, "RESP Error: Invalid data!")
             }
+            Self::Incomplete => {
+                write!(f, "RESP Error: Incomplete frame!")
+            }
         }
     }
 }
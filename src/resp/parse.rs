@@ -1,19 +1,37 @@
 use std::collections::VecDeque;
 
+use bytes::Bytes;
+
 use super::data::DataType;
 use super::errors::RESPError;
 
 // RESP sequence terminal characters. -> https://redis.io/docs/reference/protocol-spec/
-pub const _CRLF: &[u8] = b"\r\n";
+pub const CRLF: &[u8] = b"\r\n";
+
+// Upper bounds on declared lengths, so a hostile length prefix can't make us reserve gigabytes up front.
+// These match redis' `proto-max-bulk-len` and multibulk limits.
+const MAX_BULK_LEN: isize = 512 * 1024 * 1024;
+const MAX_ARRAY_LEN: isize = 1024 * 1024 * 1024;
+const PREALLOC_LIMIT: usize = 1024;
+// Same as redis' PROTO_INLINE_MAX_SIZE
+const MAX_INLINE_LEN: usize = 64 * 1024;
+// How deeply aggregates can nest. Each level is another recursive call, so without a limit a frame like
+// '*1\r\n*1\r\n...' could run the connection's task out of stack.
+const MAX_DEPTH: usize = 128;
 
 type Vec<T> = VecDeque<T>;
 type R<T> = anyhow::Result<T, RESPError>;
 
+// The parser works over whatever bytes have been received so far. If the data ends part way through a
+// frame, parsing fails with `RESPError::Incomplete` and the caller should retry once more bytes arrive.
+// On success, `position()` is the number of bytes the frame occupied.
 #[derive(Debug)]
 pub struct Parser<'data> {
     data: &'data [u8],
     position: usize,
     len: usize,
+    // how many frames deep the one being parsed is
+    depth: usize,
 }
 
 impl<'data> Parser<'data> {
@@ -22,12 +40,18 @@ impl<'data> Parser<'data> {
             data,
             position: 0,
             len: data.len(),
+            depth: 0,
         }
     }
 
     #[inline]
-    fn peek(&self) -> u8 {
-        self.data[self.position + 1]
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.len - self.position
     }
 
     #[inline]
@@ -39,88 +63,118 @@ impl<'data> Parser<'data> {
 
     #[inline]
     fn at_end(&self) -> bool {
-        self.position == self.len - 1
+        self.position >= self.len
     }
 
     // end-of-sequence
     #[inline]
     fn is_eos(&self) -> bool {
-        self.data[self.position] == b'\r' && self.peek() == b'\n'
+        self.remaining() >= 2 && &self.data[self.position..self.position + 2] == CRLF
     }
 
-    fn skip_crlf(&mut self) -> anyhow::Result<(), RESPError> {
-        match !self.at_end() {
-            true => {
-                if self.is_eos() {
-                    self.read_byte();
-                    self.read_byte();
-                    Ok(())
-                } else {
-                    Err(RESPError::InvalidData)
-                }
-            }
-            false => Err(RESPError::InvalidData),
-        }
+    // Index of the next CRLF at or after the current position.
+    fn find_crlf(&self) -> R<usize> {
+        self.data[self.position..]
+            .windows(2)
+            .position(|w| w == CRLF)
+            .map(|i| self.position + i)
+            .ok_or(RESPError::Incomplete)
     }
 
-    fn parse_len(&mut self) -> anyhow::Result<isize, RESPError> {
-        let mut buffer = String::new();
-        while !self.is_eos() && !self.at_end() {
-            buffer.push(self.read_byte() as char);
+    fn skip_crlf(&mut self) -> R<()> {
+        if self.remaining() < 2 {
+            Err(RESPError::Incomplete)
+        } else if self.is_eos() {
+            self.position += 2;
+            Ok(())
+        } else {
+            Err(RESPError::InvalidData)
         }
-        match buffer.parse::<isize>() {
-            Ok(len) => Ok(len),
-            Err(_) => Err(RESPError::InvalidData),
+    }
+
+    // Reads up to the next CRLF, consuming the CRLF as well.
+    fn read_line(&mut self) -> R<&'data [u8]> {
+        let end = self.find_crlf()?;
+        let line = &self.data[self.position..end];
+        self.position = end + 2;
+        Ok(line)
+    }
+
+    // Reads `n` bytes of payload.
+    fn read_exact(&mut self, n: usize) -> R<&'data [u8]> {
+        if self.remaining() < n {
+            return Err(RESPError::Incomplete);
         }
+        let bytes = &self.data[self.position..self.position + n];
+        self.position += n;
+        Ok(bytes)
+    }
+
+    // Leaves the terminating CRLF in place.
+    fn parse_len(&mut self) -> R<isize> {
+        let end = self.find_crlf()?;
+        let digits = &self.data[self.position..end];
+        let len = std::str::from_utf8(digits)
+            .ok()
+            .and_then(|s| s.parse::<isize>().ok())
+            .ok_or(RESPError::InvalidData)?;
+        self.position = end;
+        Ok(len)
     }
 
     fn parse_simple_str(&mut self) -> R<DataType> {
         self.read_byte(); // skip the type byte
-        let mut buffer = String::new();
-        while !self.is_eos() {
-            if !self.at_end() {
-                buffer.push(self.read_byte().to_ascii_lowercase() as char)
-            } else {
-                return Err(RESPError::InvalidData);
-            }
-        }
-        self.skip_crlf()?;
-        Ok(DataType::SimpleString(buffer))
+        let line = self.read_line()?;
+        Ok(DataType::SimpleString(Bytes::copy_from_slice(line)))
+    }
+
+    fn parse_simple_err(&mut self) -> R<DataType> {
+        self.read_byte(); // skip the type byte
+        let line = self.read_line()?;
+        Ok(DataType::SimpleError(Bytes::copy_from_slice(line)))
+    }
+
+    fn parse_integer(&mut self) -> R<DataType> {
+        self.read_byte(); // skip the type byte
+        let line = self.read_line()?;
+        std::str::from_utf8(line)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok())
+            .map(DataType::Integer)
+            .ok_or(RESPError::InvalidData)
     }
 
     fn parse_bulk_str(&mut self) -> R<DataType> {
         self.read_byte(); // skip the type byte
         let len = self.parse_len()?;
-        match len > 0 {
-            true => {
+        self.skip_crlf()?;
+        if !(-1..=MAX_BULK_LEN).contains(&len) {
+            return Err(RESPError::InvalidData);
+        }
+        match len {
+            -1 => Ok(DataType::NullBulkString),
+            _ => {
+                let bytes = self.read_exact(len as usize)?;
                 self.skip_crlf()?;
-                let mut buffer = String::with_capacity(len as usize);
-                for _ in 0..len {
-                    if !self.at_end() {
-                        buffer.push(self.read_byte().to_ascii_lowercase() as char);
-                    } else {
-                        break;
-                    }
-                }
-                if self.at_end() {
-                    buffer.push(self.read_byte().to_ascii_lowercase() as char);
-                    Ok(DataType::StoreFile(buffer))
-                } else {
-                    self.skip_crlf()?;
-                    Ok(DataType::BulkString(buffer))
-                }
+                Ok(DataType::BulkString(Bytes::copy_from_slice(bytes)))
             }
-            false => Ok(DataType::BulkString(String::new())),
         }
     }
 
     fn parse_array(&mut self) -> R<DataType> {
         self.read_byte(); // skip the type byte
         let len = self.parse_len()?;
+        self.skip_crlf()?;
+        if !(-1..=MAX_ARRAY_LEN).contains(&len) {
+            return Err(RESPError::InvalidData);
+        }
+        if len == -1 {
+            return Ok(DataType::NullArray);
+        }
         match len > 0 {
             true => {
-                self.skip_crlf()?;
-                let mut buffer = Vec::with_capacity(len as usize);
+                let len = len as usize;
+                let mut buffer = Vec::with_capacity(len.min(PREALLOC_LIMIT));
                 for _ in 0..len {
                     buffer.push_back(self.parse()?);
                 }
@@ -130,23 +184,301 @@ impl<'data> Parser<'data> {
         }
     }
 
+    fn parse_bulk_err(&mut self) -> R<DataType> {
+        match self.parse_bulk_str()? {
+            DataType::BulkString(s) => Ok(DataType::BulkError(s)),
+            _ => Err(RESPError::InvalidData),
+        }
+    }
+
+    fn parse_verbatim_str(&mut self) -> R<DataType> {
+        // =<len>\r\n<fmt>:<text>\r\n
+        match self.parse_bulk_str()? {
+            DataType::BulkString(s) if s.len() >= 4 && s[3] == b':' => {
+                Ok(DataType::VerbatimString(s.slice(..3), s.slice(4..)))
+            }
+            _ => Err(RESPError::InvalidData),
+        }
+    }
+
+    fn parse_null(&mut self) -> R<DataType> {
+        self.read_byte(); // skip the type byte
+        match self.read_line()? {
+            b"" => Ok(DataType::Null),
+            _ => Err(RESPError::InvalidData),
+        }
+    }
+
+    fn parse_boolean(&mut self) -> R<DataType> {
+        self.read_byte(); // skip the type byte
+        match self.read_line()? {
+            b"t" => Ok(DataType::Boolean(true)),
+            b"f" => Ok(DataType::Boolean(false)),
+            _ => Err(RESPError::InvalidData),
+        }
+    }
+
+    fn parse_double(&mut self) -> R<DataType> {
+        self.read_byte(); // skip the type byte
+        let line = self.read_line()?;
+        let double = match line {
+            b"inf" => f64::INFINITY,
+            b"-inf" => f64::NEG_INFINITY,
+            b"nan" => f64::NAN,
+            _ => std::str::from_utf8(line)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .ok_or(RESPError::InvalidData)?,
+        };
+        Ok(DataType::Double(double))
+    }
+
+    fn parse_big_number(&mut self) -> R<DataType> {
+        self.read_byte(); // skip the type byte
+        let line = self.read_line()?;
+        let digits = match line.first() {
+            Some(b'-') | Some(b'+') => &line[1..],
+            _ => line,
+        };
+        match !digits.is_empty() && digits.iter().all(u8::is_ascii_digit) {
+            true => Ok(DataType::BigNumber(Bytes::copy_from_slice(line))),
+            false => Err(RESPError::InvalidData),
+        }
+    }
+
+    // Shared by arrays, sets and pushes
+    fn parse_elements(&mut self) -> R<Vec<DataType>> {
+        self.read_byte(); // skip the type byte
+        let len = self.parse_len()?;
+        self.skip_crlf()?;
+        if !(0..=MAX_ARRAY_LEN).contains(&len) {
+            return Err(RESPError::InvalidData);
+        }
+        let len = len as usize;
+        let mut buffer = Vec::with_capacity(len.min(PREALLOC_LIMIT));
+        for _ in 0..len {
+            buffer.push_back(self.parse()?);
+        }
+        Ok(buffer)
+    }
+
+    // Shared by maps and attributes
+    fn parse_pairs(&mut self) -> R<Vec<(DataType, DataType)>> {
+        self.read_byte(); // skip the type byte
+        let len = self.parse_len()?;
+        self.skip_crlf()?;
+        if !(0..=MAX_ARRAY_LEN).contains(&len) {
+            return Err(RESPError::InvalidData);
+        }
+        let len = len as usize;
+        let mut buffer = Vec::with_capacity(len.min(PREALLOC_LIMIT));
+        for _ in 0..len {
+            let k = self.parse()?;
+            let v = self.parse()?;
+            buffer.push_back((k, v));
+        }
+        Ok(buffer)
+    }
+
+    fn parse_attribute(&mut self) -> R<DataType> {
+        // the attributes are followed by the reply they describe
+        let attributes = self.parse_pairs()?;
+        let data = self.parse()?;
+        Ok(DataType::Attribute(attributes, Box::new(data)))
+    }
+
+    // Inline commands are a plain line of space separated arguments, e.g. 'SET foo "bar baz"\r\n', for
+    // people typing into telnet or netcat. They're returned as an array of bulk strings, the same as a
+    // regular command.
+    fn parse_inline(&mut self) -> R<DataType> {
+        let end = match self.data[self.position..].iter().position(|b| *b == b'\n') {
+            Some(i) => self.position + i,
+            None if self.remaining() > MAX_INLINE_LEN => return Err(RESPError::InvalidData),
+            None => return Err(RESPError::Incomplete),
+        };
+        let mut line = &self.data[self.position..end];
+        if let Some((b'\r', rest)) = line.split_last() {
+            line = rest;
+        }
+        self.position = end + 1;
+        let args = split_inline_args(line)?;
+        Ok(DataType::Array(
+            args.into_iter().map(DataType::BulkString).collect(),
+        ))
+    }
+
+    // The store file sent after a FULLRESYNC looks like a bulk string, but without the trailing CRLF. It
+    // can't be told apart from a bulk string by looking at it, so the caller has to know to expect it.
+    pub fn parse_store_file(&mut self) -> R<DataType> {
+        if self.at_end() {
+            return Err(RESPError::Incomplete);
+        }
+        if self.read_byte() != b'$' {
+            return Err(RESPError::InvalidType);
+        }
+        let len = self.parse_len()?;
+        self.skip_crlf()?;
+        if !(0..=MAX_BULK_LEN).contains(&len) {
+            return Err(RESPError::InvalidData);
+        }
+        let bytes = self.read_exact(len as usize)?;
+        Ok(DataType::StoreFile(Bytes::copy_from_slice(bytes)))
+    }
+
     pub fn parse(&mut self) -> R<DataType> {
+        if self.at_end() {
+            return Err(RESPError::Incomplete);
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err(RESPError::InvalidData);
+        }
+        self.depth += 1;
+        let data = self.parse_frame();
+        self.depth -= 1;
+        data
+    }
+
+    fn parse_frame(&mut self) -> R<DataType> {
         Ok(match self.data[self.position] {
             b'+' => self.parse_simple_str()?,
+            b'-' => self.parse_simple_err()?,
+            b':' => self.parse_integer()?,
             b'$' => self.parse_bulk_str()?,
             b'*' => self.parse_array()?,
+            b'!' => self.parse_bulk_err()?,
+            b'=' => self.parse_verbatim_str()?,
+            b'_' => self.parse_null()?,
+            b'#' => self.parse_boolean()?,
+            b',' => self.parse_double()?,
+            b'(' => self.parse_big_number()?,
+            b'%' => DataType::Map(self.parse_pairs()?),
+            b'~' => DataType::Set(self.parse_elements()?),
+            b'>' => DataType::Push(self.parse_elements()?),
+            b'|' => self.parse_attribute()?,
+            // like redis, only a new top-level command can be inline -> inside an aggregate it's garbage
+            _ if self.depth == 1 => self.parse_inline()?,
             _ => return Err(RESPError::InvalidType),
         })
     }
 }
 
+// The byte written as two hex digits starting at `at`
+fn hex_byte(line: &[u8], at: usize) -> Option<u8> {
+    let digit = |i: usize| line.get(i).and_then(|b| (*b as char).to_digit(16));
+    match (digit(at), digit(at + 1)) {
+        (Some(hi), Some(lo)) => Some((hi * 16 + lo) as u8),
+        _ => None,
+    }
+}
+
+// Splits an inline command into its arguments the way redis does (-> sdssplitargs). Arguments are
+// separated by whitespace and can be quoted. Double quoted arguments support escape sequences (\n, \r,
+// \t, \b, \a, \xHH), single quoted ones only support \'. A closing quote has to be followed by
+// whitespace or the end of the line.
+fn split_inline_args(line: &[u8]) -> R<std::vec::Vec<Bytes>> {
+    let mut args = std::vec::Vec::new();
+    let mut i = 0;
+    loop {
+        while i < line.len() && line[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i == line.len() {
+            return Ok(args);
+        }
+        let mut current = std::vec::Vec::new();
+        let (mut in_dq, mut in_sq) = (false, false);
+        loop {
+            let byte = line.get(i).copied();
+            let next = line.get(i + 1).copied();
+            if in_dq {
+                match (byte, next) {
+                    (None, _) => return Err(RESPError::InvalidData), // unbalanced quotes
+                    (Some(b'\\'), Some(b'x')) if hex_byte(line, i + 2).is_some() => {
+                        current.extend(hex_byte(line, i + 2));
+                        i += 3;
+                    }
+                    (Some(b'\\'), Some(c)) => {
+                        current.push(match c {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            c => c,
+                        });
+                        i += 1;
+                    }
+                    (Some(b'"'), next) => {
+                        if next.is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return Err(RESPError::InvalidData);
+                        }
+                        i += 1;
+                        break;
+                    }
+                    (Some(c), _) => current.push(c),
+                }
+            } else if in_sq {
+                match (byte, next) {
+                    (None, _) => return Err(RESPError::InvalidData), // unbalanced quotes
+                    (Some(b'\\'), Some(b'\'')) => {
+                        current.push(b'\'');
+                        i += 1;
+                    }
+                    (Some(b'\''), next) => {
+                        if next.is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return Err(RESPError::InvalidData);
+                        }
+                        i += 1;
+                        break;
+                    }
+                    (Some(c), _) => current.push(c),
+                }
+            } else {
+                match byte {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() || c == 0 => break,
+                    Some(b'"') => in_dq = true,
+                    Some(b'\'') => in_sq = true,
+                    Some(c) => current.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(Bytes::from(current));
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use std::collections::VecDeque;
 
+    use bytes::Bytes;
+
     use super::DataType;
     use super::Parser;
+    use super::RESPError;
+    use super::MAX_DEPTH;
+    use crate::resp::data::Protocol;
+    use crate::resp::serialize::Serializer;
+
+    fn round_trip(data: DataType) {
+        let serialized = Serializer::serialize_data(&data);
+        let mut parser = Parser::new(&serialized);
+        assert_eq!(data, parser.parse().unwrap());
+        assert_eq!(parser.position(), serialized.len());
+    }
+
+    fn round_trip_resp3(data: DataType) {
+        let serialized = Serializer::serialize(&data, Protocol::Resp3);
+        let mut parser = Parser::new(&serialized);
+        assert_eq!(data, parser.parse().unwrap());
+        assert_eq!(parser.position(), serialized.len());
+    }
+
+    fn bulk(s: &'static [u8]) -> DataType {
+        DataType::BulkString(Bytes::from_static(s))
+    }
 
     #[test]
     fn test_skip_crlf() {
@@ -173,7 +505,7 @@ mod tests {
     fn test_parse_simple_str() {
         let data = b"+TEST\r\n";
         let mut parser = Parser::new(data);
-        let expected = DataType::SimpleString("test".to_string());
+        let expected = DataType::SimpleString(Bytes::from_static(b"TEST"));
         let actual = parser.parse().unwrap();
         assert_eq!(expected, actual)
     }
@@ -182,7 +514,7 @@ mod tests {
     fn test_parse_bulk_str() {
         let data = b"$4\r\nTEST\r\n";
         let mut parser = Parser::new(data);
-        let expected = DataType::BulkString("test".to_string());
+        let expected = DataType::BulkString(Bytes::from_static(b"TEST"));
         let actual = parser.parse().unwrap();
         assert_eq!(expected, actual)
     }
@@ -192,8 +524,8 @@ mod tests {
         let data = b"*2\r\n$4\r\ntest\r\n+TEST\r\n";
         let mut parser = Parser::new(data);
         let mut expected_vec = VecDeque::new();
-        expected_vec.push_back(DataType::BulkString("test".to_string()));
-        expected_vec.push_back(DataType::SimpleString("test".to_string()));
+        expected_vec.push_back(DataType::BulkString(Bytes::from_static(b"test")));
+        expected_vec.push_back(DataType::SimpleString(Bytes::from_static(b"TEST")));
         let expected = DataType::Array(expected_vec);
         let actual = parser.parse().unwrap();
         assert_eq!(expected, actual)
@@ -204,22 +536,47 @@ mod tests {
         let data = b"*2\r\n*2\r\n+OK\r\n+TEST\r\n$4\r\nTEST\r\n";
         let mut parser = Parser::new(data);
         let mut nested_vec = VecDeque::new();
-        nested_vec.push_back(DataType::SimpleString("ok".to_string()));
-        nested_vec.push_back(DataType::SimpleString("test".to_string()));
+        nested_vec.push_back(DataType::SimpleString(Bytes::from_static(b"OK")));
+        nested_vec.push_back(DataType::SimpleString(Bytes::from_static(b"TEST")));
         let nested_arr = DataType::Array(nested_vec);
         let mut outer_vec = VecDeque::new();
         outer_vec.push_back(nested_arr);
-        outer_vec.push_back(DataType::BulkString("test".to_string()));
+        outer_vec.push_back(DataType::BulkString(Bytes::from_static(b"TEST")));
         let expected = DataType::Array(outer_vec);
         let actual = parser.parse_array().unwrap();
         assert_eq!(expected, actual)
     }
 
+    #[test]
+    fn test_deep_nesting_returns_err() {
+        let mut data = b"*1\r\n".repeat(200_000);
+        data.extend_from_slice(b"$1\r\na\r\n");
+        assert!(matches!(
+            Parser::new(&data).parse(),
+            Err(RESPError::InvalidData)
+        ));
+        // nesting up to the limit is fine
+        let mut data = b"*1\r\n".repeat(MAX_DEPTH - 1);
+        data.extend_from_slice(b"$1\r\na\r\n");
+        let mut parser = Parser::new(&data);
+        assert!(parser.parse().is_ok());
+        assert_eq!(data.len(), parser.position());
+    }
+
     #[test]
     fn test_parse_empty() {
         let data = b"$0\r\n\r\n";
         let mut parser = Parser::new(data);
-        let expected = DataType::BulkString("".to_string());
+        let expected = DataType::BulkString(Bytes::from_static(b""));
+        let actual = parser.parse().unwrap();
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn test_parse_binary_bulk_str() {
+        let data = b"$6\r\n\x00\xff\r\nAb\r\n";
+        let mut parser = Parser::new(data);
+        let expected = DataType::BulkString(Bytes::from_static(b"\x00\xff\r\nAb"));
         let actual = parser.parse().unwrap();
         assert_eq!(expected, actual)
     }
@@ -249,8 +606,297 @@ mod tests {
     fn test_bulk_str_parse_without_end_crlf_returns_store_file() {
         let data = b"$16\r\nstorefilecontent";
         let mut parser = Parser::new(data);
-        let expected = DataType::StoreFile("storefilecontent".to_string());
-        let actual = parser.parse().unwrap();
+        let expected = DataType::StoreFile(Bytes::from_static(b"storefilecontent"));
+        let actual = parser.parse_store_file().unwrap();
         assert_eq!(expected, actual)
     }
+
+    #[test]
+    fn test_partial_frames_are_incomplete() {
+        let data = b"*2\r\n$3\r\nGET\r\n$5\r\nmykey\r\n";
+        for end in 0..data.len() {
+            let mut parser = Parser::new(&data[..end]);
+            assert!(matches!(parser.parse(), Err(RESPError::Incomplete)));
+        }
+        let mut parser = Parser::new(data);
+        assert!(parser.parse().is_ok());
+        assert_eq!(parser.position(), data.len());
+    }
+
+    #[test]
+    fn test_parse_stops_at_frame_boundary() {
+        let data = b"+OK\r\n$4\r\ntest\r\n";
+        let mut parser = Parser::new(data);
+        parser.parse().unwrap();
+        assert_eq!(parser.position(), 5);
+        let expected = DataType::BulkString(Bytes::from_static(b"test"));
+        assert_eq!(expected, parser.parse().unwrap());
+    }
+
+    #[test]
+    fn test_large_bulk_str() {
+        let payload = "x".repeat(64 * 1024);
+        let data = format!("${}\r\n{}\r\n", payload.len(), payload);
+        let mut parser = Parser::new(data.as_bytes());
+        let expected = DataType::BulkString(Bytes::from(payload));
+        assert_eq!(expected, parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_simple_err() {
+        let data = b"-ERR unknown command\r\n";
+        let mut parser = Parser::new(data);
+        let expected = DataType::SimpleError(Bytes::from_static(b"ERR unknown command"));
+        assert_eq!(expected, parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_integer() {
+        let data = b":-1234\r\n";
+        let mut parser = Parser::new(data);
+        assert_eq!(DataType::Integer(-1234), parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_invalid_integer_returns_err() {
+        let data = b":12a\r\n";
+        let mut parser = Parser::new(data);
+        assert!(parser.parse().is_err())
+    }
+
+    #[test]
+    fn test_parse_null_bulk_str() {
+        let data = b"$-1\r\n";
+        let mut parser = Parser::new(data);
+        assert_eq!(DataType::NullBulkString, parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_null_array() {
+        let data = b"*-1\r\n";
+        let mut parser = Parser::new(data);
+        assert_eq!(DataType::NullArray, parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_negative_len_returns_err() {
+        let data = b"$-2\r\n";
+        let mut parser = Parser::new(data);
+        assert!(parser.parse().is_err())
+    }
+
+    #[test]
+    fn test_round_trip_resp2() {
+        round_trip(DataType::SimpleString(Bytes::from_static(b"OK")));
+        round_trip(DataType::SimpleError(Bytes::from_static(
+            b"ERR syntax error",
+        )));
+        round_trip(DataType::Integer(0));
+        round_trip(DataType::Integer(i64::MIN));
+        round_trip(DataType::Integer(i64::MAX));
+        round_trip(DataType::BulkString(Bytes::from_static(b"\x00bin\r\nary")));
+        round_trip(DataType::BulkString(Bytes::new()));
+        round_trip(DataType::NullBulkString);
+        round_trip(DataType::NullArray);
+        round_trip(DataType::Array(VecDeque::new()));
+    }
+
+    #[test]
+    fn test_round_trip_nested_array() {
+        let mut inner = VecDeque::new();
+        inner.push_back(DataType::Integer(42));
+        inner.push_back(DataType::NullBulkString);
+        inner.push_back(DataType::SimpleError(Bytes::from_static(b"WRONGTYPE")));
+        let mut outer = VecDeque::new();
+        outer.push_back(DataType::Array(inner));
+        outer.push_back(DataType::NullArray);
+        outer.push_back(DataType::BulkString(Bytes::from_static(b"test")));
+        round_trip(DataType::Array(outer));
+    }
+
+    #[test]
+    fn test_round_trip_store_file() {
+        let data = DataType::StoreFile(Bytes::from_static(b"REDIS0011\xfa"));
+        let serialized = Serializer::serialize_data(&data);
+        let mut parser = Parser::new(&serialized);
+        assert_eq!(data, parser.parse_store_file().unwrap());
+    }
+
+    #[test]
+    fn test_parse_resp3_scalars() {
+        let data =
+            b"_\r\n#t\r\n#f\r\n,3.25\r\n,-inf\r\n(-3492890328409238509324850943850943825024385\r\n";
+        let mut parser = Parser::new(data);
+        assert_eq!(DataType::Null, parser.parse().unwrap());
+        assert_eq!(DataType::Boolean(true), parser.parse().unwrap());
+        assert_eq!(DataType::Boolean(false), parser.parse().unwrap());
+        assert_eq!(DataType::Double(3.25), parser.parse().unwrap());
+        assert_eq!(DataType::Double(f64::NEG_INFINITY), parser.parse().unwrap());
+        let expected = DataType::BigNumber(Bytes::from_static(
+            b"-3492890328409238509324850943850943825024385",
+        ));
+        assert_eq!(expected, parser.parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_verbatim_str() {
+        let data = b"=15\r\ntxt:Some string\r\n";
+        let mut parser = Parser::new(data);
+        let expected = DataType::VerbatimString(
+            Bytes::from_static(b"txt"),
+            Bytes::from_static(b"Some string"),
+        );
+        assert_eq!(expected, parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_bulk_err() {
+        let data = b"!21\r\nSYNTAX invalid syntax\r\n";
+        let mut parser = Parser::new(data);
+        let expected = DataType::BulkError(Bytes::from_static(b"SYNTAX invalid syntax"));
+        assert_eq!(expected, parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_map() {
+        let data = b"%2\r\n+first\r\n:1\r\n+second\r\n:2\r\n";
+        let mut parser = Parser::new(data);
+        let mut expected = VecDeque::new();
+        expected.push_back((DataType::simple_str("first"), DataType::Integer(1)));
+        expected.push_back((DataType::simple_str("second"), DataType::Integer(2)));
+        assert_eq!(DataType::Map(expected), parser.parse().unwrap())
+    }
+
+    #[test]
+    fn test_parse_attribute() {
+        let data = b"|1\r\n+key-popularity\r\n%1\r\n$1\r\na\r\n,0.1923\r\n*1\r\n:2039123\r\n";
+        let mut parser = Parser::new(data);
+        let mut popularity = VecDeque::new();
+        popularity.push_back((bulk(b"a"), DataType::Double(0.1923)));
+        let mut attributes = VecDeque::new();
+        attributes.push_back((
+            DataType::simple_str("key-popularity"),
+            DataType::Map(popularity),
+        ));
+        let mut reply = VecDeque::new();
+        reply.push_back(DataType::Integer(2039123));
+        let expected = DataType::Attribute(attributes, Box::new(DataType::Array(reply)));
+        assert_eq!(expected, parser.parse().unwrap());
+        assert_eq!(parser.position(), data.len());
+    }
+
+    #[test]
+    fn test_partial_resp3_frames_are_incomplete() {
+        let data = b"%1\r\n+key\r\n~2\r\n#t\r\n,1.5\r\n";
+        for end in 0..data.len() {
+            let mut parser = Parser::new(&data[..end]);
+            assert!(matches!(parser.parse(), Err(RESPError::Incomplete)));
+        }
+    }
+
+    #[test]
+    fn test_invalid_resp3_returns_err() {
+        assert!(Parser::new(b"#x\r\n").parse().is_err());
+        assert!(Parser::new(b",1.2.3\r\n").parse().is_err());
+        assert!(Parser::new(b"(12a\r\n").parse().is_err());
+        assert!(Parser::new(b"=2\r\nab\r\n").parse().is_err());
+    }
+
+    #[test]
+    fn test_round_trip_resp3() {
+        round_trip_resp3(DataType::Null);
+        round_trip_resp3(DataType::Boolean(true));
+        round_trip_resp3(DataType::Double(-1.5));
+        round_trip_resp3(DataType::Double(1e300));
+        round_trip_resp3(DataType::Double(f64::INFINITY));
+        round_trip_resp3(DataType::BigNumber(Bytes::from_static(
+            b"1234567899876543210",
+        )));
+        round_trip_resp3(DataType::BulkError(Bytes::from_static(b"ERR\r\nbulk")));
+        round_trip_resp3(DataType::VerbatimString(
+            Bytes::from_static(b"txt"),
+            Bytes::from_static(b"a\r\nb"),
+        ));
+        let mut pairs = VecDeque::new();
+        pairs.push_back((bulk(b"k"), DataType::Set(VecDeque::from([bulk(b"v")]))));
+        round_trip_resp3(DataType::Map(pairs.clone()));
+        round_trip_resp3(DataType::Push(VecDeque::from([bulk(b"message")])));
+        round_trip_resp3(DataType::Attribute(pairs, Box::new(DataType::Integer(1))));
+    }
+
+    #[test]
+    fn test_resp3_downgrades_to_resp2() {
+        let mut pairs = VecDeque::new();
+        pairs.push_back((bulk(b"k"), DataType::Boolean(true)));
+        let map = Serializer::serialize(&DataType::Map(pairs), Protocol::Resp2);
+        assert_eq!(b"*2\r\n$1\r\nk\r\n:1\r\n".to_vec(), map);
+        let null = Serializer::serialize(&DataType::Null, Protocol::Resp2);
+        assert_eq!(b"$-1\r\n".to_vec(), null);
+        let double = Serializer::serialize(&DataType::Double(2.5), Protocol::Resp2);
+        assert_eq!(b"$3\r\n2.5\r\n".to_vec(), double);
+        let null = Serializer::serialize(&DataType::NullBulkString, Protocol::Resp3);
+        assert_eq!(b"_\r\n".to_vec(), null);
+    }
+
+    fn inline(data: &[u8]) -> std::vec::Vec<Bytes> {
+        match Parser::new(data).parse().unwrap() {
+            DataType::Array(args) => args
+                .into_iter()
+                .map(|a| a.try_to_bytes().unwrap())
+                .collect(),
+            _ => panic!("inline commands should parse to an array"),
+        }
+    }
+
+    #[test]
+    fn test_parse_inline() {
+        let args = inline(b"SET foo  bar\r\n");
+        assert_eq!(args, [&b"SET"[..], b"foo", b"bar"]);
+        // netcat may send a bare newline
+        let args = inline(b"PING\n");
+        assert_eq!(args, [&b"PING"[..]]);
+    }
+
+    #[test]
+    fn test_parse_inline_quoted() {
+        let args = inline(b"SET \"hello world\" 'it\\'s'\r\n");
+        assert_eq!(args, [&b"SET"[..], b"hello world", b"it's"]);
+        let args = inline(b"SET k \"a\\tb\\x41\\x4g\\\"\"\r\n");
+        assert_eq!(args, [&b"SET"[..], b"k", b"a\tbAx4g\""]);
+        let args = inline(b"ECHO \"\"\r\n");
+        assert_eq!(args, [&b"ECHO"[..], b""]);
+    }
+
+    #[test]
+    fn test_parse_inline_empty_line() {
+        assert!(inline(b"\r\n").is_empty());
+        assert!(inline(b"   \n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_inline_unbalanced_quotes_returns_err() {
+        assert!(Parser::new(b"SET \"foo\r\n").parse().is_err());
+        assert!(Parser::new(b"SET 'foo\r\n").parse().is_err());
+        assert!(Parser::new(b"SET \"foo\"bar\r\n").parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_inline_inside_aggregate_returns_err() {
+        let data = b"*2\r\nSET foo\r\n$3\r\nbar\r\n";
+        assert!(matches!(
+            Parser::new(data).parse(),
+            Err(RESPError::InvalidType)
+        ));
+    }
+
+    #[test]
+    fn test_parse_inline_without_newline_is_incomplete() {
+        let mut parser = Parser::new(b"SET foo bar");
+        assert!(matches!(parser.parse(), Err(RESPError::Incomplete)));
+        let data = vec![b'a'; 64 * 1024 + 1];
+        assert!(matches!(
+            Parser::new(&data).parse(),
+            Err(RESPError::InvalidData)
+        ));
+    }
 }
@@ -1,29 +1,46 @@
-use super::data::DataType;
+use std::collections::VecDeque;
+
+use super::data::{DataType, Protocol};
 
 #[derive(Debug)]
 pub struct Serializer {}
 
 impl Serializer {
-    pub fn to_simple_str(str: &str) -> String {
-        format!("+{}\r\n", str)
+    pub fn to_simple_str(str: &str) -> Vec<u8> {
+        format!("+{}\r\n", str).into_bytes()
     }
 
-    pub fn to_bulk_str(str: &str) -> String {
-        format!("${}\r\n{}\r\n", str.len(), str)
+    pub fn to_bulk_str(bytes: &[u8]) -> Vec<u8> {
+        let mut buffer = format!("${}\r\n", bytes.len()).into_bytes();
+        buffer.extend_from_slice(bytes);
+        buffer.extend_from_slice(b"\r\n");
+        buffer
     }
 
-    pub fn to_arr(strs: Vec<&str>) -> String {
-        let mut buffer = String::with_capacity(128);
-        buffer.push('*');
-        buffer.push_str(&strs.len().to_string());
-        buffer.push_str("\r\n");
+    pub fn to_arr<T: AsRef<[u8]>>(strs: Vec<T>) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(128);
+        buffer.push(b'*');
+        buffer.extend_from_slice(strs.len().to_string().as_bytes());
+        buffer.extend_from_slice(b"\r\n");
         strs.iter()
-            .for_each(|s| buffer.push_str(&Self::to_bulk_str(s)));
+            .for_each(|s| buffer.extend_from_slice(&Self::to_bulk_str(s.as_ref())));
         buffer
     }
 
-    pub fn to_simple_err(str: &str) -> String {
-        format!("-ERR {}\r\n", str)
+    pub fn to_simple_err(str: &str) -> Vec<u8> {
+        format!("-ERR {}\r\n", str).into_bytes()
+    }
+
+    pub fn to_integer(int: i64) -> Vec<u8> {
+        format!(":{}\r\n", int).into_bytes()
+    }
+
+    pub fn to_null_bulk_str() -> Vec<u8> {
+        b"$-1\r\n".to_vec()
+    }
+
+    pub fn to_null_arr() -> Vec<u8> {
+        b"*-1\r\n".to_vec()
     }
 
     pub fn serialize_store_file(mut bytes: Vec<u8>) -> Vec<u8> {
@@ -32,22 +49,150 @@ impl Serializer {
         buffer
     }
 
-    pub fn serialize_data(dt: DataType) -> String {
+    #[inline]
+    fn write_line(buffer: &mut Vec<u8>, prefix: u8, line: &[u8]) {
+        buffer.push(prefix);
+        buffer.extend_from_slice(line);
+        buffer.extend_from_slice(b"\r\n");
+    }
+
+    // Simple strings and errors can't contain a CR or LF
+    #[inline]
+    fn write_simple(buffer: &mut Vec<u8>, prefix: u8, line: &[u8]) {
+        buffer.push(prefix);
+        buffer.extend(line.iter().map(|b| match b {
+            b'\r' | b'\n' => b' ',
+            b => *b,
+        }));
+        buffer.extend_from_slice(b"\r\n");
+    }
+
+    // Shortest representation that parses back to the same value. Exponents are only used for very large
+    // or very small magnitudes.
+    pub fn format_double(double: f64) -> String {
+        if double.is_nan() {
+            "nan".to_string()
+        } else if double.is_infinite() {
+            match double > 0.0 {
+                true => "inf".to_string(),
+                false => "-inf".to_string(),
+            }
+        } else if double == 0.0 || (1e-5..1e17).contains(&double.abs()) {
+            format!("{}", double)
+        } else {
+            format!("{:e}", double)
+        }
+    }
+
+    fn write_pairs(
+        buffer: &mut Vec<u8>,
+        prefix: u8,
+        pairs: &VecDeque<(DataType, DataType)>,
+        proto: Protocol,
+    ) {
+        // RESP2 has no maps -> they go out as a flat array of alternating keys and values
+        let len = match proto {
+            Protocol::Resp2 => pairs.len() * 2,
+            Protocol::Resp3 => pairs.len(),
+        };
+        Self::write_line(buffer, prefix, len.to_string().as_bytes());
+        for (k, v) in pairs {
+            Self::write_data(buffer, k, proto);
+            Self::write_data(buffer, v, proto);
+        }
+    }
+
+    fn write_elements(buffer: &mut Vec<u8>, prefix: u8, vec: &VecDeque<DataType>, proto: Protocol) {
+        Self::write_line(buffer, prefix, vec.len().to_string().as_bytes());
+        vec.iter()
+            .for_each(|dt| Self::write_data(buffer, dt, proto));
+    }
+
+    fn write_bulk(buffer: &mut Vec<u8>, prefix: u8, bytes: &[u8]) {
+        Self::write_line(buffer, prefix, bytes.len().to_string().as_bytes());
+        buffer.extend_from_slice(bytes);
+        buffer.extend_from_slice(b"\r\n");
+    }
+
+    // Types that only exist in RESP3 get downgraded to their closest RESP2 equivalent
+    fn write_data(buffer: &mut Vec<u8>, dt: &DataType, proto: Protocol) {
+        let resp3 = proto == Protocol::Resp3;
         match dt {
-            DataType::SimpleString(s) => Serializer::to_simple_str(&s),
-            DataType::BulkString(s) => Serializer::to_bulk_str(&s),
-            DataType::Array(vec) => {
-                let mut buff = Vec::with_capacity(8);
-                for dt in vec {
-                    let serial = Serializer::serialize_data(dt);
-                    buff.push(serial);
+            DataType::SimpleString(s) => Self::write_simple(buffer, b'+', s),
+            DataType::SimpleError(s) => Self::write_simple(buffer, b'-', s),
+            DataType::Integer(i) => Self::write_line(buffer, b':', i.to_string().as_bytes()),
+            DataType::BulkString(s) => Self::write_bulk(buffer, b'$', s),
+            DataType::Array(vec) => Self::write_elements(buffer, b'*', vec, proto),
+            DataType::StoreFile(s) => {
+                Self::write_line(buffer, b'$', s.len().to_string().as_bytes());
+                buffer.extend_from_slice(s);
+            }
+            // RESP3 has a single null type
+            DataType::NullBulkString | DataType::NullArray | DataType::Null if resp3 => {
+                buffer.extend_from_slice(b"_\r\n")
+            }
+            DataType::NullBulkString | DataType::Null => buffer.extend_from_slice(b"$-1\r\n"),
+            DataType::NullArray => buffer.extend_from_slice(b"*-1\r\n"),
+            DataType::BulkError(s) => match resp3 {
+                true => Self::write_bulk(buffer, b'!', s),
+                false => Self::write_simple(buffer, b'-', s),
+            },
+            DataType::Boolean(b) => match resp3 {
+                true => Self::write_line(buffer, b'#', if *b { b"t" } else { b"f" }),
+                false => Self::write_line(buffer, b':', if *b { b"1" } else { b"0" }),
+            },
+            DataType::Double(d) => {
+                let double = Self::format_double(*d);
+                match resp3 {
+                    true => Self::write_line(buffer, b',', double.as_bytes()),
+                    false => Self::write_bulk(buffer, b'$', double.as_bytes()),
+                }
+            }
+            DataType::BigNumber(n) => match resp3 {
+                true => Self::write_line(buffer, b'(', n),
+                false => Self::write_bulk(buffer, b'$', n),
+            },
+            DataType::VerbatimString(format, s) => match resp3 {
+                true => {
+                    Self::write_line(buffer, b'=', (s.len() + 4).to_string().as_bytes());
+                    buffer.extend_from_slice(format);
+                    buffer.push(b':');
+                    buffer.extend_from_slice(s);
+                    buffer.extend_from_slice(b"\r\n");
+                }
+                false => Self::write_bulk(buffer, b'$', s),
+            },
+            DataType::Map(pairs) => match resp3 {
+                true => Self::write_pairs(buffer, b'%', pairs, proto),
+                false => Self::write_pairs(buffer, b'*', pairs, proto),
+            },
+            DataType::Set(vec) => match resp3 {
+                true => Self::write_elements(buffer, b'~', vec, proto),
+                false => Self::write_elements(buffer, b'*', vec, proto),
+            },
+            DataType::Push(vec) => match resp3 {
+                true => Self::write_elements(buffer, b'>', vec, proto),
+                false => Self::write_elements(buffer, b'*', vec, proto),
+            },
+            DataType::Attribute(attributes, data) => {
+                // RESP2 clients don't get the attributes at all
+                if resp3 {
+                    Self::write_pairs(buffer, b'|', attributes, proto);
                 }
-                // more nonsense
-                Serializer::to_arr(buff.iter().map(|s| s.as_str()).collect())
+                Self::write_data(buffer, data, proto);
             }
-            _ => unimplemented!(),
         }
     }
+
+    pub fn serialize(dt: &DataType, proto: Protocol) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(64);
+        Self::write_data(&mut buffer, dt, proto);
+        buffer
+    }
+
+    pub fn serialize_data(dt: &DataType) -> Vec<u8> {
+        Self::serialize(dt, Protocol::Resp2)
+    }
 }
 
 mod tests {
@@ -57,17 +202,43 @@ mod tests {
 
     #[test]
     fn test_to_simple_str() {
-        assert_eq!("+test\r\n", Serializer::to_simple_str("test"))
+        assert_eq!(b"+test\r\n".to_vec(), Serializer::to_simple_str("test"))
     }
 
     #[test]
     fn test_to_bulk_str() {
-        assert_eq!("$4\r\ntest\r\n", Serializer::to_bulk_str("test"))
+        assert_eq!(b"$4\r\ntest\r\n".to_vec(), Serializer::to_bulk_str(b"test"))
+    }
+
+    #[test]
+    fn test_to_bulk_str_binary() {
+        let bytes = b"\x00\xffA\r\n";
+        assert_eq!(
+            b"$5\r\n\x00\xffA\r\n\r\n".to_vec(),
+            Serializer::to_bulk_str(bytes)
+        )
     }
 
     #[test]
     fn test_to_arr() {
         let arr = Vec::from(["test"]);
-        assert_eq!("*1\r\n$4\r\ntest\r\n", Serializer::to_arr(arr))
+        assert_eq!(b"*1\r\n$4\r\ntest\r\n".to_vec(), Serializer::to_arr(arr))
+    }
+
+    #[test]
+    fn test_to_integer() {
+        assert_eq!(b":-42\r\n".to_vec(), Serializer::to_integer(-42))
+    }
+
+    #[test]
+    fn test_serialize_simple_err_strips_newlines() {
+        use super::DataType;
+        use bytes::Bytes;
+
+        let err = DataType::SimpleError(Bytes::from_static(b"ERR bad\r\nthing"));
+        assert_eq!(
+            b"-ERR bad  thing\r\n".to_vec(),
+            Serializer::serialize_data(&err)
+        )
     }
 }
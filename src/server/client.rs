@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use bytes::Bytes;
+
+use crate::resp::data::Protocol;
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+// Per-connection state. Each connection task owns its own Client.
+#[derive(Debug)]
+pub struct Client {
+    pub id: u64,
+    pub name: Option<Bytes>,
+    pub protocol: Protocol,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
+            name: None,
+            protocol: Protocol::default(),
+        }
+    }
+}
@@ -0,0 +1,1218 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+
+use super::string::{check_len, get_string};
+use super::{
+    next_arg, parse_int, to_lowercase, Args, CommandFlags, CommandHandler, CommandResult, Context,
+    KeySpec, R,
+};
+
+// Bits are numbered from the most significant bit of the first byte, like redis. A string is at most 512MB,
+// so the last bit is 2^32 - 1.
+const MAX_BITS: u64 = 1 << 32;
+
+fn get_bit(bytes: &[u8], pos: u64) -> bool {
+    match bytes.get((pos / 8) as usize) {
+        Some(byte) => byte >> (7 - pos % 8) & 1 == 1,
+        None => false,
+    }
+}
+
+// The string has to be long enough already
+fn set_bit(bytes: &mut [u8], pos: u64, bit: bool) {
+    let mask = 1 << (7 - pos % 8);
+    let byte = &mut bytes[(pos / 8) as usize];
+    match bit {
+        true => *byte |= mask,
+        false => *byte &= !mask,
+    }
+}
+
+fn parse_offset(arg: &[u8]) -> R<u64> {
+    parse_int::<u64>(arg)
+        .ok()
+        .filter(|offset| *offset < MAX_BITS)
+        .ok_or_else(|| {
+            CommandError::CommandFailed(
+                "ERR bit offset is not an integer or out of range".to_string(),
+            )
+        })
+}
+
+// Makes sure there's a byte for bit `pos`, zero padding the string if needed
+fn grow_to_bit(bytes: &mut Vec<u8>, pos: u64) -> R<()> {
+    let len = (pos / 8) as usize + 1;
+    check_len(len)?;
+    if bytes.len() < len {
+        bytes.resize(len, 0);
+    }
+    Ok(())
+}
+
+// Turns a start and end (both inclusive, negative -> counted back from the end) into a range of 0..len.
+// None if it's empty.
+fn clamp_range(start: i64, end: i64, len: i64) -> Option<(u64, u64)> {
+    if start < 0 && end < 0 && start > end {
+        return None;
+    }
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let end = if end < 0 { len + end } else { end }.max(0).min(len - 1);
+    (start <= end).then_some((start as u64, end as u64))
+}
+
+// The optional [start end [BYTE | BIT]] of BITCOUNT and BITPOS -> the range of bits, both inclusive
+fn bit_range(
+    bytes: &[u8],
+    start: Option<Bytes>,
+    end: Option<Bytes>,
+    unit: Option<Bytes>,
+) -> R<Option<(u64, u64)>> {
+    let in_bits = match unit.map(|unit| to_lowercase(&unit)).as_deref() {
+        None | Some("byte") => false,
+        Some("bit") => true,
+        Some(_) => return Err(CommandError::InvalidOption),
+    };
+    let start = match start {
+        Some(start) => parse_int::<i64>(&start)?,
+        None => 0,
+    };
+    let end = match end {
+        Some(end) => parse_int::<i64>(&end)?,
+        None => -1,
+    };
+    let range = match in_bits {
+        true => clamp_range(start, end, bytes.len() as i64 * 8),
+        false => clamp_range(start, end, bytes.len() as i64).map(|(s, e)| (s * 8, e * 8 + 7)),
+    };
+    Ok(range)
+}
+
+// The set bits in positions from..=to
+fn count_bits(bytes: &[u8], from: u64, to: u64) -> u64 {
+    let (first, last) = ((from / 8) as usize, (to / 8) as usize);
+    let head = 0xffu8 >> (from % 8);
+    let tail = 0xffu8 << (7 - to % 8);
+    if first == last {
+        return (bytes[first] & head & tail).count_ones() as u64;
+    }
+    let middle = bytes[first + 1..last]
+        .iter()
+        .map(|b| b.count_ones() as u64)
+        .sum::<u64>();
+    (bytes[first] & head).count_ones() as u64 + middle + (bytes[last] & tail).count_ones() as u64
+}
+
+// The first position in from..=to holding `bit`
+fn find_bit(bytes: &[u8], bit: bool, from: u64, to: u64) -> Option<u64> {
+    // whole bytes with none of the bits we're after get skipped
+    let skip = match bit {
+        true => 0x00,
+        false => 0xff,
+    };
+    let mut pos = from;
+    while pos <= to {
+        if pos & 7 == 0 && pos + 7 <= to && bytes[(pos / 8) as usize] == skip {
+            pos += 8;
+            continue;
+        }
+        if get_bit(bytes, pos) == bit {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
+// SETBIT key offset value
+pub struct SetBit;
+
+impl CommandHandler for SetBit {
+    fn name(&self) -> &'static str {
+        "setbit"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets or clears the bit at offset of the string value. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let offset = parse_offset(&next_arg(&mut args, self.name())?)?;
+        let bit = match next_arg(&mut args, self.name())?.as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => {
+                return Err(CommandError::CommandFailed(
+                    "ERR bit is not an integer or out of range".to_string(),
+                ))
+            }
+        };
+        let store = &mut ctx.server.store;
+        let mut bytes = get_string(store, &key)?.map_or_else(Vec::new, |v| v.to_vec());
+        grow_to_bit(&mut bytes, offset)?;
+        let old = get_bit(&bytes, offset);
+        set_bit(&mut bytes, offset, bit);
+        store.kv_store.try_write(key, Bytes::from(bytes))?;
+        Ok(CommandResult::Reply(DataType::Integer(old as i64)))
+    }
+}
+
+// GETBIT key offset
+pub struct GetBit;
+
+impl CommandHandler for GetBit {
+    fn name(&self) -> &'static str {
+        "getbit"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns a bit value by offset."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let offset = parse_offset(&next_arg(&mut args, self.name())?)?;
+        let bytes = get_string(&ctx.server.store, &key)?.unwrap_or_default();
+        let bit = get_bit(&bytes, offset);
+        Ok(CommandResult::Reply(DataType::Integer(bit as i64)))
+    }
+}
+
+// BITCOUNT key [start end [BYTE | BIT]]
+pub struct BitCount;
+
+impl CommandHandler for BitCount {
+    fn name(&self) -> &'static str {
+        "bitcount"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Counts the number of set bits (population counting) in a string."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        // a start needs an end
+        if args.len() == 1 || args.len() > 3 {
+            return Err(CommandError::InvalidOption);
+        }
+        let bytes = get_string(&ctx.server.store, &key)?.unwrap_or_default();
+        let range = bit_range(&bytes, args.pop_front(), args.pop_front(), args.pop_front())?;
+        let count = range.map_or(0, |(from, to)| count_bits(&bytes, from, to));
+        Ok(CommandResult::Reply(DataType::Integer(count as i64)))
+    }
+}
+
+// BITPOS key bit [start [end [BYTE | BIT]]]
+pub struct BitPos;
+
+impl CommandHandler for BitPos {
+    fn name(&self) -> &'static str {
+        "bitpos"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Finds the first set (1) or clear (0) bit in a string."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let bit = match next_arg(&mut args, self.name())?.as_ref() {
+            b"0" => false,
+            b"1" => true,
+            _ => {
+                return Err(CommandError::CommandFailed(
+                    "ERR The bit argument must be 1 or 0.".to_string(),
+                ))
+            }
+        };
+        if args.len() > 3 {
+            return Err(CommandError::InvalidOption);
+        }
+        let end_given = args.len() >= 2;
+        let bytes = get_string(&ctx.server.store, &key)?.unwrap_or_default();
+        let range = bit_range(&bytes, args.pop_front(), args.pop_front(), args.pop_front())?;
+        let pos = match range {
+            // a missing (or empty) string has no set bits, and is all clear bits
+            _ if bytes.is_empty() => match bit {
+                true => -1,
+                false => 0,
+            },
+            None => -1,
+            Some((from, to)) => match find_bit(&bytes, bit, from, to) {
+                Some(pos) => pos as i64,
+                // without an end, the string counts as padded with clear bits
+                None if !bit && !end_given => to as i64 + 1,
+                None => -1,
+            },
+        };
+        Ok(CommandResult::Reply(DataType::Integer(pos)))
+    }
+}
+
+// BITOP <AND | OR | XOR | NOT> destkey key [key ...] -> shorter strings count as padded with zero bytes
+pub struct BitOp;
+
+impl CommandHandler for BitOp {
+    fn name(&self) -> &'static str {
+        "bitop"
+    }
+
+    fn arity(&self) -> i64 {
+        -4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Performs bitwise operations on multiple strings, and stores the result."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(2, -1, 1)
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let op = to_lowercase(&next_arg(&mut args, self.name())?);
+        let dest = next_arg(&mut args, self.name())?;
+        // None -> NOT
+        let op: Option<fn(u8, u8) -> u8> = match op.as_str() {
+            "and" => Some(|a, b| a & b),
+            "or" => Some(|a, b| a | b),
+            "xor" => Some(|a, b| a ^ b),
+            "not" if args.len() == 1 => None,
+            "not" => {
+                return Err(CommandError::CommandFailed(
+                    "ERR BITOP NOT must be called with a single source key.".to_string(),
+                ))
+            }
+            _ => return Err(CommandError::InvalidOption),
+        };
+        let store = &mut ctx.server.store;
+        let sources = args
+            .iter()
+            .map(|key| get_string(store, key).map(Option::unwrap_or_default))
+            .collect::<R<Vec<Bytes>>>()?;
+        let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+        let result = (0..len)
+            .map(|i| {
+                let byte = |s: &Bytes| s.get(i).copied().unwrap_or(0);
+                let first = byte(&sources[0]);
+                match op {
+                    Some(op) => sources[1..].iter().fold(first, |acc, s| op(acc, byte(s))),
+                    None => !first,
+                }
+            })
+            .collect::<Vec<u8>>();
+        // the destination is replaced whatever it held, and an empty result just deletes it
+        store.remove(&dest);
+        if !result.is_empty() {
+            store.kv_store.try_write(dest, Bytes::from(result))?;
+        }
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// A BITFIELD type, e.g. i5 or u16
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FieldType {
+    signed: bool,
+    bits: u32,
+}
+
+impl FieldType {
+    fn parse(arg: &[u8]) -> R<Self> {
+        let invalid = || {
+            CommandError::CommandFailed(
+                "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is."
+                    .to_string(),
+            )
+        };
+        let (signed, max_bits) = match arg.first() {
+            Some(b'i') => (true, 64),
+            Some(b'u') => (false, 63),
+            _ => return Err(invalid()),
+        };
+        match parse_int::<u32>(&arg[1..]) {
+            Ok(bits) if (1..=max_bits).contains(&bits) => Ok(Self { signed, bits }),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn min(&self) -> i128 {
+        match self.signed {
+            true => -(1 << (self.bits - 1)),
+            false => 0,
+        }
+    }
+
+    fn max(&self) -> i128 {
+        match self.signed {
+            true => (1 << (self.bits - 1)) - 1,
+            false => (1 << self.bits) - 1,
+        }
+    }
+
+    // A field offset is a bit offset, or with a '#' prefix, a multiple of the type's width
+    fn parse_offset(&self, arg: &[u8]) -> R<u64> {
+        let offset = match arg.strip_prefix(b"#") {
+            Some(n) => parse_int::<u64>(n)
+                .ok()
+                .and_then(|n| n.checked_mul(self.bits as u64)),
+            None => parse_int::<u64>(arg).ok(),
+        };
+        offset
+            .filter(|offset| offset + (self.bits as u64) <= MAX_BITS)
+            .ok_or_else(|| {
+                CommandError::CommandFailed(
+                    "ERR bit offset is not an integer or out of range".to_string(),
+                )
+            })
+    }
+
+    fn read(&self, bytes: &[u8], offset: u64) -> i128 {
+        let raw =
+            (0..self.bits as u64).fold(0u64, |acc, i| acc << 1 | get_bit(bytes, offset + i) as u64);
+        // sign extend
+        match self.signed && raw >> (self.bits - 1) & 1 == 1 {
+            true => raw as i128 - (1 << self.bits),
+            false => raw as i128,
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: u64, val: i128) {
+        for i in 0..self.bits {
+            let bit = val >> (self.bits - 1 - i) & 1 == 1;
+            set_bit(bytes, offset + i as u64, bit);
+        }
+    }
+
+    // Fits `val` into the type, going by the overflow mode. None if it doesn't fit, and the mode is FAIL.
+    fn fit(&self, val: i128, overflow: Overflow) -> Option<i128> {
+        if (self.min()..=self.max()).contains(&val) {
+            return Some(val);
+        }
+        match overflow {
+            Overflow::Fail => None,
+            Overflow::Sat => Some(val.clamp(self.min(), self.max())),
+            Overflow::Wrap => {
+                let wrapped = val.rem_euclid(1 << self.bits);
+                match wrapped > self.max() {
+                    true => Some(wrapped - (1 << self.bits)),
+                    false => Some(wrapped),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+#[derive(Debug)]
+enum FieldOp {
+    Get(FieldType, u64),
+    Set(FieldType, u64, i128, Overflow),
+    IncrBy(FieldType, u64, i128, Overflow),
+}
+
+impl FieldOp {
+    // The byte length a write needs the string to be
+    fn needs_len(&self) -> Option<u64> {
+        match self {
+            Self::Get(..) => None,
+            Self::Set(ty, offset, ..) | Self::IncrBy(ty, offset, ..) => {
+                Some((offset + ty.bits as u64).div_ceil(8))
+            }
+        }
+    }
+}
+
+// GET, SET and INCRBY, each going by the OVERFLOW that came before it (WRAP by default)
+fn parse_field_ops(
+    handler: &dyn CommandHandler,
+    mut args: Args,
+    read_only: bool,
+) -> R<Vec<FieldOp>> {
+    let mut ops = Vec::new();
+    let mut overflow = Overflow::Wrap;
+    while let Some(sub) = args.pop_front() {
+        let sub = to_lowercase(&sub);
+        if read_only && sub != "get" {
+            return Err(CommandError::CommandFailed(format!(
+                "ERR {} only supports the GET subcommand",
+                handler.name().to_uppercase()
+            )));
+        }
+        let mut arg = || args.pop_front().ok_or(CommandError::InvalidOption);
+        match sub.as_str() {
+            "overflow" => {
+                overflow = match to_lowercase(&arg()?).as_str() {
+                    "wrap" => Overflow::Wrap,
+                    "sat" => Overflow::Sat,
+                    "fail" => Overflow::Fail,
+                    _ => {
+                        return Err(CommandError::CommandFailed(
+                            "ERR Invalid OVERFLOW type specified".to_string(),
+                        ))
+                    }
+                };
+            }
+            "get" | "set" | "incrby" => {
+                let ty = FieldType::parse(&arg()?)?;
+                let offset = ty.parse_offset(&arg()?)?;
+                let op = match sub.as_str() {
+                    "get" => FieldOp::Get(ty, offset),
+                    "set" => {
+                        let val = parse_int::<i64>(&arg()?)?;
+                        // the bits of the value are what's set -> a negative value into an unsigned type is
+                        // a big positive one
+                        let val = match ty.signed {
+                            true => val as i128,
+                            false => val as u64 as i128,
+                        };
+                        FieldOp::Set(ty, offset, val, overflow)
+                    }
+                    _ => FieldOp::IncrBy(ty, offset, parse_int::<i64>(&arg()?)? as i128, overflow),
+                };
+                ops.push(op);
+            }
+            _ => return Err(CommandError::InvalidOption),
+        }
+    }
+    Ok(ops)
+}
+
+// Runs the ops in order -> a reply for each of them, nil for a write that failed with OVERFLOW FAIL
+fn bitfield(ctx: &mut Context, key: Bytes, ops: Vec<FieldOp>) -> R<CommandResult> {
+    let store = &mut ctx.server.store;
+    let mut bytes = get_string(store, &key)?.map_or_else(Vec::new, |v| v.to_vec());
+    let needs_len = ops.iter().filter_map(FieldOp::needs_len).max();
+    if let Some(len) = needs_len {
+        check_len(len as usize)?;
+        if bytes.len() < len as usize {
+            bytes.resize(len as usize, 0);
+        }
+    }
+    let mut replies = VecDeque::with_capacity(ops.len());
+    for op in ops {
+        let reply = match op {
+            FieldOp::Get(ty, offset) => Some(ty.read(&bytes, offset)),
+            FieldOp::Set(ty, offset, val, overflow) => ty.fit(val, overflow).map(|val| {
+                let old = ty.read(&bytes, offset);
+                ty.write(&mut bytes, offset, val);
+                old
+            }),
+            FieldOp::IncrBy(ty, offset, by, overflow) => {
+                let val = ty.read(&bytes, offset) + by;
+                ty.fit(val, overflow)
+                    .inspect(|val| ty.write(&mut bytes, offset, *val))
+            }
+        };
+        replies.push_back(match reply {
+            Some(n) => DataType::Integer(n as i64),
+            None => DataType::NullBulkString,
+        });
+    }
+    if needs_len.is_some() {
+        store.kv_store.try_write(key, Bytes::from(bytes))?;
+    }
+    Ok(CommandResult::Reply(DataType::Array(replies)))
+}
+
+// BITFIELD key [GET encoding offset | [OVERFLOW <WRAP | SAT | FAIL>] <SET encoding offset value |
+//   INCRBY encoding offset increment> [GET encoding offset | [OVERFLOW <WRAP | SAT | FAIL>] ...]]
+pub struct BitField;
+
+impl CommandHandler for BitField {
+    fn name(&self) -> &'static str {
+        "bitfield"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Performs arbitrary bitfield integer operations on strings."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let ops = parse_field_ops(self, args, false)?;
+        bitfield(ctx, key, ops)
+    }
+}
+
+// BITFIELD_RO key [GET encoding offset [GET encoding offset ...]]
+pub struct BitFieldRo;
+
+impl CommandHandler for BitFieldRo {
+    fn name(&self) -> &'static str {
+        "bitfield_ro"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Performs arbitrary read-only bitfield integer operations on strings."
+    }
+
+    fn group(&self) -> &'static str {
+        "bitmap"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let ops = parse_field_ops(self, args, true)?;
+        bitfield(ctx, key, ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::super::tests::{new_server, run, run_err};
+    use super::{FieldType, Overflow};
+    use crate::resp::data::DataType;
+    use crate::server::errors::CommandError;
+
+    fn ints(vals: &[Option<i64>]) -> DataType {
+        DataType::Array(
+            vals.iter()
+                .map(|v| match v {
+                    Some(n) => DataType::Integer(*n),
+                    None => DataType::NullBulkString,
+                })
+                .collect(),
+        )
+    }
+
+    fn int(reply: DataType) -> i64 {
+        match reply {
+            DataType::Integer(n) => n,
+            other => panic!("expected an integer, got {:?}", other),
+        }
+    }
+
+    const OFFSET_ERR: &str = "ERR bit offset is not an integer or out of range";
+    const SYNTAX_ERR: &str = "ERR syntax error";
+
+    #[tokio::test]
+    async fn test_setbit_getbit() {
+        let server = new_server();
+        // SETBIT returns the bit it replaced
+        assert_eq!(
+            0,
+            int(run(&server, &[b"setbit", b"k", b"7", b"1"]).await.unwrap())
+        );
+        assert_eq!(
+            1,
+            int(run(&server, &[b"setbit", b"k", b"7", b"0"]).await.unwrap())
+        );
+        assert_eq!(
+            0,
+            int(run(&server, &[b"setbit", b"k", b"7", b"1"]).await.unwrap())
+        );
+        assert_eq!(
+            1,
+            int(run(&server, &[b"getbit", b"k", b"7"]).await.unwrap())
+        );
+        assert_eq!(
+            0,
+            int(run(&server, &[b"getbit", b"k", b"6"]).await.unwrap())
+        );
+        // bits past the end of the string, or of a missing key, are clear
+        assert_eq!(
+            0,
+            int(run(&server, &[b"getbit", b"k", b"1000"]).await.unwrap())
+        );
+        assert_eq!(
+            0,
+            int(run(&server, &[b"getbit", b"nope", b"0"]).await.unwrap())
+        );
+        assert_eq!(1, int(run(&server, &[b"strlen", b"k"]).await.unwrap()));
+        // setting a bit past the end zero pads the string up to it
+        run(&server, &[b"setbit", b"k", b"100", b"1"])
+            .await
+            .unwrap();
+        assert_eq!(13, int(run(&server, &[b"strlen", b"k"]).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_setbit_getbit_errors() {
+        let server = new_server();
+        assert_eq!(
+            OFFSET_ERR,
+            run_err(&server, &[b"setbit", b"k", b"4294967296", b"1"]).await
+        );
+        assert_eq!(
+            OFFSET_ERR,
+            run_err(&server, &[b"getbit", b"k", b"-1"]).await
+        );
+        assert_eq!(
+            "ERR bit is not an integer or out of range",
+            run_err(&server, &[b"setbit", b"k", b"1", b"2"]).await
+        );
+        // nothing was created along the way
+        assert_eq!(0, int(run(&server, &[b"exists", b"k"]).await.unwrap()));
+
+        run(&server, &[b"rpush", b"list", b"a"]).await.unwrap();
+        assert_eq!(
+            CommandError::WrongType.to_string(),
+            run_err(&server, &[b"setbit", b"list", b"0", b"1"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitcount() {
+        let server = new_server();
+        run(&server, &[b"set", b"foo", b"foobar"]).await.unwrap();
+        assert_eq!(26, int(run(&server, &[b"bitcount", b"foo"]).await.unwrap()));
+        assert_eq!(
+            4,
+            int(run(&server, &[b"bitcount", b"foo", b"0", b"0"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            6,
+            int(run(&server, &[b"bitcount", b"foo", b"1", b"1", b"BYTE"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            17,
+            int(run(&server, &[b"bitcount", b"foo", b"5", b"30", b"BIT"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            7,
+            int(run(&server, &[b"bitcount", b"foo", b"-2", b"-1"])
+                .await
+                .unwrap())
+        );
+        // a backwards range is empty
+        assert_eq!(
+            0,
+            int(run(&server, &[b"bitcount", b"foo", b"-1", b"-2"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(0, int(run(&server, &[b"bitcount", b"nope"]).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_bitcount_errors() {
+        let server = new_server();
+        run(&server, &[b"set", b"foo", b"foobar"]).await.unwrap();
+        // a start needs an end
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(&server, &[b"bitcount", b"foo", b"1"]).await
+        );
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(&server, &[b"bitcount", b"foo", b"0", b"1", b"bits"]).await
+        );
+        assert_eq!(
+            CommandError::NotAnInteger.to_string(),
+            run_err(&server, &[b"bitcount", b"foo", b"a", b"1"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitpos() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"\xff\xf0\x00"])
+            .await
+            .unwrap();
+        assert_eq!(
+            12,
+            int(run(&server, &[b"bitpos", b"k", b"0"]).await.unwrap())
+        );
+        assert_eq!(
+            8,
+            int(run(&server, &[b"bitpos", b"k", b"1", b"1"]).await.unwrap())
+        );
+        assert_eq!(
+            -1,
+            int(run(&server, &[b"bitpos", b"k", b"1", b"2"]).await.unwrap())
+        );
+        assert_eq!(
+            -1,
+            int(run(&server, &[b"bitpos", b"k", b"1", b"2", b"-1", b"byte"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            7,
+            int(run(&server, &[b"bitpos", b"k", b"1", b"7", b"15", b"bit"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            12,
+            int(run(&server, &[b"bitpos", b"k", b"0", b"7", b"15", b"BIT"])
+                .await
+                .unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitpos_clear_bit_past_the_end() {
+        let server = new_server();
+        run(&server, &[b"set", b"ones", b"\xff\xff"]).await.unwrap();
+        // with no end, the string counts as padded with clear bits
+        assert_eq!(
+            16,
+            int(run(&server, &[b"bitpos", b"ones", b"0"]).await.unwrap())
+        );
+        // an end pins the search to the string
+        assert_eq!(
+            -1,
+            int(run(&server, &[b"bitpos", b"ones", b"0", b"0", b"-1"])
+                .await
+                .unwrap())
+        );
+        // a missing key is all clear bits
+        assert_eq!(
+            0,
+            int(run(&server, &[b"bitpos", b"nope", b"0"]).await.unwrap())
+        );
+        assert_eq!(
+            -1,
+            int(run(&server, &[b"bitpos", b"nope", b"1"]).await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitpos_errors() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"\xff"]).await.unwrap();
+        assert_eq!(
+            "ERR The bit argument must be 1 or 0.",
+            run_err(&server, &[b"bitpos", b"k", b"2"]).await
+        );
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(&server, &[b"bitpos", b"k", b"1", b"0", b"1", b"bit", b"x"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitop() {
+        let server = new_server();
+        run(&server, &[b"mset", b"a", b"foobar", b"b", b"abcdef"])
+            .await
+            .unwrap();
+        let ops: &[(&[u8], &[u8])] = &[
+            (b"and", b"`bc`ab"),
+            (b"OR", b"goofev"),
+            (b"xor", b"\x07\x0d\x0c\x06\x04\x14"),
+        ];
+        for (op, expected) in ops {
+            assert_eq!(
+                6,
+                int(run(&server, &[b"bitop", op, b"dest", b"a", b"b"])
+                    .await
+                    .unwrap())
+            );
+            assert_eq!(
+                DataType::BulkString(Bytes::copy_from_slice(expected)),
+                run(&server, &[b"get", b"dest"]).await.unwrap()
+            );
+        }
+        run(&server, &[b"bitop", b"not", b"dest", b"a"])
+            .await
+            .unwrap();
+        assert_eq!(
+            DataType::BulkString(Bytes::from_static(b"\x99\x90\x90\x9d\x9e\x8d")),
+            run(&server, &[b"get", b"dest"]).await.unwrap()
+        );
+        // a missing key counts as all zero bytes
+        run(&server, &[b"bitop", b"and", b"dest", b"a", b"nope"])
+            .await
+            .unwrap();
+        assert_eq!(
+            DataType::BulkString(Bytes::from_static(&[0; 6])),
+            run(&server, &[b"get", b"dest"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitop_replaces_dest() {
+        let server = new_server();
+        run(&server, &[b"mset", b"a", b"foobar", b"dest", b"x"])
+            .await
+            .unwrap();
+        run(&server, &[b"expire", b"dest", b"100"]).await.unwrap();
+        run(&server, &[b"bitop", b"or", b"dest", b"a"])
+            .await
+            .unwrap();
+        // the result is a new value, so the old one's TTL goes with it
+        assert_eq!(-1, int(run(&server, &[b"ttl", b"dest"]).await.unwrap()));
+        // an empty result deletes the destination
+        assert_eq!(
+            0,
+            int(run(&server, &[b"bitop", b"or", b"dest", b"nope"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(0, int(run(&server, &[b"exists", b"dest"]).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_bitop_errors() {
+        let server = new_server();
+        run(&server, &[b"mset", b"a", b"foobar", b"b", b"abcdef"])
+            .await
+            .unwrap();
+        assert_eq!(
+            "ERR BITOP NOT must be called with a single source key.",
+            run_err(&server, &[b"bitop", b"not", b"dest", b"a", b"b"]).await
+        );
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(&server, &[b"bitop", b"nand", b"dest", b"a"]).await
+        );
+        run(&server, &[b"rpush", b"list", b"a"]).await.unwrap();
+        assert_eq!(
+            CommandError::WrongType.to_string(),
+            run_err(&server, &[b"bitop", b"or", b"dest", b"a", b"list"]).await
+        );
+        assert_eq!(0, int(run(&server, &[b"exists", b"dest"]).await.unwrap()));
+    }
+
+    #[test]
+    fn test_field_fit() {
+        let u8 = FieldType::parse(b"u8").unwrap();
+        let i8 = FieldType::parse(b"i8").unwrap();
+        assert_eq!(Some(44), u8.fit(300, Overflow::Wrap));
+        assert_eq!(Some(255), u8.fit(300, Overflow::Sat));
+        assert_eq!(None, u8.fit(300, Overflow::Fail));
+        assert_eq!(Some(255), u8.fit(-1, Overflow::Wrap));
+        assert_eq!(Some(0), u8.fit(-1, Overflow::Sat));
+        assert_eq!(Some(-128), i8.fit(128, Overflow::Wrap));
+        assert_eq!(Some(127), i8.fit(128, Overflow::Sat));
+        assert_eq!(Some(-128), i8.fit(-200, Overflow::Sat));
+        let i64 = FieldType::parse(b"i64").unwrap();
+        assert_eq!(
+            Some(i64::MIN as i128),
+            i64.fit(i64::MAX as i128 + 1, Overflow::Wrap)
+        );
+        assert!(FieldType::parse(b"u64").is_err());
+        assert!(FieldType::parse(b"i0").is_err());
+        assert!(FieldType::parse(b"x8").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bitfield() {
+        let server = new_server();
+        assert_eq!(
+            ints(&[Some(0), Some(6)]),
+            run(
+                &server,
+                &[
+                    b"bitfield",
+                    b"k",
+                    b"set",
+                    b"i8",
+                    b"0",
+                    b"100",
+                    b"get",
+                    b"u4",
+                    b"0"
+                ]
+            )
+            .await
+            .unwrap()
+        );
+        // the default overflow wraps
+        assert_eq!(
+            ints(&[Some(-56)]),
+            run(
+                &server,
+                &[b"bitfield", b"k", b"incrby", b"i8", b"0", b"100"]
+            )
+            .await
+            .unwrap()
+        );
+        // a negative value is stored as its two's complement
+        assert_eq!(
+            ints(&[Some(0), Some(255)]),
+            run(
+                &server,
+                &[
+                    b"bitfield",
+                    b"c",
+                    b"set",
+                    b"u8",
+                    b"0",
+                    b"-1",
+                    b"get",
+                    b"u8",
+                    b"0"
+                ]
+            )
+            .await
+            .unwrap()
+        );
+        // GET alone doesn't create the key
+        assert_eq!(
+            ints(&[Some(0)]),
+            run(&server, &[b"bitfield", b"nope", b"get", b"i16", b"0"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(0, int(run(&server, &[b"exists", b"nope"]).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_offsets() {
+        let server = new_server();
+        // '#' offsets are in units of the type's width
+        assert_eq!(
+            ints(&[Some(1), Some(0)]),
+            run(
+                &server,
+                &[
+                    b"bitfield",
+                    b"c",
+                    b"incrby",
+                    b"u2",
+                    b"#1",
+                    b"1",
+                    b"incrby",
+                    b"u2",
+                    b"#1",
+                    b"3"
+                ]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[Some(0)]),
+            run(&server, &[b"bitfield", b"c", b"get", b"u8", b"0"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_overflow() {
+        let server = new_server();
+        run(&server, &[b"bitfield", b"k", b"set", b"i8", b"0", b"-100"])
+            .await
+            .unwrap();
+        assert_eq!(
+            ints(&[Some(-128)]),
+            run(
+                &server,
+                &[
+                    b"bitfield",
+                    b"k",
+                    b"overflow",
+                    b"sat",
+                    b"incrby",
+                    b"i8",
+                    b"0",
+                    b"-100"
+                ]
+            )
+            .await
+            .unwrap()
+        );
+        // FAIL skips the write and replies nil for it
+        assert_eq!(
+            ints(&[None, Some(-128)]),
+            run(
+                &server,
+                &[
+                    b"bitfield",
+                    b"k",
+                    b"overflow",
+                    b"fail",
+                    b"incrby",
+                    b"i8",
+                    b"0",
+                    b"-1",
+                    b"get",
+                    b"i8",
+                    b"0",
+                ]
+            )
+            .await
+            .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_ro() {
+        let server = new_server();
+        run(&server, &[b"bitfield", b"c", b"set", b"u8", b"0", b"255"])
+            .await
+            .unwrap();
+        assert_eq!(
+            ints(&[Some(255), Some(0)]),
+            run(
+                &server,
+                &[
+                    b"bitfield_ro",
+                    b"c",
+                    b"get",
+                    b"u8",
+                    b"0",
+                    b"get",
+                    b"u8",
+                    b"100"
+                ]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            "ERR BITFIELD_RO only supports the GET subcommand",
+            run_err(&server, &[b"bitfield_ro", b"c", b"set", b"u8", b"0", b"1"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bitfield_errors() {
+        let server = new_server();
+        assert_eq!(
+            "ERR Invalid bitfield type. Use something like i16 u8. Note that u64 is not supported but i64 is.",
+            run_err(&server, &[b"bitfield", b"c", b"get", b"u64", b"0"]).await
+        );
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(&server, &[b"bitfield", b"c", b"get", b"u8"]).await
+        );
+        assert_eq!(
+            OFFSET_ERR,
+            run_err(&server, &[b"bitfield", b"c", b"get", b"u8", b"-1"]).await
+        );
+        assert_eq!(
+            "ERR Invalid OVERFLOW type specified",
+            run_err(&server, &[b"bitfield", b"c", b"overflow", b"never"]).await
+        );
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(&server, &[b"bitfield", b"c", b"frob"]).await
+        );
+        // a bad op later on means none of them run
+        assert_eq!(
+            SYNTAX_ERR,
+            run_err(
+                &server,
+                &[b"bitfield", b"c", b"set", b"u8", b"0", b"1", b"frob"]
+            )
+            .await
+        );
+        assert_eq!(0, int(run(&server, &[b"exists", b"c"]).await.unwrap()));
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+
+use crate::resp::data::{DataType, Protocol};
+use crate::server::errors::CommandError;
+use crate::server::REDIS_VERSION;
+
+use super::{parse_int, Args, CommandHandler, CommandResult, Context, OptionEntry, R};
+
+// PING [message]
+pub struct Ping;
+
+impl CommandHandler for Ping {
+    fn name(&self) -> &'static str {
+        "ping"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the server's liveliness response."
+    }
+
+    fn group(&self) -> &'static str {
+        "connection"
+    }
+
+    fn execute(&self, _ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let reply = match (args.pop_front(), args.is_empty()) {
+            (None, _) => DataType::simple_str("PONG"),
+            (Some(message), true) => DataType::BulkString(message),
+            (Some(_), false) => return Err(CommandError::InvalidArgs(self.name().to_string())),
+        };
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// ECHO message
+pub struct Echo;
+
+impl CommandHandler for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the given string."
+    }
+
+    fn group(&self) -> &'static str {
+        "connection"
+    }
+
+    fn execute(&self, _ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let message = super::next_arg(&mut args, self.name())?;
+        Ok(CommandResult::Reply(DataType::BulkString(message)))
+    }
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+pub struct Hello;
+
+impl CommandHandler for Hello {
+    fn name(&self) -> &'static str {
+        "hello"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Handshakes with the Redis server."
+    }
+
+    fn group(&self) -> &'static str {
+        "connection"
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] =
+            &[OptionEntry::new("auth", 2), OptionEntry::new("setname", 1)];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let protover = match args.pop_front() {
+            Some(v) => Some(parse_int::<i64>(&v).map_err(|_| {
+                CommandError::CommandFailed(
+                    "ERR Protocol version is not an integer or out of range".to_string(),
+                )
+            })?),
+            None => None,
+        };
+        let mut auth = None;
+        let mut setname = None;
+        for mut opt in self.parse_options(args)? {
+            match opt.name {
+                "auth" => auth = opt.val.pop_front().zip(opt.val.pop_front()),
+                "setname" => setname = opt.val.pop_front(),
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        let protocol = match protover {
+            None => Ok(ctx.client.protocol),
+            Some(2) => Ok(Protocol::Resp2),
+            Some(3) => Ok(Protocol::Resp3),
+            Some(_) => Err("NOPROTO unsupported protocol version"),
+        };
+        // There's no ACL support, so only the default user (which has no password) exists
+        let auth = match auth {
+            Some((user, _)) if user.as_ref() != b"default" => {
+                Err("WRONGPASS invalid username-password pair or user is disabled.")
+            }
+            _ => Ok(()),
+        };
+        let name = match setname {
+            Some(name) if name.iter().any(|b| !(b'!'..=b'~').contains(b)) => {
+                Err("ERR Client names cannot contain spaces, newlines or special characters.")
+            }
+            name => Ok(name),
+        };
+        let (protocol, name) = match (protocol, auth, name) {
+            (Ok(protocol), Ok(_), Ok(name)) => (protocol, name),
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+                return Err(CommandError::CommandFailed(e.to_string()));
+            }
+        };
+        ctx.client.protocol = protocol;
+        if let Some(name) = name {
+            ctx.client.name = Some(name).filter(|n| !n.is_empty());
+        }
+        let info = VecDeque::from([
+            (DataType::bulk_str("server"), DataType::bulk_str("redis")),
+            (
+                DataType::bulk_str("version"),
+                DataType::bulk_str(REDIS_VERSION),
+            ),
+            (
+                DataType::bulk_str("proto"),
+                DataType::Integer(protocol.version()),
+            ),
+            (
+                DataType::bulk_str("id"),
+                DataType::Integer(ctx.client.id as i64),
+            ),
+            (DataType::bulk_str("mode"), DataType::bulk_str("standalone")),
+            (
+                DataType::bulk_str("role"),
+                DataType::bulk_str(ctx.server.replica_info.role()),
+            ),
+            (
+                DataType::bulk_str("modules"),
+                DataType::Array(VecDeque::new()),
+            ),
+        ]);
+        Ok(CommandResult::Reply(DataType::Map(info)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use super::super::tests::{new_server, run, run_err, to_data};
+    use super::super::{dispatch, CommandResult, R};
+    use crate::resp::data::{DataType, Protocol};
+    use crate::server::client::Client;
+    use crate::server::errors::CommandError;
+    use crate::server::Server;
+
+    // Like run, but on the given client, so what a command changes about the connection sticks
+    async fn run_as(
+        server: &Arc<RwLock<Server>>,
+        client: &mut Client,
+        args: &[&[u8]],
+    ) -> R<DataType> {
+        match dispatch(to_data(args), server, client).await? {
+            CommandResult::Reply(reply) => Ok(reply),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    fn assert_failed(result: R<DataType>, prefix: &str) {
+        match result {
+            Err(CommandError::CommandFailed(e)) => assert!(e.starts_with(prefix), "{}", e),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    // The value of `field` in HELLO's reply
+    fn hello_field(reply: &DataType, field: &str) -> DataType {
+        match reply {
+            DataType::Map(pairs) => pairs
+                .iter()
+                .find(|(k, _)| *k == DataType::bulk_str(field))
+                .map(|(_, v)| v.clone())
+                .unwrap(),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let server = new_server();
+        assert_eq!(
+            DataType::simple_str("PONG"),
+            run(&server, &[b"ping"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("hi"),
+            run(&server, &[b"ping", b"hi"]).await.unwrap()
+        );
+        assert_eq!(
+            "ERR wrong number of arguments for 'ping' command",
+            run_err(&server, &[b"ping", b"a", b"b"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hello_switches_protocol() {
+        let server = new_server();
+        let mut client = Client::new();
+        assert_eq!(Protocol::Resp2, client.protocol);
+
+        let reply = run_as(&server, &mut client, &[b"hello", b"3"])
+            .await
+            .unwrap();
+        assert_eq!(Protocol::Resp3, client.protocol);
+        assert_eq!(DataType::Integer(3), hello_field(&reply, "proto"));
+        assert_eq!(DataType::bulk_str("redis"), hello_field(&reply, "server"));
+        assert_eq!(DataType::bulk_str("master"), hello_field(&reply, "role"));
+
+        // no version keeps the current one
+        let reply = run_as(&server, &mut client, &[b"hello"]).await.unwrap();
+        assert_eq!(Protocol::Resp3, client.protocol);
+        assert_eq!(DataType::Integer(3), hello_field(&reply, "proto"));
+
+        let reply = run_as(&server, &mut client, &[b"hello", b"2"])
+            .await
+            .unwrap();
+        assert_eq!(Protocol::Resp2, client.protocol);
+        assert_eq!(DataType::Integer(2), hello_field(&reply, "proto"));
+    }
+
+    #[tokio::test]
+    async fn test_hello_errors() {
+        let server = new_server();
+        let mut client = Client::new();
+        let cases: &[(&[&[u8]], &str)] = &[
+            (&[b"hello", b"4"], "NOPROTO"),
+            (&[b"hello", b"three"], "ERR Protocol version"),
+            (&[b"hello", b"3", b"auth", b"someone", b"pass"], "WRONGPASS"),
+            (
+                &[b"hello", b"3", b"setname", b"my name"],
+                "ERR Client names",
+            ),
+        ];
+        for (cmd, prefix) in cases {
+            assert_failed(run_as(&server, &mut client, cmd).await, prefix);
+        }
+        // a failed HELLO changes nothing
+        assert_eq!(Protocol::Resp2, client.protocol);
+        assert_eq!(None, client.name);
+
+        run_as(
+            &server,
+            &mut client,
+            &[b"hello", b"3", b"auth", b"default", b"", b"setname", b"app"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(Protocol::Resp3, client.protocol);
+        assert_eq!(Some(bytes::Bytes::from("app")), client.name);
+    }
+}
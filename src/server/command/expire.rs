@@ -0,0 +1,552 @@
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::store::now_ms;
+
+use super::{
+    next_arg, parse_int, Args, CommandFlags, CommandHandler, CommandResult, Context, KeySpec,
+    OptionEntry, R,
+};
+
+#[derive(Clone, Copy)]
+pub enum Unit {
+    Seconds,
+    Millis,
+}
+
+impl Unit {
+    pub fn to_ms(self, time: i64) -> Option<i64> {
+        match self {
+            Self::Seconds => time.checked_mul(1000),
+            Self::Millis => Some(time),
+        }
+    }
+
+    pub fn in_unit(self, ms: i64) -> i64 {
+        match self {
+            Self::Seconds => ms / 1000,
+            Self::Millis => ms,
+        }
+    }
+}
+
+// The conditions EXPIRE and co. take
+pub const CONDITIONS: &[OptionEntry] = &[
+    OptionEntry::new("nx", 0),
+    OptionEntry::new("xx", 0),
+    OptionEntry::new("gt", 0),
+    OptionEntry::new("lt", 0),
+];
+
+// When a key given `time` expires -> `absolute` if `time` is a unix time rather than a time to live. Fails if
+// that's out of range.
+pub fn to_expire_at(name: &str, time: i64, unit: Unit, absolute: bool) -> R<i64> {
+    unit.to_ms(time)
+        .and_then(|ms| match absolute {
+            true => Some(ms),
+            false => now_ms().checked_add(ms),
+        })
+        .ok_or_else(|| {
+            CommandError::CommandFailed(format!("ERR invalid expire time in '{}' command", name))
+        })
+}
+
+// NX, XX, GT or LT -> whether a new expiry replaces the current one
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Condition {
+    nx: bool,
+    xx: bool,
+    gt: bool,
+    lt: bool,
+}
+
+impl Condition {
+    // `args` are the conditions alone, which the handler takes as its options (-> CONDITIONS)
+    pub fn parse(handler: &dyn CommandHandler, args: Args) -> R<Self> {
+        let mut cond = Self::default();
+        for opt in handler.parse_options(args)? {
+            match opt.name {
+                "nx" => cond.nx = true,
+                "xx" => cond.xx = true,
+                "gt" => cond.gt = true,
+                "lt" => cond.lt = true,
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        if cond.nx && (cond.xx || cond.gt || cond.lt) {
+            return Err(CommandError::CommandFailed(
+                "ERR NX and XX, GT or LT options at the same time are not compatible".to_string(),
+            ));
+        }
+        if cond.gt && cond.lt {
+            return Err(CommandError::CommandFailed(
+                "ERR GT and LT options at the same time are not compatible".to_string(),
+            ));
+        }
+        Ok(cond)
+    }
+
+    // No current expiry counts as one that never comes -> GT never holds, LT always does
+    pub fn allows(&self, current: Option<i64>, at: i64) -> bool {
+        match current {
+            None => !self.xx && !self.gt,
+            Some(current) => !self.nx && (!self.gt || at > current) && (!self.lt || at < current),
+        }
+    }
+}
+
+// EXPIRE and co. -> `absolute` for the *AT variants, which take a unix time instead of a time to live
+fn expire(
+    ctx: &mut Context,
+    handler: &dyn CommandHandler,
+    mut args: Args,
+    unit: Unit,
+    absolute: bool,
+) -> R<CommandResult> {
+    let key = next_arg(&mut args, handler.name())?;
+    let time = parse_int::<i64>(&next_arg(&mut args, handler.name())?)?;
+    let cond = Condition::parse(handler, args)?;
+    let at = to_expire_at(handler.name(), time, unit, absolute)?;
+
+    let store = &mut ctx.server.store;
+    if !store.contains_key(&key) {
+        return Ok(CommandResult::Reply(DataType::Integer(0)));
+    }
+    if !cond.allows(store.expire_at(&key), at) {
+        return Ok(CommandResult::Reply(DataType::Integer(0)));
+    }
+    // a time that's already passed deletes the key straight away
+    match at <= now_ms() {
+        true => store.remove(&key),
+        false => store.set_expire_at(key, at),
+    };
+    Ok(CommandResult::Reply(DataType::Integer(1)))
+}
+
+// The time to live of something that expires at `at`
+pub fn time_left(at: i64, unit: Unit) -> i64 {
+    let ttl = (at - now_ms()).max(0);
+    match unit {
+        // rounded to the nearest second, like redis
+        Unit::Seconds => (ttl + 500) / 1000,
+        Unit::Millis => ttl,
+    }
+}
+
+// TTL and co. -> -2 if the key doesn't exist, -1 if it has no expiry
+fn ttl(
+    ctx: &mut Context,
+    handler: &dyn CommandHandler,
+    mut args: Args,
+    unit: Unit,
+    absolute: bool,
+) -> R<CommandResult> {
+    let key = next_arg(&mut args, handler.name())?;
+    let store = &ctx.server.store;
+    let reply = match (store.contains_key(&key), store.expire_at(&key)) {
+        (false, _) => -2,
+        (true, None) => -1,
+        (true, Some(at)) if absolute => unit.in_unit(at),
+        (true, Some(at)) => time_left(at, unit),
+    };
+    Ok(CommandResult::Reply(DataType::Integer(reply)))
+}
+
+// EXPIRE key seconds [NX | XX | GT | LT]
+pub struct Expire;
+
+impl CommandHandler for Expire {
+    fn name(&self) -> &'static str {
+        "expire"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the expiration time of a key in seconds."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        CONDITIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        expire(ctx, self, args, Unit::Seconds, false)
+    }
+}
+
+// PEXPIRE key milliseconds [NX | XX | GT | LT]
+pub struct PExpire;
+
+impl CommandHandler for PExpire {
+    fn name(&self) -> &'static str {
+        "pexpire"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the expiration time of a key in milliseconds."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        CONDITIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        expire(ctx, self, args, Unit::Millis, false)
+    }
+}
+
+// EXPIREAT key unix-time-seconds [NX | XX | GT | LT]
+pub struct ExpireAt;
+
+impl CommandHandler for ExpireAt {
+    fn name(&self) -> &'static str {
+        "expireat"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the expiration time of a key to a Unix timestamp."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        CONDITIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        expire(ctx, self, args, Unit::Seconds, true)
+    }
+}
+
+// PEXPIREAT key unix-time-milliseconds [NX | XX | GT | LT]
+pub struct PExpireAt;
+
+impl CommandHandler for PExpireAt {
+    fn name(&self) -> &'static str {
+        "pexpireat"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the expiration time of a key to a Unix milliseconds timestamp."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        CONDITIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        expire(ctx, self, args, Unit::Millis, true)
+    }
+}
+
+// TTL key
+pub struct Ttl;
+
+impl CommandHandler for Ttl {
+    fn name(&self) -> &'static str {
+        "ttl"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the expiration time in seconds of a key."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        ttl(ctx, self, args, Unit::Seconds, false)
+    }
+}
+
+// PTTL key
+pub struct PTtl;
+
+impl CommandHandler for PTtl {
+    fn name(&self) -> &'static str {
+        "pttl"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the expiration time in milliseconds of a key."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        ttl(ctx, self, args, Unit::Millis, false)
+    }
+}
+
+// EXPIRETIME key
+pub struct ExpireTime;
+
+impl CommandHandler for ExpireTime {
+    fn name(&self) -> &'static str {
+        "expiretime"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the expiration time of a key as a Unix timestamp."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        ttl(ctx, self, args, Unit::Seconds, true)
+    }
+}
+
+// PEXPIRETIME key
+pub struct PExpireTime;
+
+impl CommandHandler for PExpireTime {
+    fn name(&self) -> &'static str {
+        "pexpiretime"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the expiration time of a key as a Unix milliseconds timestamp."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        ttl(ctx, self, args, Unit::Millis, true)
+    }
+}
+
+// PERSIST key
+pub struct Persist;
+
+impl CommandHandler for Persist {
+    fn name(&self) -> &'static str {
+        "persist"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes the expiration time of a key."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let persisted = ctx.server.store.persist(&key);
+        Ok(CommandResult::Reply(DataType::Integer(persisted as i64)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::super::tests::{new_server, run};
+    use crate::resp::data::DataType;
+    use crate::server::store::now_ms;
+
+    fn int(reply: DataType) -> i64 {
+        match reply {
+            DataType::Integer(n) => n,
+            other => panic!("expected an integer, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_expire_ttl_persist() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"v"]).await.unwrap();
+        assert_eq!(-1, int(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        assert_eq!(-2, int(run(&server, &[b"ttl", b"nope"]).await.unwrap()));
+        assert_eq!(
+            0,
+            int(run(&server, &[b"expire", b"nope", b"10"]).await.unwrap())
+        );
+
+        assert_eq!(
+            1,
+            int(run(&server, &[b"expire", b"k", b"100"]).await.unwrap())
+        );
+        assert_eq!(100, int(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        let pttl = int(run(&server, &[b"pttl", b"k"]).await.unwrap());
+        assert!(pttl > 99_000 && pttl <= 100_000);
+        let at = int(run(&server, &[b"pexpiretime", b"k"]).await.unwrap());
+        assert_eq!(
+            at / 1000,
+            int(run(&server, &[b"expiretime", b"k"]).await.unwrap())
+        );
+
+        assert_eq!(1, int(run(&server, &[b"persist", b"k"]).await.unwrap()));
+        assert_eq!(0, int(run(&server, &[b"persist", b"k"]).await.unwrap()));
+        assert_eq!(
+            -1,
+            int(run(&server, &[b"pexpiretime", b"k"]).await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expire_conditions() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"v"]).await.unwrap();
+        let cases: &[(&[&[u8]], i64)] = &[
+            (&[b"expire", b"k", b"100", b"xx"], 0),
+            (&[b"expire", b"k", b"100", b"gt"], 0),
+            (&[b"expire", b"k", b"100", b"lt"], 1),
+            (&[b"expire", b"k", b"200", b"nx"], 0),
+            (&[b"expire", b"k", b"50", b"GT"], 0),
+            (&[b"expire", b"k", b"200", b"gt"], 1),
+            (&[b"expire", b"k", b"300", b"xx", b"lt"], 0),
+            (&[b"expire", b"k", b"300", b"xx", b"gt"], 1),
+        ];
+        for (cmd, expected) in cases {
+            assert_eq!(
+                *expected,
+                int(run(&server, cmd).await.unwrap()),
+                "{:?}",
+                cmd
+            );
+        }
+        assert_eq!(300, int(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        assert!(run(&server, &[b"expire", b"k", b"1", b"nx", b"xx"])
+            .await
+            .is_err());
+        assert!(run(&server, &[b"expire", b"k", b"1", b"gt", b"lt"])
+            .await
+            .is_err());
+        assert!(run(&server, &[b"expire", b"k", b"1", b"later"])
+            .await
+            .is_err());
+        let too_big = i64::MAX.to_string();
+        assert!(run(&server, &[b"expire", b"k", too_big.as_bytes()])
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_keys_are_gone() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"v"]).await.unwrap();
+        run(&server, &[b"xadd", b"s", b"1-1", b"f", b"v"])
+            .await
+            .unwrap();
+        // a time in the past deletes the key straight away
+        let past = (now_ms() / 1000 - 10).to_string();
+        assert_eq!(
+            1,
+            int(run(&server, &[b"expireat", b"k", past.as_bytes()])
+                .await
+                .unwrap())
+        );
+        assert_eq!(0, int(run(&server, &[b"exists", b"k"]).await.unwrap()));
+
+        assert_eq!(
+            1,
+            int(run(&server, &[b"pexpire", b"s", b"1"]).await.unwrap())
+        );
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            DataType::simple_str("none"),
+            run(&server, &[b"type", b"s"]).await.unwrap()
+        );
+        assert_eq!(0, int(run(&server, &[b"dbsize"]).await.unwrap()));
+        // writing to the key again starts from scratch, without the old expiry
+        run(&server, &[b"xadd", b"s", b"1-1", b"f", b"v"])
+            .await
+            .unwrap();
+        assert_eq!(-1, int(run(&server, &[b"ttl", b"s"]).await.unwrap()));
+    }
+}
@@ -0,0 +1,1637 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::{thread_rng, Rng};
+
+use crate::resp::data::{DataType, Protocol};
+use crate::server::errors::CommandError;
+use crate::server::store::hash::Hash;
+use crate::server::store::{now_ms, KeyType, Store};
+
+use super::expire::{time_left, to_expire_at, Condition, Unit, CONDITIONS};
+use super::keyspace::ScanArgs;
+use super::string::{bulk_or_null, format_float, parse_float};
+use super::{
+    next_arg, parse_int, to_lowercase, Args, CommandFlags, CommandHandler, CommandResult, Context,
+    KeySpec, OptionEntry, R,
+};
+
+// The hash at `key`, if it holds one
+fn get_hash<'a>(store: &'a Store, key: &[u8]) -> R<Option<&'a Hash>> {
+    store.check_type(key, KeyType::Hash)?;
+    Ok(store.hash_store.get(key))
+}
+
+fn get_hash_mut<'a>(store: &'a mut Store, key: &[u8]) -> R<Option<&'a mut Hash>> {
+    store.check_type(key, KeyType::Hash)?;
+    Ok(store.hash_store.get_mut(key))
+}
+
+// A hash that's been emptied doesn't exist anymore
+fn remove_if_empty(store: &mut Store, key: &[u8]) {
+    if store.hash_store.get(key).is_some_and(Hash::is_empty) {
+        store.remove(key);
+    }
+}
+
+// The (field, value) pairs HSET takes
+fn pairs(handler: &dyn CommandHandler, mut args: Args) -> R<Vec<(Bytes, Bytes)>> {
+    if args.is_empty() || args.len() & 1 != 0 {
+        return Err(CommandError::InvalidArgs(handler.name().to_string()));
+    }
+    let mut pairs = Vec::with_capacity(args.len() / 2);
+    while let (Some(field), Some(val)) = (args.pop_front(), args.pop_front()) {
+        pairs.push((field, val));
+    }
+    Ok(pairs)
+}
+
+// HSET key field value [field value ...]
+pub struct HSet;
+
+impl CommandHandler for HSet {
+    fn name(&self) -> &'static str {
+        "hset"
+    }
+
+    fn arity(&self) -> i64 {
+        -4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Creates or modifies the value of a field in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let pairs = pairs(self, args)?;
+        let store = &mut ctx.server.store;
+        get_hash(store, &key)?;
+        let hash = store.hash_store.get_or_create(key);
+        let added = pairs
+            .into_iter()
+            .filter(|(field, val)| hash.insert(field.clone(), val.clone()))
+            .count();
+        Ok(CommandResult::Reply(DataType::Integer(added as i64)))
+    }
+}
+
+// HSETNX key field value
+pub struct HSetNx;
+
+impl CommandHandler for HSetNx {
+    fn name(&self) -> &'static str {
+        "hsetnx"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the value of a field in a hash only when the field doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let field = next_arg(&mut args, self.name())?;
+        let val = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        if get_hash(store, &key)?.is_some_and(|hash| hash.contains_key(&field)) {
+            return Ok(CommandResult::Reply(DataType::Integer(0)));
+        }
+        store.hash_store.get_or_create(key).insert(field, val);
+        Ok(CommandResult::Reply(DataType::Integer(1)))
+    }
+}
+
+// HGET key field
+pub struct HGet;
+
+impl CommandHandler for HGet {
+    fn name(&self) -> &'static str {
+        "hget"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the value of a field in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let field = next_arg(&mut args, self.name())?;
+        let val = get_hash(&ctx.server.store, &key)?.and_then(|hash| hash.get(&field).cloned());
+        Ok(CommandResult::Reply(bulk_or_null(val)))
+    }
+}
+
+// HMGET key field [field ...]
+pub struct HMGet;
+
+impl CommandHandler for HMGet {
+    fn name(&self) -> &'static str {
+        "hmget"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the values of all fields in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let hash = get_hash(&ctx.server.store, &key)?;
+        let vals = args
+            .iter()
+            .map(|field| bulk_or_null(hash.and_then(|hash| hash.get(field).cloned())))
+            .collect();
+        Ok(CommandResult::Reply(DataType::Array(vals)))
+    }
+}
+
+// HDEL key field [field ...]
+pub struct HDel;
+
+impl CommandHandler for HDel {
+    fn name(&self) -> &'static str {
+        "hdel"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Deletes one or more fields and their values from a hash. Deletes the hash if no fields remain."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        let removed = match get_hash_mut(store, &key)? {
+            Some(hash) => args.iter().filter(|field| hash.remove(field)).count(),
+            None => 0,
+        };
+        remove_if_empty(store, &key);
+        Ok(CommandResult::Reply(DataType::Integer(removed as i64)))
+    }
+}
+
+// HEXISTS key field
+pub struct HExists;
+
+impl CommandHandler for HExists {
+    fn name(&self) -> &'static str {
+        "hexists"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Determines whether a field exists in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let field = next_arg(&mut args, self.name())?;
+        let exists =
+            get_hash(&ctx.server.store, &key)?.is_some_and(|hash| hash.contains_key(&field));
+        Ok(CommandResult::Reply(DataType::Integer(exists as i64)))
+    }
+}
+
+// HLEN key
+pub struct HLen;
+
+impl CommandHandler for HLen {
+    fn name(&self) -> &'static str {
+        "hlen"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the number of fields in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let len = get_hash(&ctx.server.store, &key)?.map_or(0, Hash::len);
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// HSTRLEN key field
+pub struct HStrLen;
+
+impl CommandHandler for HStrLen {
+    fn name(&self) -> &'static str {
+        "hstrlen"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the length of the value of a field."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let field = next_arg(&mut args, self.name())?;
+        let len = get_hash(&ctx.server.store, &key)?
+            .and_then(|hash| hash.get(&field))
+            .map_or(0, |val| val.len());
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// HKEYS, HVALS and HGETALL
+fn get_all(ctx: &mut Context, mut args: Args, name: &str) -> R<CommandResult> {
+    let key = next_arg(&mut args, name)?;
+    let hash = get_hash(&ctx.server.store, &key)?;
+    let fields = hash.into_iter().flat_map(Hash::iter);
+    let bulk = |b: &Bytes| DataType::BulkString(b.clone());
+    let reply = match name {
+        "hkeys" => DataType::Array(fields.map(|(f, _)| bulk(f)).collect()),
+        "hvals" => DataType::Array(fields.map(|(_, v)| bulk(v)).collect()),
+        _ => DataType::Map(fields.map(|(f, v)| (bulk(f), bulk(v))).collect()),
+    };
+    Ok(CommandResult::Reply(reply))
+}
+
+// HKEYS key
+pub struct HKeys;
+
+impl CommandHandler for HKeys {
+    fn name(&self) -> &'static str {
+        "hkeys"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns all fields in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        get_all(ctx, args, self.name())
+    }
+}
+
+// HVALS key
+pub struct HVals;
+
+impl CommandHandler for HVals {
+    fn name(&self) -> &'static str {
+        "hvals"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns all values in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        get_all(ctx, args, self.name())
+    }
+}
+
+// HGETALL key
+pub struct HGetAll;
+
+impl CommandHandler for HGetAll {
+    fn name(&self) -> &'static str {
+        "hgetall"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns all fields and values in a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        get_all(ctx, args, self.name())
+    }
+}
+
+// HINCRBY key field increment
+pub struct HIncrBy;
+
+impl CommandHandler for HIncrBy {
+    fn name(&self) -> &'static str {
+        "hincrby"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Increments the integer value of a field in a hash by a number. Uses 0 as initial value if the field \
+         doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let field = next_arg(&mut args, self.name())?;
+        let by = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let store = &mut ctx.server.store;
+        let current = match get_hash(store, &key)?.and_then(|hash| hash.get(&field)) {
+            Some(val) => parse_int::<i64>(val).map_err(|_| {
+                CommandError::CommandFailed("ERR hash value is not an integer".to_string())
+            })?,
+            None => 0,
+        };
+        let n = current.checked_add(by).ok_or_else(|| {
+            CommandError::CommandFailed("ERR increment or decrement would overflow".to_string())
+        })?;
+        // the field keeps its expiry
+        store
+            .hash_store
+            .get_or_create(key)
+            .update(field, Bytes::from(n.to_string()));
+        Ok(CommandResult::Reply(DataType::Integer(n)))
+    }
+}
+
+// HINCRBYFLOAT key field increment
+pub struct HIncrByFloat;
+
+impl CommandHandler for HIncrByFloat {
+    fn name(&self) -> &'static str {
+        "hincrbyfloat"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Increments the floating point value of a field by a number. Uses 0 as initial value if the field \
+         doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let field = next_arg(&mut args, self.name())?;
+        let by = parse_float(&next_arg(&mut args, self.name())?)?;
+        let store = &mut ctx.server.store;
+        let current = match get_hash(store, &key)?.and_then(|hash| hash.get(&field)) {
+            Some(val) => parse_float(val).map_err(|_| {
+                CommandError::CommandFailed("ERR hash value is not a float".to_string())
+            })?,
+            None => 0.0,
+        };
+        let n = current + by;
+        if !n.is_finite() {
+            return Err(CommandError::CommandFailed(
+                "ERR increment would produce NaN or Infinity".to_string(),
+            ));
+        }
+        let val = Bytes::from(format_float(n));
+        store
+            .hash_store
+            .get_or_create(key)
+            .update(field, val.clone());
+        Ok(CommandResult::Reply(DataType::BulkString(val)))
+    }
+}
+
+// HRANDFIELD key [count [WITHVALUES]]
+pub struct HRandField;
+
+impl CommandHandler for HRandField {
+    fn name(&self) -> &'static str {
+        "hrandfield"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns one or more random fields from a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let count = match args.pop_front() {
+            // redis' limit, so the reply can't be a silly size
+            Some(count) => match parse_int::<i64>(&count)? {
+                count if count < -(i64::MAX / 2) => {
+                    return Err(CommandError::CommandFailed(
+                        "ERR value is out of range".to_string(),
+                    ))
+                }
+                count => Some(count),
+            },
+            None => None,
+        };
+        let with_values = match args.pop_front() {
+            Some(arg) if to_lowercase(&arg) == "withvalues" && count.is_some() => true,
+            Some(_) => return Err(CommandError::InvalidOption),
+            None => false,
+        };
+        if !args.is_empty() {
+            return Err(CommandError::InvalidOption);
+        }
+        let hash = get_hash(&ctx.server.store, &key)?;
+        let mut rng = thread_rng();
+        let count = match (hash, count) {
+            (None, None) => return Ok(CommandResult::Reply(DataType::NullBulkString)),
+            (None, Some(_)) => return Ok(CommandResult::Reply(DataType::Array(VecDeque::new()))),
+            (Some(hash), None) => {
+                let field = hash.iter().choose(&mut rng).map(|(f, _)| f.clone());
+                return Ok(CommandResult::Reply(bulk_or_null(field)));
+            }
+            (Some(_), Some(count)) => count,
+        };
+        let fields = hash.into_iter().flat_map(Hash::iter).collect::<Vec<_>>();
+        // positive -> distinct fields, negative -> the same field can come up more than once
+        let picked = match count >= 0 {
+            true => {
+                let mut picked = fields
+                    .iter()
+                    .copied()
+                    .choose_multiple(&mut rng, count as usize);
+                picked.shuffle(&mut rng);
+                picked
+            }
+            false => (0..count.unsigned_abs())
+                .map(|_| fields[rng.gen_range(0..fields.len())])
+                .collect(),
+        };
+        let bulk = |b: &Bytes| DataType::BulkString(b.clone());
+        let reply = match (with_values, ctx.client.protocol) {
+            (false, _) => picked.into_iter().map(|(f, _)| bulk(f)).collect(),
+            (true, Protocol::Resp2) => picked
+                .into_iter()
+                .flat_map(|(f, v)| [bulk(f), bulk(v)])
+                .collect(),
+            (true, Protocol::Resp3) => picked
+                .into_iter()
+                .map(|(f, v)| DataType::Array(VecDeque::from([bulk(f), bulk(v)])))
+                .collect(),
+        };
+        Ok(CommandResult::Reply(DataType::Array(reply)))
+    }
+}
+
+// HSCAN key cursor [MATCH pattern] [COUNT count] [NOVALUES]
+pub struct HScan;
+
+impl CommandHandler for HScan {
+    fn name(&self) -> &'static str {
+        "hscan"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Iterates over fields and values of a hash."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("match", 1),
+            OptionEntry::new("count", 1),
+            OptionEntry::new("novalues", 0),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let scan = ScanArgs::parse(self, args)?;
+        let reply = match get_hash(&ctx.server.store, &key)? {
+            Some(hash) => {
+                let (next, fields) = hash.scan(scan.cursor, scan.count);
+                let items =
+                    fields
+                        .filter(|(field, _)| scan.matches(field))
+                        .flat_map(|(field, val)| match scan.novalues {
+                            true => vec![field.clone()],
+                            false => vec![field.clone(), val.clone()],
+                        });
+                ScanArgs::reply(next, items)
+            }
+            None => ScanArgs::reply(0, std::iter::empty()),
+        };
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// FIELDS numfields field [field ...] -> the fields. The commands that take it declare an arity that lets
+// 'FIELDS 0' through, so it gets the numFields error rather than an arity one, like redis.
+fn parse_fields(mut args: Args) -> R<Vec<Bytes>> {
+    match args.pop_front() {
+        Some(arg) if to_lowercase(&arg) == "fields" => {}
+        _ => {
+            return Err(CommandError::CommandFailed(
+                "ERR Mandatory argument FIELDS is missing or not at the right position".to_string(),
+            ))
+        }
+    }
+    let numfields = args.pop_front().unwrap_or_default();
+    match parse_int::<usize>(&numfields) {
+        Ok(0) | Err(_) => Err(CommandError::CommandFailed(
+            "ERR Parameter `numFields` should be greater than 0".to_string(),
+        )),
+        Ok(n) if n != args.len() => Err(CommandError::CommandFailed(
+            "ERR The `numfields` parameter must match the number of arguments".to_string(),
+        )),
+        Ok(_) => Ok(args.into()),
+    }
+}
+
+// HEXPIRE and HPEXPIRE -> for each field, -2 if there's no such field, 0 if the condition doesn't hold, 1
+// if its expiry was set, or 2 if it was deleted straight away, as the time's already passed
+fn expire_fields(
+    ctx: &mut Context,
+    handler: &dyn CommandHandler,
+    mut args: Args,
+    unit: Unit,
+) -> R<CommandResult> {
+    let key = next_arg(&mut args, handler.name())?;
+    let time = parse_int::<i64>(&next_arg(&mut args, handler.name())?)?;
+    // the condition (if there is one) comes before FIELDS
+    let fields_at = args
+        .iter()
+        .position(|arg| to_lowercase(arg) == "fields")
+        .unwrap_or(args.len());
+    let fields = parse_fields(args.split_off(fields_at))?;
+    let cond = Condition::parse(handler, args)?;
+    if time < 0 {
+        return Err(CommandError::CommandFailed(format!(
+            "ERR invalid expire time in '{}' command",
+            handler.name()
+        )));
+    }
+    let at = to_expire_at(handler.name(), time, unit, false)?;
+
+    let store = &mut ctx.server.store;
+    let hash = match get_hash_mut(store, &key)? {
+        Some(hash) => hash,
+        None => {
+            let replies = fields.iter().map(|_| DataType::Integer(-2)).collect();
+            return Ok(CommandResult::Reply(DataType::Array(replies)));
+        }
+    };
+    let replies = fields
+        .into_iter()
+        .map(|field| {
+            let reply = match hash.contains_key(&field) {
+                false => -2,
+                true if !cond.allows(hash.expire_at(&field), at) => 0,
+                true if at <= now_ms() => {
+                    hash.remove(&field);
+                    2
+                }
+                true => {
+                    hash.set_expire_at(field, at);
+                    1
+                }
+            };
+            DataType::Integer(reply)
+        })
+        .collect();
+    store.track_field_expiry(&key);
+    remove_if_empty(store, &key);
+    Ok(CommandResult::Reply(DataType::Array(replies)))
+}
+
+// HEXPIRE key seconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub struct HExpire;
+
+impl CommandHandler for HExpire {
+    fn name(&self) -> &'static str {
+        "hexpire"
+    }
+
+    fn arity(&self) -> i64 {
+        -5
+    }
+
+    fn summary(&self) -> &'static str {
+        "Set expiry for hash field using relative time to expire (seconds)"
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        CONDITIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        expire_fields(ctx, self, args, Unit::Seconds)
+    }
+}
+
+// HPEXPIRE key milliseconds [NX | XX | GT | LT] FIELDS numfields field [field ...]
+pub struct HPExpire;
+
+impl CommandHandler for HPExpire {
+    fn name(&self) -> &'static str {
+        "hpexpire"
+    }
+
+    fn arity(&self) -> i64 {
+        -5
+    }
+
+    fn summary(&self) -> &'static str {
+        "Set expiry for hash field using relative time to expire (milliseconds)"
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        CONDITIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        expire_fields(ctx, self, args, Unit::Millis)
+    }
+}
+
+// HTTL key FIELDS numfields field [field ...] -> for each field, -2 if there's no such field, -1 if it has
+// no expiry
+pub struct HTtl;
+
+impl CommandHandler for HTtl {
+    fn name(&self) -> &'static str {
+        "httl"
+    }
+
+    fn arity(&self) -> i64 {
+        -4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the TTL in seconds of a hash field."
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let fields = parse_fields(args)?;
+        let hash = get_hash(&ctx.server.store, &key)?;
+        let replies = fields
+            .iter()
+            .map(|field| {
+                let ttl = match hash.filter(|hash| hash.contains_key(field)) {
+                    None => -2,
+                    Some(hash) => match hash.expire_at(field) {
+                        Some(at) => time_left(at, Unit::Seconds),
+                        None => -1,
+                    },
+                };
+                DataType::Integer(ttl)
+            })
+            .collect();
+        Ok(CommandResult::Reply(DataType::Array(replies)))
+    }
+}
+
+// HPERSIST key FIELDS numfields field [field ...] -> for each field, -2 if there's no such field, -1 if it
+// has no expiry, 1 if its expiry was removed
+pub struct HPersist;
+
+impl CommandHandler for HPersist {
+    fn name(&self) -> &'static str {
+        "hpersist"
+    }
+
+    fn arity(&self) -> i64 {
+        -4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes the expiration time for each specified field"
+    }
+
+    fn group(&self) -> &'static str {
+        "hash"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let fields = parse_fields(args)?;
+        let mut hash = get_hash_mut(&mut ctx.server.store, &key)?;
+        let replies = fields
+            .iter()
+            .map(|field| {
+                let reply = match hash.as_deref_mut() {
+                    Some(hash) if hash.contains_key(field) => match hash.persist(field) {
+                        true => 1,
+                        false => -1,
+                    },
+                    _ => -2,
+                };
+                DataType::Integer(reply)
+            })
+            .collect();
+        ctx.server.store.track_field_expiry(&key);
+        Ok(CommandResult::Reply(DataType::Array(replies)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::RwLock;
+
+    use super::super::tests::{new_server, run, run_err};
+    use crate::resp::data::DataType;
+    use crate::server::errors::CommandError;
+    use crate::server::Server;
+
+    fn bulks(vals: &[&str]) -> DataType {
+        DataType::Array(vals.iter().map(|v| DataType::bulk_str(v)).collect())
+    }
+
+    fn ints(vals: &[i64]) -> DataType {
+        DataType::Array(vals.iter().map(|v| DataType::Integer(*v)).collect())
+    }
+
+    fn len(reply: DataType) -> usize {
+        match reply {
+            DataType::Array(items) => items.len(),
+            other => panic!("expected an array, got {:?}", other),
+        }
+    }
+
+    async fn exists(server: &Arc<RwLock<Server>>, key: &[u8]) -> bool {
+        run(server, &[b"exists", key]).await.unwrap() == DataType::Integer(1)
+    }
+
+    // A hash of f0..f<n> -> v0..v<n>
+    async fn hset_n(server: &Arc<RwLock<Server>>, key: &[u8], n: usize) {
+        let mut cmd: Vec<Vec<u8>> = vec![b"hset".to_vec(), key.to_vec()];
+        for i in 0..n {
+            cmd.push(format!("f{}", i).into_bytes());
+            cmd.push(format!("v{}", i).into_bytes());
+        }
+        let cmd = cmd.iter().map(|c| c.as_slice()).collect::<Vec<_>>();
+        run(server, &cmd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_hset_hget() {
+        let server = new_server();
+        // HSET counts the fields it added, not the ones it overwrote
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"hset", b"h", b"a", b"1", b"b", b"2"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"hset", b"h", b"a", b"3", b"c", b"4"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("3"),
+            run(&server, &[b"hget", b"h", b"a"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"hget", b"h", b"nope"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"hget", b"nope", b"a"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("hash"),
+            run(&server, &[b"type", b"h"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hsetnx() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"hsetnx", b"h", b"a", b"2"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"hsetnx", b"h", b"b", b"2"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("1"),
+            run(&server, &[b"hget", b"h", b"a"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hmget_hexists_hlen_hstrlen() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1", b"b", b"22"])
+            .await
+            .unwrap();
+        assert_eq!(
+            DataType::Array(
+                [
+                    DataType::bulk_str("1"),
+                    DataType::NullBulkString,
+                    DataType::bulk_str("22"),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            run(&server, &[b"hmget", b"h", b"a", b"nope", b"b"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"hexists", b"h", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"hexists", b"h", b"nope"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"hlen", b"h"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"hstrlen", b"h", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"hstrlen", b"h", b"nope"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hdel() {
+        let server = new_server();
+        run(
+            &server,
+            &[b"hset", b"h", b"a", b"1", b"b", b"2", b"c", b"3"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"hdel", b"h", b"a", b"b", b"nope"])
+                .await
+                .unwrap()
+        );
+        // deleting the last field deletes the hash
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"hdel", b"h", b"c"]).await.unwrap()
+        );
+        assert!(!exists(&server, b"h").await);
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"hdel", b"h", b"c"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hset_errors() {
+        let server = new_server();
+        assert_eq!(
+            CommandError::InvalidArgs("hset".to_string()).to_string(),
+            run_err(&server, &[b"hset", b"h", b"a", b"1", b"b"]).await
+        );
+        assert!(!exists(&server, b"h").await);
+
+        run(&server, &[b"set", b"s", b"v"]).await.unwrap();
+        run(&server, &[b"hset", b"h", b"a", b"1"]).await.unwrap();
+        let wrong_type = CommandError::WrongType.to_string();
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"hset", b"s", b"a", b"1"]).await
+        );
+        assert_eq!(wrong_type, run_err(&server, &[b"hget", b"s", b"a"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"get", b"h"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"lpush", b"h", b"a"]).await);
+    }
+
+    #[tokio::test]
+    async fn test_hkeys_hvals_hgetall() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1", b"b", b"2"])
+            .await
+            .unwrap();
+        let sorted = |reply: DataType| match reply {
+            DataType::Array(items) => {
+                let mut items = items.into_iter().collect::<Vec<_>>();
+                items.sort_by_key(|item| item.try_to_bytes().unwrap());
+                items
+            }
+            other => panic!("expected an array, got {:?}", other),
+        };
+        assert_eq!(
+            vec![DataType::bulk_str("a"), DataType::bulk_str("b")],
+            sorted(run(&server, &[b"hkeys", b"h"]).await.unwrap())
+        );
+        assert_eq!(
+            vec![DataType::bulk_str("1"), DataType::bulk_str("2")],
+            sorted(run(&server, &[b"hvals", b"h"]).await.unwrap())
+        );
+        match run(&server, &[b"hgetall", b"h"]).await.unwrap() {
+            DataType::Map(pairs) => assert_eq!(2, pairs.len()),
+            other => panic!("expected a map, got {:?}", other),
+        }
+        assert_eq!(
+            bulks(&[]),
+            run(&server, &[b"hvals", b"nope"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hincrby() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(11),
+            run(&server, &[b"hincrby", b"h", b"a", b"10"])
+                .await
+                .unwrap()
+        );
+        // a missing field starts at 0
+        assert_eq!(
+            DataType::Integer(-5),
+            run(&server, &[b"hincrby", b"h", b"new", b"-5"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(-5),
+            run(&server, &[b"hincrby", b"nope", b"new", b"-5"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hincrby_errors() {
+        let server = new_server();
+        run(
+            &server,
+            &[
+                b"hset",
+                b"h",
+                b"s",
+                b"x",
+                b"f",
+                b"1.5",
+                b"max",
+                b"9223372036854775807",
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            "ERR hash value is not an integer",
+            run_err(&server, &[b"hincrby", b"h", b"s", b"1"]).await
+        );
+        assert_eq!(
+            "ERR hash value is not an integer",
+            run_err(&server, &[b"hincrby", b"h", b"f", b"1"]).await
+        );
+        assert_eq!(
+            "ERR increment or decrement would overflow",
+            run_err(&server, &[b"hincrby", b"h", b"max", b"1"]).await
+        );
+        assert_eq!(
+            CommandError::NotAnInteger.to_string(),
+            run_err(&server, &[b"hincrby", b"h", b"new", b"x"]).await
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"hexists", b"h", b"new"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hincrbyfloat() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"11", b"g", b"0.1"])
+            .await
+            .unwrap();
+        assert_eq!(
+            DataType::bulk_str("11.5"),
+            run(&server, &[b"hincrbyfloat", b"h", b"a", b"0.5"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("250"),
+            run(&server, &[b"hincrbyfloat", b"h", b"f", b"2.5e2"])
+                .await
+                .unwrap()
+        );
+        // printed the short way, not as 0.30000000000000004
+        assert_eq!(
+            DataType::bulk_str("0.3"),
+            run(&server, &[b"hincrbyfloat", b"h", b"g", b"0.2"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hincrbyfloat_errors() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"s", b"x", b"f", b"1.5e308"])
+            .await
+            .unwrap();
+        assert_eq!(
+            "ERR hash value is not a float",
+            run_err(&server, &[b"hincrbyfloat", b"h", b"s", b"1"]).await
+        );
+        assert_eq!(
+            "ERR value is not a valid float",
+            run_err(&server, &[b"hincrbyfloat", b"h", b"f", b"inf"]).await
+        );
+        assert_eq!(
+            "ERR increment would produce NaN or Infinity",
+            run_err(&server, &[b"hincrbyfloat", b"h", b"f", b"1.5e308"]).await
+        );
+        assert_eq!(
+            DataType::bulk_str("1.5e308"),
+            run(&server, &[b"hget", b"h", b"f"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hrandfield() {
+        let server = new_server();
+        hset_n(&server, b"h", 100).await;
+        assert!(matches!(
+            run(&server, &[b"hrandfield", b"h"]).await.unwrap(),
+            DataType::BulkString(_)
+        ));
+        assert_eq!(
+            5,
+            len(run(&server, &[b"hrandfield", b"h", b"5"]).await.unwrap())
+        );
+        // a positive count gets distinct fields, so no more than there are
+        assert_eq!(
+            100,
+            len(run(&server, &[b"hrandfield", b"h", b"500"]).await.unwrap())
+        );
+        // a negative one can repeat them
+        assert_eq!(
+            500,
+            len(run(&server, &[b"hrandfield", b"h", b"-500"]).await.unwrap())
+        );
+        assert_eq!(
+            10,
+            len(run(&server, &[b"hrandfield", b"h", b"5", b"withvalues"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"hrandfield", b"nope"]).await.unwrap()
+        );
+        assert_eq!(
+            0,
+            len(run(&server, &[b"hrandfield", b"nope", b"3"]).await.unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hrandfield_errors() {
+        let server = new_server();
+        hset_n(&server, b"h", 3).await;
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"hrandfield", b"h", b"1", b"values"]).await
+        );
+        // WITHVALUES needs a count
+        assert_eq!(
+            CommandError::NotAnInteger.to_string(),
+            run_err(&server, &[b"hrandfield", b"h", b"withvalues"]).await
+        );
+        assert_eq!(
+            "ERR value is out of range",
+            run_err(&server, &[b"hrandfield", b"h", b"-9223372036854775807"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hscan() {
+        let server = new_server();
+        hset_n(&server, b"h", 100).await;
+        let (mut cursor, mut seen) = (b"0".to_vec(), Vec::new());
+        loop {
+            let reply = run(&server, &[b"hscan", b"h", &cursor, b"match", b"f1*"])
+                .await
+                .unwrap();
+            let (next, items) = match reply {
+                DataType::Array(mut reply) => {
+                    (reply.pop_front().unwrap(), reply.pop_front().unwrap())
+                }
+                other => panic!("expected an array, got {:?}", other),
+            };
+            if let DataType::Array(items) = items {
+                seen.extend(items);
+            }
+            cursor = next.try_to_bytes().unwrap().to_vec();
+            if cursor == b"0" {
+                break;
+            }
+        }
+        // f1, f10..f19 and their values
+        assert_eq!(22, seen.len());
+
+        match run(
+            &server,
+            &[b"hscan", b"h", b"0", b"count", b"1000", b"novalues"],
+        )
+        .await
+        .unwrap()
+        {
+            DataType::Array(reply) => assert_eq!(100, len(reply[1].clone())),
+            other => panic!("expected an array, got {:?}", other),
+        }
+        assert_eq!(
+            DataType::Array(
+                [DataType::bulk_str("0"), DataType::Array(Default::default())]
+                    .into_iter()
+                    .collect()
+            ),
+            run(&server, &[b"hscan", b"nope", b"0"]).await.unwrap()
+        );
+        // TYPE is only for SCAN
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"hscan", b"h", b"0", b"type", b"hash"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hexpire_httl() {
+        let server = new_server();
+        run(
+            &server,
+            &[b"hset", b"h", b"a", b"1", b"b", b"2", b"c", b"3"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            ints(&[1, -2]),
+            run(
+                &server,
+                &[b"hexpire", b"h", b"100", b"fields", b"2", b"a", b"nope"]
+            )
+            .await
+            .unwrap()
+        );
+        // 50 isn't greater than a's 100, and b has no expiry for NX to refuse
+        assert_eq!(
+            ints(&[0]),
+            run(
+                &server,
+                &[b"hexpire", b"h", b"50", b"gt", b"fields", b"1", b"a"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[0, 1]),
+            run(
+                &server,
+                &[b"hexpire", b"h", b"50", b"NX", b"FIELDS", b"2", b"a", b"b"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[100, 50, -1, -2]),
+            run(
+                &server,
+                &[b"httl", b"h", b"fields", b"4", b"a", b"b", b"c", b"nope"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[-2, -2]),
+            run(
+                &server,
+                &[b"hexpire", b"nope", b"10", b"fields", b"2", b"a", b"b"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[-2]),
+            run(&server, &[b"httl", b"nope", b"fields", b"1", b"a"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hpersist() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1", b"c", b"3"])
+            .await
+            .unwrap();
+        run(&server, &[b"hexpire", b"h", b"100", b"fields", b"1", b"a"])
+            .await
+            .unwrap();
+        assert_eq!(
+            ints(&[1, -1, -2]),
+            run(
+                &server,
+                &[b"hpersist", b"h", b"fields", b"3", b"a", b"c", b"nope"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[-1]),
+            run(&server, &[b"httl", b"h", b"fields", b"1", b"a"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_field_expiry_on_write() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1", b"b", b"2"])
+            .await
+            .unwrap();
+        run(&server, &[b"hexpire", b"h", b"50", b"fields", b"1", b"b"])
+            .await
+            .unwrap();
+        // incrementing a field keeps its expiry, setting it drops it
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"hincrby", b"h", b"b", b"1"]).await.unwrap()
+        );
+        assert_eq!(
+            ints(&[50]),
+            run(&server, &[b"httl", b"h", b"fields", b"1", b"b"])
+                .await
+                .unwrap()
+        );
+        run(&server, &[b"hset", b"h", b"b", b"4"]).await.unwrap();
+        assert_eq!(
+            ints(&[-1]),
+            run(&server, &[b"httl", b"h", b"fields", b"1", b"b"])
+                .await
+                .unwrap()
+        );
+        // a time that's already passed deletes the field
+        assert_eq!(
+            ints(&[2]),
+            run(&server, &[b"hpexpire", b"h", b"0", b"fields", b"1", b"a"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"hexists", b"h", b"a"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_fields_are_removed() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1", b"b", b"2"])
+            .await
+            .unwrap();
+        // fields that have expired are gone when the hash is next touched
+        run(&server, &[b"hpexpire", b"h", b"1", b"fields", b"1", b"a"])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"hlen", b"h"]).await.unwrap()
+        );
+        // and so is the hash, if that empties it
+        run(&server, &[b"hpexpire", b"h", b"1", b"fields", b"1", b"b"])
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(!exists(&server, b"h").await);
+        assert!(server.read().await.store.hash_store.get(b"h").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hexpire_errors() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1"]).await.unwrap();
+        assert_eq!(
+            "ERR The `numfields` parameter must match the number of arguments",
+            run_err(&server, &[b"hexpire", b"h", b"10", b"fields", b"2", b"a"]).await
+        );
+        assert_eq!(
+            "ERR NX and XX, GT or LT options at the same time are not compatible",
+            run_err(
+                &server,
+                &[b"hexpire", b"h", b"10", b"nx", b"xx", b"fields", b"1", b"a"]
+            )
+            .await
+        );
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(
+                &server,
+                &[b"hexpire", b"h", b"10", b"later", b"fields", b"1", b"a"]
+            )
+            .await
+        );
+        assert_eq!(
+            "ERR invalid expire time in 'hexpire' command",
+            run_err(&server, &[b"hexpire", b"h", b"-1", b"fields", b"1", b"a"]).await
+        );
+        assert_eq!(
+            "ERR Mandatory argument FIELDS is missing or not at the right position",
+            run_err(&server, &[b"httl", b"h", b"field", b"1", b"a"]).await
+        );
+        assert_eq!(
+            ints(&[-1]),
+            run(&server, &[b"httl", b"h", b"fields", b"1", b"a"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_fields() {
+        let server = new_server();
+        run(&server, &[b"hset", b"h", b"a", b"1"]).await.unwrap();
+        let cmds: [&[&[u8]]; 4] = [
+            &[b"hexpire", b"h", b"100", b"FIELDS", b"0"],
+            &[b"hpexpire", b"h", b"100", b"nx", b"fields", b"0"],
+            &[b"httl", b"h", b"fields", b"0"],
+            &[b"hpersist", b"h", b"fields", b"0"],
+        ];
+        for cmd in cmds {
+            assert_eq!(
+                "ERR Parameter `numFields` should be greater than 0",
+                run_err(&server, cmd).await
+            );
+        }
+        assert_eq!(
+            CommandError::InvalidArgs("hexpire".to_string()).to_string(),
+            run_err(&server, &[b"hexpire", b"h", b"100", b"fields"]).await
+        );
+    }
+}
@@ -0,0 +1,556 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::glob::glob_match;
+use crate::server::store::free_in_background;
+
+use super::{
+    next_arg, parse_int, to_lowercase, Args, CommandFlags, CommandHandler, CommandResult, Context,
+    KeySpec, OptionEntry, R,
+};
+
+// TYPE key
+pub struct Type;
+
+impl CommandHandler for Type {
+    fn name(&self) -> &'static str {
+        "type"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Determines the type of value stored at a key."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let tipe = ctx
+            .server
+            .store
+            .key_type(&key)
+            .map_or("none", |t| t.to_str());
+        Ok(CommandResult::Reply(DataType::simple_str(tipe)))
+    }
+}
+
+// DEL key [key ...]
+pub struct Del;
+
+impl CommandHandler for Del {
+    fn name(&self) -> &'static str {
+        "del"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Deletes one or more keys."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let store = &mut ctx.server.store;
+        let deleted = args.iter().filter(|key| store.remove(key)).count();
+        Ok(CommandResult::Reply(DataType::Integer(deleted as i64)))
+    }
+}
+
+// UNLINK key [key ...]
+pub struct Unlink;
+
+impl CommandHandler for Unlink {
+    fn name(&self) -> &'static str {
+        "unlink"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Asynchronously deletes one or more keys."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let store = &mut ctx.server.store;
+        let unlinked = args.iter().filter(|key| store.unlink(key)).count();
+        Ok(CommandResult::Reply(DataType::Integer(unlinked as i64)))
+    }
+}
+
+// EXISTS key [key ...] -> a key given more than once is counted each time
+pub struct Exists;
+
+impl CommandHandler for Exists {
+    fn name(&self) -> &'static str {
+        "exists"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Determines whether one or more keys exist."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let store = &ctx.server.store;
+        let existing = args.iter().filter(|key| store.contains_key(key)).count();
+        Ok(CommandResult::Reply(DataType::Integer(existing as i64)))
+    }
+}
+
+// KEYS pattern
+pub struct Keys;
+
+impl CommandHandler for Keys {
+    fn name(&self) -> &'static str {
+        "keys"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns all key names that match a pattern."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let pattern = next_arg(&mut args, self.name())?;
+        let keys = ctx
+            .server
+            .store
+            .keys()
+            .filter(|key| glob_match(&pattern, key, false))
+            .map(|key| DataType::BulkString(key.clone()))
+            .collect::<VecDeque<_>>();
+        Ok(CommandResult::Reply(DataType::Array(keys)))
+    }
+}
+
+// DBSIZE
+pub struct DbSize;
+
+impl CommandHandler for DbSize {
+    fn name(&self) -> &'static str {
+        "dbsize"
+    }
+
+    fn arity(&self) -> i64 {
+        1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the number of keys in the database."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn execute(&self, ctx: &mut Context, _args: Args) -> R<CommandResult> {
+        let len = ctx.server.store.len();
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// RANDOMKEY
+pub struct RandomKey;
+
+impl CommandHandler for RandomKey {
+    fn name(&self) -> &'static str {
+        "randomkey"
+    }
+
+    fn arity(&self) -> i64 {
+        1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns a random key name from the database."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn execute(&self, ctx: &mut Context, _args: Args) -> R<CommandResult> {
+        let reply = match ctx.server.store.random_key() {
+            Some(key) => DataType::BulkString(key.clone()),
+            None => DataType::NullBulkString,
+        };
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// The args SCAN shares with the scans over a single collection: cursor [MATCH pattern] [COUNT count],
+// plus SCAN's [TYPE type] and HSCAN's [NOVALUES]
+pub struct ScanArgs {
+    pub cursor: u64,
+    pub pattern: Option<Bytes>,
+    pub count: usize,
+    pub tipe: Option<String>,
+    pub novalues: bool,
+}
+
+impl ScanArgs {
+    const DEFAULT_COUNT: usize = 10;
+
+    // Options the handler doesn't declare are a syntax error, so e.g. TYPE is only taken by SCAN
+    pub fn parse(handler: &dyn CommandHandler, mut args: Args) -> R<Self> {
+        let cursor = next_arg(&mut args, handler.name())?;
+        let cursor = parse_int::<u64>(&cursor)
+            .map_err(|_| CommandError::CommandFailed("ERR invalid cursor".to_string()))?;
+        let mut scan = Self {
+            cursor,
+            pattern: None,
+            count: Self::DEFAULT_COUNT,
+            tipe: None,
+            novalues: false,
+        };
+        for option in handler.parse_options(args)? {
+            match option.name {
+                "match" => scan.pattern = Some(option.value()?),
+                "count" => {
+                    scan.count = match parse_int::<usize>(&option.value()?)? {
+                        0 => return Err(CommandError::InvalidOption),
+                        count => count,
+                    }
+                }
+                "type" => scan.tipe = Some(to_lowercase(&option.value()?)),
+                "novalues" => scan.novalues = true,
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        Ok(scan)
+    }
+
+    pub fn matches(&self, key: &[u8]) -> bool {
+        match &self.pattern {
+            Some(pattern) => glob_match(pattern, key, false),
+            None => true,
+        }
+    }
+
+    // [next cursor, [items...]]
+    pub fn reply(next: u64, items: impl Iterator<Item = Bytes>) -> DataType {
+        DataType::Array(VecDeque::from([
+            DataType::BulkString(Bytes::from(next.to_string())),
+            DataType::Array(items.map(DataType::BulkString).collect()),
+        ]))
+    }
+}
+
+// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+pub struct Scan;
+
+impl CommandHandler for Scan {
+    fn name(&self) -> &'static str {
+        "scan"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Iterates over the key names in the database."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("match", 1),
+            OptionEntry::new("count", 1),
+            OptionEntry::new("type", 1),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let scan = ScanArgs::parse(self, args)?;
+        // like MATCH, TYPE filters what's returned, it doesn't change how far a call gets
+        let (next, keys) = ctx.server.store.scan(scan.cursor, scan.count);
+        let keys = keys
+            .into_iter()
+            .filter(|(key, _)| scan.matches(key))
+            .filter(|(_, tipe)| match &scan.tipe {
+                Some(t) => t == tipe.to_str(),
+                None => true,
+            })
+            .map(|(key, _)| key.clone());
+        Ok(CommandResult::Reply(ScanArgs::reply(next, keys)))
+    }
+}
+
+// FLUSHDB and FLUSHALL are the same thing, as there's only one database
+fn flush(ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+    let lazy = match args.pop_front().map(|arg| to_lowercase(&arg)) {
+        None => false,
+        Some(mode) if args.is_empty() && mode == "async" => true,
+        Some(mode) if args.is_empty() && mode == "sync" => false,
+        Some(_) => return Err(CommandError::InvalidOption),
+    };
+    let old = ctx.server.store.flush();
+    match lazy {
+        true => free_in_background(old),
+        false => drop(old),
+    }
+    Ok(CommandResult::Reply(DataType::simple_str("OK")))
+}
+
+// FLUSHDB [ASYNC | SYNC]
+pub struct FlushDb;
+
+impl CommandHandler for FlushDb {
+    fn name(&self) -> &'static str {
+        "flushdb"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes all keys from the current database."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        flush(ctx, args)
+    }
+}
+
+// FLUSHALL [ASYNC | SYNC]
+pub struct FlushAll;
+
+impl CommandHandler for FlushAll {
+    fn name(&self) -> &'static str {
+        "flushall"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes all keys from all databases."
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        flush(ctx, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::super::tests::{new_server, run};
+    use crate::resp::data::DataType;
+
+    async fn fill(server: &std::sync::Arc<tokio::sync::RwLock<crate::server::Server>>) {
+        for key in ["a", "b", "user:1", "user:2"] {
+            run(server, &[b"set", key.as_bytes(), b"v"]).await.unwrap();
+        }
+        run(server, &[b"xadd", b"stream", b"1-1", b"f", b"v"])
+            .await
+            .unwrap();
+    }
+
+    fn sorted(reply: DataType) -> Vec<String> {
+        let mut keys = match reply {
+            DataType::Array(keys) => keys
+                .iter()
+                .map(|k| k.try_to_string().unwrap())
+                .collect::<Vec<_>>(),
+            other => panic!("expected an array, got {:?}", other),
+        };
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn test_del_exists_unlink() {
+        let server = new_server();
+        fill(&server).await;
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"exists", b"a", b"a", b"stream", b"nope"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"del", b"a", b"stream", b"nope", b"a"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"a", b"stream"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"unlink", b"b", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"dbsize"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keys() {
+        let server = new_server();
+        fill(&server).await;
+        let all = run(&server, &[b"keys", b"*"]).await.unwrap();
+        assert_eq!(vec!["a", "b", "stream", "user:1", "user:2"], sorted(all));
+        let users = run(&server, &[b"keys", b"user:*"]).await.unwrap();
+        assert_eq!(vec!["user:1", "user:2"], sorted(users));
+        let single = run(&server, &[b"keys", b"?"]).await.unwrap();
+        assert_eq!(vec!["a", "b"], sorted(single));
+    }
+
+    // Follows the cursor to the end, returning every key seen
+    async fn scan_all(
+        server: &std::sync::Arc<tokio::sync::RwLock<crate::server::Server>>,
+        options: &[&[u8]],
+    ) -> Vec<String> {
+        let (mut cursor, mut keys) = (Bytes::from("0"), Vec::new());
+        loop {
+            let mut cmd: Vec<&[u8]> = vec![b"scan", &cursor];
+            cmd.extend(options);
+            let mut reply = match run(server, &cmd).await.unwrap() {
+                DataType::Array(reply) => reply,
+                other => panic!("expected an array, got {:?}", other),
+            };
+            keys.extend(sorted(reply.pop_back().unwrap()));
+            cursor = match reply.pop_front().unwrap() {
+                DataType::BulkString(cursor) => cursor,
+                other => panic!("expected a cursor, got {:?}", other),
+            };
+            if cursor == "0" {
+                keys.sort();
+                return keys;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scan() {
+        let server = new_server();
+        fill(&server).await;
+        assert_eq!(
+            vec!["a", "b", "stream", "user:1", "user:2"],
+            scan_all(&server, &[b"count", b"1"]).await
+        );
+        assert_eq!(
+            vec!["user:1", "user:2"],
+            scan_all(&server, &[b"MATCH", b"user:*", b"COUNT", b"2"]).await
+        );
+        assert_eq!(
+            vec!["stream"],
+            scan_all(&server, &[b"type", b"stream"]).await
+        );
+        assert!(run(&server, &[b"scan", b"-1"]).await.is_err());
+        assert!(run(&server, &[b"scan", b"0", b"count", b"0"])
+            .await
+            .is_err());
+        assert!(run(&server, &[b"scan", b"0", b"novalues"]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_randomkey_and_flush() {
+        let server = new_server();
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"randomkey"]).await.unwrap()
+        );
+        fill(&server).await;
+        match run(&server, &[b"randomkey"]).await.unwrap() {
+            DataType::BulkString(key) => assert_ne!(Bytes::new(), key),
+            other => panic!("expected a key, got {:?}", other),
+        }
+        assert!(run(&server, &[b"flushall", b"later"]).await.is_err());
+        run(&server, &[b"flushall", b"async"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"dbsize"]).await.unwrap()
+        );
+        fill(&server).await;
+        run(&server, &[b"flushdb"]).await.unwrap();
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"randomkey"]).await.unwrap()
+        );
+    }
+}
@@ -0,0 +1,1908 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::store::blocked::Serve;
+use crate::server::store::list::QuickList;
+use crate::server::store::{KeyType, Store};
+
+use super::string::bulk_or_null;
+use super::{
+    next_arg, parse_int, to_lowercase, Args, Blocking, CommandFlags, CommandHandler, CommandResult,
+    Context, KeySpec, OptionEntry, R,
+};
+
+// The list at `key`, if it holds one
+fn get_list<'a>(store: &'a Store, key: &[u8]) -> R<Option<&'a QuickList>> {
+    store.check_type(key, KeyType::List)?;
+    Ok(store.list_store.get(key))
+}
+
+fn get_list_mut<'a>(store: &'a mut Store, key: &[u8]) -> R<Option<&'a mut QuickList>> {
+    store.check_type(key, KeyType::List)?;
+    Ok(store.list_store.get_mut(key))
+}
+
+// A list that's been emptied doesn't exist anymore
+fn remove_if_empty(store: &mut Store, key: &[u8]) {
+    if store.list_store.get(key).is_some_and(QuickList::is_empty) {
+        store.remove(key);
+    }
+}
+
+// Which end of a list to push to or pop from. Left is the head.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum End {
+    Left,
+    Right,
+}
+
+impl End {
+    fn parse(arg: &[u8]) -> R<Self> {
+        match to_lowercase(arg).as_str() {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            _ => Err(CommandError::InvalidOption),
+        }
+    }
+
+    fn push(&self, list: &mut QuickList, val: Bytes) {
+        match self {
+            Self::Left => list.push_front(val),
+            Self::Right => list.push_back(val),
+        }
+    }
+
+    fn pop(&self, list: &mut QuickList) -> Option<Bytes> {
+        match self {
+            Self::Left => list.pop_front(),
+            Self::Right => list.pop_back(),
+        }
+    }
+}
+
+// Pops up to `count` elements off the list at `key`, removing the key if that empties it
+fn pop_n(store: &mut Store, key: &[u8], end: End, count: usize) -> R<Vec<Bytes>> {
+    let vals = match get_list_mut(store, key)? {
+        Some(list) => (0..count).map_while(|_| end.pop(list)).collect(),
+        None => Vec::new(),
+    };
+    remove_if_empty(store, key);
+    Ok(vals)
+}
+
+// Pops an element off `src` and pushes it onto `dst`, which can be the same list (-> a rotation). None if
+// there's no `src`.
+fn move_element(store: &mut Store, src: &[u8], dst: Bytes, from: End, to: End) -> R<Option<Bytes>> {
+    if get_list(store, src)?.is_none() {
+        return Ok(None);
+    }
+    store.check_type(&dst, KeyType::List)?;
+    let val = pop_n(store, src, from, 1)?.pop();
+    if let Some(val) = &val {
+        to.push(store.list_for_push(dst), val.clone());
+    }
+    Ok(val)
+}
+
+// An index into a list, negative -> counted back from the end. None if it's out of range.
+fn to_index(index: i64, len: usize) -> Option<usize> {
+    let index = if index < 0 { len as i64 + index } else { index };
+    (0..len as i64).contains(&index).then_some(index as usize)
+}
+
+// A start and stop (both inclusive, negative -> counted back from the end) clamped to the list. None if the
+// range is empty.
+fn to_range(start: i64, stop: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { len + start } else { start }.max(0);
+    let stop = if stop < 0 { len + stop } else { stop }.min(len - 1);
+    (start <= stop).then_some((start as usize, stop as usize))
+}
+
+fn parse_count(arg: &[u8]) -> R<usize> {
+    parse_int::<i64>(arg)?.try_into().map_err(|_| {
+        CommandError::CommandFailed("ERR value is out of range, must be positive".to_string())
+    })
+}
+
+// The timeout of a blocking command, in seconds -> None to wait for as long as it takes
+fn parse_timeout(arg: &[u8]) -> R<Option<Duration>> {
+    let secs = std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|secs| secs.is_finite())
+        .ok_or_else(|| {
+            CommandError::CommandFailed("ERR timeout is not a float or out of range".to_string())
+        })?;
+    if secs < 0.0 {
+        return Err(CommandError::CommandFailed(
+            "ERR timeout is negative".to_string(),
+        ));
+    }
+    if secs == 0.0 {
+        return Ok(None);
+    }
+    Duration::try_from_secs_f64(secs)
+        .map(Some)
+        .map_err(|_| CommandError::CommandFailed("ERR timeout is out of range".to_string()))
+}
+
+// Blocks the client on `keys` until `serve` gets it a reply from one of them, see Store::blocked
+fn block(
+    ctx: &mut Context,
+    keys: Vec<Bytes>,
+    timeout: Option<Duration>,
+    timed_out: DataType,
+    serve: Serve,
+) -> CommandResult {
+    let (id, rx) = ctx.server.store.blocked.block(keys, serve);
+    CommandResult::Block(Blocking {
+        id,
+        rx,
+        timeout,
+        timed_out,
+    })
+}
+
+// LPUSH, RPUSH, LPUSHX and RPUSHX -> the length of the list after the push. The X variants only push onto a
+// list that exists.
+fn push(
+    handler: &dyn CommandHandler,
+    ctx: &mut Context,
+    mut args: Args,
+    end: End,
+    only_existing: bool,
+) -> R<CommandResult> {
+    let key = next_arg(&mut args, handler.name())?;
+    let store = &mut ctx.server.store;
+    if get_list(store, &key)?.is_none() && only_existing {
+        return Ok(CommandResult::Reply(DataType::Integer(0)));
+    }
+    let list = store.list_for_push(key);
+    for val in args {
+        end.push(list, val);
+    }
+    Ok(CommandResult::Reply(DataType::Integer(list.len() as i64)))
+}
+
+// LPOP and RPOP. Without a count, the reply is the element. With one, it's an array of elements.
+fn pop(
+    handler: &dyn CommandHandler,
+    ctx: &mut Context,
+    mut args: Args,
+    end: End,
+) -> R<CommandResult> {
+    let key = next_arg(&mut args, handler.name())?;
+    let count = match args.pop_front() {
+        Some(count) => Some(parse_count(&count)?),
+        None => None,
+    };
+    if !args.is_empty() {
+        return Err(CommandError::InvalidOption);
+    }
+    let store = &mut ctx.server.store;
+    if get_list(store, &key)?.is_none() {
+        return Ok(CommandResult::Reply(match count {
+            Some(_) => DataType::NullArray,
+            None => DataType::NullBulkString,
+        }));
+    }
+    let reply = match count {
+        Some(count) => {
+            let vals = pop_n(store, &key, end, count)?;
+            DataType::Array(vals.into_iter().map(DataType::BulkString).collect())
+        }
+        None => bulk_or_null(pop_n(store, &key, end, 1)?.pop()),
+    };
+    Ok(CommandResult::Reply(reply))
+}
+
+// LPUSH key element [element ...]
+pub struct LPush;
+
+impl CommandHandler for LPush {
+    fn name(&self) -> &'static str {
+        "lpush"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Prepends one or more elements to a list. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        push(self, ctx, args, End::Left, false)
+    }
+}
+
+// RPUSH key element [element ...]
+pub struct RPush;
+
+impl CommandHandler for RPush {
+    fn name(&self) -> &'static str {
+        "rpush"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Appends one or more elements to a list. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        push(self, ctx, args, End::Right, false)
+    }
+}
+
+// LPUSHX key element [element ...]
+pub struct LPushX;
+
+impl CommandHandler for LPushX {
+    fn name(&self) -> &'static str {
+        "lpushx"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Prepends one or more elements to a list only when the list exists."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        push(self, ctx, args, End::Left, true)
+    }
+}
+
+// RPUSHX key element [element ...]
+pub struct RPushX;
+
+impl CommandHandler for RPushX {
+    fn name(&self) -> &'static str {
+        "rpushx"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Appends one or more elements to a list only when the list exists."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        push(self, ctx, args, End::Right, true)
+    }
+}
+
+// LPOP key [count]
+pub struct LPop;
+
+impl CommandHandler for LPop {
+    fn name(&self) -> &'static str {
+        "lpop"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the first elements in a list after removing it. Deletes the list if the last element was popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        pop(self, ctx, args, End::Left)
+    }
+}
+
+// RPOP key [count]
+pub struct RPop;
+
+impl CommandHandler for RPop {
+    fn name(&self) -> &'static str {
+        "rpop"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns and removes the last elements of a list. Deletes the list if the last element was popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        pop(self, ctx, args, End::Right)
+    }
+}
+
+// LLEN key
+pub struct LLen;
+
+impl CommandHandler for LLen {
+    fn name(&self) -> &'static str {
+        "llen"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the length of a list."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let len = get_list(&ctx.server.store, &key)?.map_or(0, QuickList::len);
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// LRANGE key start stop
+pub struct LRange;
+
+impl CommandHandler for LRange {
+    fn name(&self) -> &'static str {
+        "lrange"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns a range of elements from a list."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let start = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let stop = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let vals = match get_list(&ctx.server.store, &key)? {
+            Some(list) => match to_range(start, stop, list.len()) {
+                Some((start, stop)) => list
+                    .iter()
+                    .skip(start)
+                    .take(stop - start + 1)
+                    .map(|v| DataType::BulkString(v.clone()))
+                    .collect(),
+                None => VecDeque::new(),
+            },
+            None => VecDeque::new(),
+        };
+        Ok(CommandResult::Reply(DataType::Array(vals)))
+    }
+}
+
+// LINDEX key index
+pub struct LIndex;
+
+impl CommandHandler for LIndex {
+    fn name(&self) -> &'static str {
+        "lindex"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns an element from a list by its index."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let index = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let val = get_list(&ctx.server.store, &key)?
+            .and_then(|list| list.get(to_index(index, list.len())?))
+            .cloned();
+        Ok(CommandResult::Reply(bulk_or_null(val)))
+    }
+}
+
+// LSET key index element
+pub struct LSet;
+
+impl CommandHandler for LSet {
+    fn name(&self) -> &'static str {
+        "lset"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the value of an element in a list by its index."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let index = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let val = next_arg(&mut args, self.name())?;
+        let list = get_list_mut(&mut ctx.server.store, &key)?
+            .ok_or_else(|| CommandError::CommandFailed("ERR no such key".to_string()))?;
+        match to_index(index, list.len()) {
+            Some(index) => list.set(index, val),
+            None => {
+                return Err(CommandError::CommandFailed(
+                    "ERR index out of range".to_string(),
+                ))
+            }
+        };
+        Ok(CommandResult::Reply(DataType::simple_str("OK")))
+    }
+}
+
+// LREM key count element -> removes the first `count` elements equal to `element`, or the last ones if
+// `count` is negative, or all of them if it's 0
+pub struct LRem;
+
+impl CommandHandler for LRem {
+    fn name(&self) -> &'static str {
+        "lrem"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes elements from a list. Deletes the list if the last element was removed."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let count = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let elem = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        let list = match get_list_mut(store, &key)? {
+            Some(list) => list,
+            None => return Ok(CommandResult::Reply(DataType::Integer(0))),
+        };
+        let matches = list.iter().filter(|v| **v == elem).count();
+        let limit = match count {
+            0 => matches,
+            count => (count.unsigned_abs() as usize).min(matches),
+        };
+        // from the tail -> leave the matches before the last `limit` of them
+        let skip = match count < 0 {
+            true => matches - limit,
+            false => 0,
+        };
+        let mut seen = 0;
+        list.retain(|v| {
+            if *v != elem {
+                return true;
+            }
+            seen += 1;
+            seen <= skip || seen > skip + limit
+        });
+        remove_if_empty(store, &key);
+        Ok(CommandResult::Reply(DataType::Integer(limit as i64)))
+    }
+}
+
+// LTRIM key start stop
+pub struct LTrim;
+
+impl CommandHandler for LTrim {
+    fn name(&self) -> &'static str {
+        "ltrim"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes elements from both ends of a list. Deletes the list if all elements were trimmed."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let start = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let stop = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let store = &mut ctx.server.store;
+        if let Some(list) = get_list_mut(store, &key)? {
+            match to_range(start, stop, list.len()) {
+                Some((start, stop)) => {
+                    let len = list.len();
+                    for _ in stop + 1..len {
+                        list.pop_back();
+                    }
+                    for _ in 0..start {
+                        list.pop_front();
+                    }
+                }
+                None => {
+                    store.remove(&key);
+                }
+            }
+        }
+        Ok(CommandResult::Reply(DataType::simple_str("OK")))
+    }
+}
+
+// LINSERT key <BEFORE | AFTER> pivot element
+pub struct LInsert;
+
+impl CommandHandler for LInsert {
+    fn name(&self) -> &'static str {
+        "linsert"
+    }
+
+    fn arity(&self) -> i64 {
+        5
+    }
+
+    fn summary(&self) -> &'static str {
+        "Inserts an element before or after another element in a list."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let after = match to_lowercase(&next_arg(&mut args, self.name())?).as_str() {
+            "before" => false,
+            "after" => true,
+            _ => return Err(CommandError::InvalidOption),
+        };
+        let pivot = next_arg(&mut args, self.name())?;
+        let val = next_arg(&mut args, self.name())?;
+        let list = match get_list_mut(&mut ctx.server.store, &key)? {
+            Some(list) => list,
+            None => return Ok(CommandResult::Reply(DataType::Integer(0))),
+        };
+        // -1 -> there's no pivot
+        let pivot = list.iter().position(|v| *v == pivot);
+        let len = match pivot {
+            Some(index) => {
+                list.insert(index + after as usize, val);
+                list.len() as i64
+            }
+            None => -1,
+        };
+        Ok(CommandResult::Reply(DataType::Integer(len)))
+    }
+}
+
+// LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]
+pub struct LPos;
+
+impl CommandHandler for LPos {
+    fn name(&self) -> &'static str {
+        "lpos"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the index of matching elements in a list."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("rank", 1),
+            OptionEntry::new("count", 1),
+            OptionEntry::new("maxlen", 1),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let elem = next_arg(&mut args, self.name())?;
+        let (mut rank, mut count, mut maxlen) = (1i64, None, 0usize);
+        for option in self.parse_options(args)? {
+            match option.name {
+                "rank" => {
+                    rank = parse_int(&option.value()?)?;
+                    if rank == 0 {
+                        return Err(CommandError::CommandFailed(
+                            "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... \
+                             or use negative to start from the end of the list"
+                                .to_string(),
+                        ));
+                    }
+                }
+                "count" => {
+                    count = Some(
+                        parse_int::<i64>(&option.value()?)?
+                            .try_into()
+                            .map_err(|_| {
+                                CommandError::CommandFailed(
+                                    "ERR COUNT can't be negative".to_string(),
+                                )
+                            })?,
+                    )
+                }
+                _ => {
+                    maxlen = parse_int::<i64>(&option.value()?)?
+                        .try_into()
+                        .map_err(|_| {
+                            CommandError::CommandFailed("ERR MAXLEN can't be negative".to_string())
+                        })?
+                }
+            }
+        }
+        let positions = match get_list(&ctx.server.store, &key)? {
+            Some(list) => {
+                let len = list.len();
+                // a negative rank searches from the tail, but the positions are still from the head
+                let indexed: Box<dyn Iterator<Item = (usize, &Bytes)>> = match rank > 0 {
+                    true => Box::new(list.iter().enumerate()),
+                    false => Box::new(list.iter().rev().enumerate().map(|(i, v)| (len - 1 - i, v))),
+                };
+                let maxlen = if maxlen == 0 { len } else { maxlen };
+                // COUNT 0 -> every match
+                let wanted = match count {
+                    Some(0) => usize::MAX,
+                    Some(count) => count,
+                    None => 1,
+                };
+                indexed
+                    .take(maxlen)
+                    .filter(|(_, v)| **v == elem)
+                    .skip(rank.unsigned_abs() as usize - 1)
+                    .take(wanted)
+                    .map(|(i, _)| i as i64)
+                    .collect::<Vec<_>>()
+            }
+            None => Vec::new(),
+        };
+        let reply = match count {
+            Some(_) => DataType::Array(positions.into_iter().map(DataType::Integer).collect()),
+            None => match positions.first() {
+                Some(pos) => DataType::Integer(*pos),
+                None => DataType::NullBulkString,
+            },
+        };
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// LMOVE source destination <LEFT | RIGHT> <LEFT | RIGHT>
+pub struct LMove;
+
+impl CommandHandler for LMove {
+    fn name(&self) -> &'static str {
+        "lmove"
+    }
+
+    fn arity(&self) -> i64 {
+        5
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns an element after popping it from one list and pushing it to another. Deletes the list if the \
+         last element was moved."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, 2, 1)
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let src = next_arg(&mut args, self.name())?;
+        let dst = next_arg(&mut args, self.name())?;
+        let from = End::parse(&next_arg(&mut args, self.name())?)?;
+        let to = End::parse(&next_arg(&mut args, self.name())?)?;
+        let val = move_element(&mut ctx.server.store, &src, dst, from, to)?;
+        Ok(CommandResult::Reply(bulk_or_null(val)))
+    }
+}
+
+// The args of LMPOP: numkeys key [key ...] <LEFT | RIGHT> [COUNT count]
+#[derive(Debug)]
+struct MPopArgs {
+    keys: Vec<Bytes>,
+    end: End,
+    count: usize,
+}
+
+impl MPopArgs {
+    fn parse(handler: &dyn CommandHandler, mut args: Args) -> R<Self> {
+        let numkeys = next_arg(&mut args, handler.name())?;
+        let numkeys = match parse_int::<usize>(&numkeys) {
+            Ok(n) if n > 0 => n,
+            _ => {
+                return Err(CommandError::CommandFailed(
+                    "ERR numkeys should be greater than 0".to_string(),
+                ))
+            }
+        };
+        // the keys, then at least the end
+        if numkeys >= args.len() {
+            return Err(CommandError::InvalidOption);
+        }
+        let keys = args.drain(..numkeys).collect();
+        let end = End::parse(&next_arg(&mut args, handler.name())?)?;
+        let count = match handler.parse_options(args)?.pop_back() {
+            Some(option) => match parse_int::<usize>(&option.value()?) {
+                Ok(n) if n > 0 => n,
+                _ => {
+                    return Err(CommandError::CommandFailed(
+                        "ERR count should be greater than 0".to_string(),
+                    ))
+                }
+            },
+            None => 1,
+        };
+        Ok(Self { keys, end, count })
+    }
+
+    // Pops from the first of the keys holding a list
+    fn pop(&self, store: &mut Store) -> R<Option<DataType>> {
+        for key in &self.keys {
+            if let Some(reply) = mpop_from(store, key, self.end, self.count)? {
+                return Ok(Some(reply));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// Pops up to `count` elements off the list at `key` -> [key, [element ...]], or None if there's no list
+fn mpop_from(store: &mut Store, key: &Bytes, end: End, count: usize) -> R<Option<DataType>> {
+    if get_list(store, key)?.is_none() {
+        return Ok(None);
+    }
+    let vals = pop_n(store, key, end, count)?;
+    Ok(Some(DataType::Array(VecDeque::from([
+        DataType::BulkString(key.clone()),
+        DataType::Array(vals.into_iter().map(DataType::BulkString).collect()),
+    ]))))
+}
+
+// LMPOP numkeys key [key ...] <LEFT | RIGHT> [COUNT count]
+pub struct LMPop;
+
+impl CommandHandler for LMPop {
+    fn name(&self) -> &'static str {
+        "lmpop"
+    }
+
+    fn arity(&self) -> i64 {
+        -4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns multiple elements from a list after removing them. Deletes the list if the last element was \
+         popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE | CommandFlags::MOVABLEKEYS
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::keynum(1)
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[OptionEntry::new("count", 1)];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let mpop = MPopArgs::parse(self, args)?;
+        let reply = mpop
+            .pop(&mut ctx.server.store)?
+            .unwrap_or(DataType::NullArray);
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// BLPOP and BRPOP -> [key, element], from the first of the keys holding a list
+fn blocking_pop(
+    handler: &dyn CommandHandler,
+    ctx: &mut Context,
+    mut args: Args,
+    end: End,
+) -> R<CommandResult> {
+    // the timeout comes last, after any number of keys
+    let timeout = args
+        .pop_back()
+        .ok_or_else(|| CommandError::InvalidArgs(handler.name().to_string()))?;
+    let timeout = parse_timeout(&timeout)?;
+    let keys = Vec::from(args);
+    let store = &mut ctx.server.store;
+    let pair = |key: &Bytes, val| {
+        DataType::Array(VecDeque::from([
+            DataType::BulkString(key.clone()),
+            DataType::BulkString(val),
+        ]))
+    };
+    for key in &keys {
+        if let Some(val) = pop_n(store, key, end, 1)?.pop() {
+            return Ok(CommandResult::Reply(pair(key, val)));
+        }
+    }
+    let serve: Serve = Box::new(move |store, key| {
+        let val = pop_n(store, key, end, 1).ok()?.pop()?;
+        Some(pair(key, val))
+    });
+    Ok(block(ctx, keys, timeout, DataType::NullArray, serve))
+}
+
+// BLPOP key [key ...] timeout
+pub struct BLPop;
+
+impl CommandHandler for BLPop {
+    fn name(&self) -> &'static str {
+        "blpop"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes and returns the first element in a list. Blocks until an element is available otherwise. \
+         Deletes the list if the last element was popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE | CommandFlags::BLOCKING
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, -2, 1)
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        blocking_pop(self, ctx, args, End::Left)
+    }
+}
+
+// BRPOP key [key ...] timeout
+pub struct BRPop;
+
+impl CommandHandler for BRPop {
+    fn name(&self) -> &'static str {
+        "brpop"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes and returns the last element in a list. Blocks until an element is available otherwise. \
+         Deletes the list if the last element was popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE | CommandFlags::BLOCKING
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, -2, 1)
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        blocking_pop(self, ctx, args, End::Right)
+    }
+}
+
+// BLMOVE source destination <LEFT | RIGHT> <LEFT | RIGHT> timeout
+pub struct BLMove;
+
+impl CommandHandler for BLMove {
+    fn name(&self) -> &'static str {
+        "blmove"
+    }
+
+    fn arity(&self) -> i64 {
+        6
+    }
+
+    fn summary(&self) -> &'static str {
+        "Pops an element from a list, pushes it to another list and returns it. Blocks until an element is \
+         available otherwise. Deletes the list if the last element was moved."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE | CommandFlags::BLOCKING
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, 2, 1)
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let src = next_arg(&mut args, self.name())?;
+        let dst = next_arg(&mut args, self.name())?;
+        let from = End::parse(&next_arg(&mut args, self.name())?)?;
+        let to = End::parse(&next_arg(&mut args, self.name())?)?;
+        let timeout = parse_timeout(&next_arg(&mut args, self.name())?)?;
+        if let Some(val) = move_element(&mut ctx.server.store, &src, dst.clone(), from, to)? {
+            return Ok(CommandResult::Reply(DataType::BulkString(val)));
+        }
+        // the destination can have changed type by the time there's something to move
+        let serve: Serve =
+            Box::new(
+                move |store, key| match move_element(store, key, dst.clone(), from, to) {
+                    Ok(val) => val.map(DataType::BulkString),
+                    Err(e) => Some(DataType::SimpleError(e.to_string().into())),
+                },
+            );
+        Ok(block(
+            ctx,
+            vec![src],
+            timeout,
+            DataType::NullBulkString,
+            serve,
+        ))
+    }
+}
+
+// BLMPOP timeout numkeys key [key ...] <LEFT | RIGHT> [COUNT count]
+pub struct BLMPop;
+
+impl CommandHandler for BLMPop {
+    fn name(&self) -> &'static str {
+        "blmpop"
+    }
+
+    fn arity(&self) -> i64 {
+        -5
+    }
+
+    fn summary(&self) -> &'static str {
+        "Pops the first element from one of multiple lists. Blocks until an element is available otherwise. \
+         Deletes the list if the last element was popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "list"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE | CommandFlags::BLOCKING | CommandFlags::MOVABLEKEYS
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::keynum(2)
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[OptionEntry::new("count", 1)];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let timeout = parse_timeout(&next_arg(&mut args, self.name())?)?;
+        let mpop = MPopArgs::parse(self, args)?;
+        let store = &mut ctx.server.store;
+        if let Some(reply) = mpop.pop(store)? {
+            return Ok(CommandResult::Reply(reply));
+        }
+        let MPopArgs { keys, end, count } = mpop;
+        let serve: Serve =
+            Box::new(move |store, key| mpop_from(store, key, end, count).ok().flatten());
+        Ok(block(ctx, keys, timeout, DataType::NullArray, serve))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use tokio::sync::RwLock;
+
+    use super::super::tests::{new_server, run, run_err};
+    use crate::resp::data::DataType;
+    use crate::server::errors::CommandError;
+    use crate::server::Server;
+
+    fn bulks(vals: &[&str]) -> DataType {
+        DataType::Array(vals.iter().map(|v| DataType::bulk_str(v)).collect())
+    }
+
+    fn ints(vals: &[i64]) -> DataType {
+        DataType::Array(vals.iter().map(|v| DataType::Integer(*v)).collect())
+    }
+
+    fn pair(key: &str, vals: &[&str]) -> DataType {
+        DataType::Array([DataType::bulk_str(key), bulks(vals)].into_iter().collect())
+    }
+
+    async fn lrange(server: &Arc<RwLock<Server>>, key: &[u8]) -> DataType {
+        run(server, &[b"lrange", key, b"0", b"-1"]).await.unwrap()
+    }
+
+    async fn exists(server: &Arc<RwLock<Server>>, key: &[u8]) -> bool {
+        run(server, &[b"exists", key]).await.unwrap() == DataType::Integer(1)
+    }
+
+    // A fresh list at `key`, replacing whatever was there
+    async fn rpush(server: &Arc<RwLock<Server>>, key: &[u8], vals: &[&[u8]]) {
+        run(server, &[b"del", key]).await.unwrap();
+        let mut cmd: Vec<&[u8]> = vec![b"rpush", key];
+        cmd.extend(vals);
+        run(server, &cmd).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_push() {
+        let server = new_server();
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"rpush", b"l", b"a", b"b", b"c"])
+                .await
+                .unwrap()
+        );
+        // LPUSH pushes its elements one at a time, so they end up reversed
+        assert_eq!(
+            DataType::Integer(5),
+            run(&server, &[b"lpush", b"l", b"z", b"y"]).await.unwrap()
+        );
+        assert_eq!(
+            bulks(&["y", "z", "a", "b", "c"]),
+            lrange(&server, b"l").await
+        );
+        assert_eq!(
+            DataType::Integer(5),
+            run(&server, &[b"llen", b"l"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"llen", b"nope"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("list"),
+            run(&server, &[b"type", b"l"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pushx() {
+        let server = new_server();
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"lpushx", b"l", b"a"]).await.unwrap()
+        );
+        assert!(!exists(&server, b"l").await);
+        rpush(&server, b"l", &[b"a"]).await;
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"rpushx", b"l", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"lpushx", b"l", b"_"]).await.unwrap()
+        );
+        assert_eq!(bulks(&["_", "a", "b"]), lrange(&server, b"l").await);
+    }
+
+    #[tokio::test]
+    async fn test_pop() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a", b"b", b"c", b"d", b"e"]).await;
+        assert_eq!(
+            DataType::bulk_str("a"),
+            run(&server, &[b"lpop", b"l"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("e"),
+            run(&server, &[b"rpop", b"l"]).await.unwrap()
+        );
+        // with a count the reply is always an array, even an empty one
+        assert_eq!(
+            bulks(&["b"]),
+            run(&server, &[b"lpop", b"l", b"1"]).await.unwrap()
+        );
+        assert_eq!(
+            bulks(&[]),
+            run(&server, &[b"lpop", b"l", b"0"]).await.unwrap()
+        );
+        assert_eq!(
+            bulks(&["d", "c"]),
+            run(&server, &[b"rpop", b"l", b"10"]).await.unwrap()
+        );
+        // popping the last element deletes the list
+        assert!(!exists(&server, b"l").await);
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"lpop", b"l"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullArray,
+            run(&server, &[b"rpop", b"l", b"1"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_pop_errors() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a"]).await;
+        assert_eq!(
+            "ERR value is out of range, must be positive",
+            run_err(&server, &[b"lpop", b"l", b"-1"]).await
+        );
+        assert_eq!(
+            CommandError::NotAnInteger.to_string(),
+            run_err(&server, &[b"rpop", b"l", b"one"]).await
+        );
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"lpop", b"l", b"1", b"2"]).await
+        );
+        assert_eq!(
+            CommandError::InvalidArgs("rpush".to_string()).to_string(),
+            run_err(&server, &[b"rpush", b"l"]).await
+        );
+        assert_eq!(bulks(&["a"]), lrange(&server, b"l").await);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_type() {
+        let server = new_server();
+        run(&server, &[b"set", b"s", b"v"]).await.unwrap();
+        rpush(&server, b"l", &[b"a"]).await;
+        let wrong_type = CommandError::WrongType.to_string();
+        assert_eq!(wrong_type, run_err(&server, &[b"lpush", b"s", b"a"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"rpushx", b"s", b"a"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"lpop", b"s"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"llen", b"s"]).await);
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"lrange", b"s", b"0", b"-1"]).await
+        );
+        // and the other way around
+        assert_eq!(wrong_type, run_err(&server, &[b"get", b"l"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"append", b"l", b"a"]).await);
+        assert_eq!(
+            DataType::bulk_str("v"),
+            run(&server, &[b"get", b"s"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lrange() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a", b"b", b"c", b"d", b"e"]).await;
+        assert_eq!(
+            bulks(&["b", "c"]),
+            run(&server, &[b"lrange", b"l", b"1", b"2"]).await.unwrap()
+        );
+        // out of range indexes are clamped to the list
+        assert_eq!(
+            bulks(&["d", "e"]),
+            run(&server, &[b"lrange", b"l", b"-2", b"100"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulks(&["a"]),
+            run(&server, &[b"lrange", b"l", b"-100", b"0"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulks(&[]),
+            run(&server, &[b"lrange", b"l", b"3", b"1"]).await.unwrap()
+        );
+        assert_eq!(
+            bulks(&[]),
+            run(&server, &[b"lrange", b"l", b"5", b"10"]).await.unwrap()
+        );
+        assert_eq!(bulks(&[]), lrange(&server, b"nope").await);
+    }
+
+    #[tokio::test]
+    async fn test_lindex_lset() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a", b"b", b"c"]).await;
+        assert_eq!(
+            DataType::bulk_str("a"),
+            run(&server, &[b"lindex", b"l", b"0"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("c"),
+            run(&server, &[b"lindex", b"l", b"-1"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"lindex", b"l", b"3"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"lindex", b"l", b"-4"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("OK"),
+            run(&server, &[b"lset", b"l", b"-2", b"x"]).await.unwrap()
+        );
+        assert_eq!(bulks(&["a", "x", "c"]), lrange(&server, b"l").await);
+
+        assert_eq!(
+            "ERR no such key",
+            run_err(&server, &[b"lset", b"nope", b"0", b"x"]).await
+        );
+        assert_eq!(
+            "ERR index out of range",
+            run_err(&server, &[b"lset", b"l", b"3", b"x"]).await
+        );
+        assert_eq!(
+            CommandError::NotAnInteger.to_string(),
+            run_err(&server, &[b"lindex", b"l", b"first"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ltrim() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a", b"b", b"c", b"d", b"e"]).await;
+        assert_eq!(
+            DataType::simple_str("OK"),
+            run(&server, &[b"ltrim", b"l", b"1", b"-2"]).await.unwrap()
+        );
+        assert_eq!(bulks(&["b", "c", "d"]), lrange(&server, b"l").await);
+        // an empty range empties, so deletes, the list
+        assert_eq!(
+            DataType::simple_str("OK"),
+            run(&server, &[b"ltrim", b"l", b"2", b"1"]).await.unwrap()
+        );
+        assert!(!exists(&server, b"l").await);
+    }
+
+    #[tokio::test]
+    async fn test_lrem() {
+        let server = new_server();
+        let vals: &[&[u8]] = &[b"a", b"x", b"b", b"x", b"c", b"x"];
+        // a positive count removes from the head, a negative one from the tail, and 0 removes them all
+        for (count, removed, left) in [
+            (b"2" as &[u8], 2, &["a", "b", "c", "x"] as &[&str]),
+            (b"-2", 2, &["a", "x", "b", "c"]),
+            (b"0", 3, &["a", "b", "c"]),
+            (b"-10", 3, &["a", "b", "c"]),
+        ] {
+            rpush(&server, b"l", vals).await;
+            assert_eq!(
+                DataType::Integer(removed),
+                run(&server, &[b"lrem", b"l", count, b"x"]).await.unwrap()
+            );
+            assert_eq!(bulks(left), lrange(&server, b"l").await);
+        }
+        rpush(&server, b"l", &[b"x", b"x"]).await;
+        run(&server, &[b"lrem", b"l", b"0", b"x"]).await.unwrap();
+        assert!(!exists(&server, b"l").await);
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"lrem", b"nope", b"0", b"x"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_linsert() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a", b"c"]).await;
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"linsert", b"l", b"before", b"c", b"b"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(4),
+            run(&server, &[b"linsert", b"l", b"AFTER", b"c", b"d"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(bulks(&["a", "b", "c", "d"]), lrange(&server, b"l").await);
+        // -1 if there's no pivot, 0 if there's no list
+        assert_eq!(
+            DataType::Integer(-1),
+            run(&server, &[b"linsert", b"l", b"after", b"nope", b"e"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"linsert", b"nope", b"after", b"a", b"e"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"linsert", b"l", b"in", b"a", b"e"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lpos() {
+        let server = new_server();
+        rpush(
+            &server,
+            b"l",
+            &[b"a", b"b", b"c", b"1", b"2", b"3", b"c", b"c"],
+        )
+        .await;
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"lpos", b"l", b"c"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"lpos", b"l", b"x"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"lpos", b"nope", b"x"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lpos_rank_count_maxlen() {
+        let server = new_server();
+        rpush(
+            &server,
+            b"l",
+            &[b"a", b"b", b"c", b"1", b"2", b"3", b"c", b"c"],
+        )
+        .await;
+        assert_eq!(
+            DataType::Integer(6),
+            run(&server, &[b"lpos", b"l", b"c", b"rank", b"2"])
+                .await
+                .unwrap()
+        );
+        // a negative rank counts matches from the tail
+        assert_eq!(
+            DataType::Integer(7),
+            run(&server, &[b"lpos", b"l", b"c", b"rank", b"-1"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            ints(&[2, 6]),
+            run(&server, &[b"lpos", b"l", b"c", b"count", b"2"])
+                .await
+                .unwrap()
+        );
+        // COUNT 0 -> all the matches
+        assert_eq!(
+            ints(&[2, 6, 7]),
+            run(&server, &[b"lpos", b"l", b"c", b"count", b"0"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            ints(&[6, 2]),
+            run(
+                &server,
+                &[b"lpos", b"l", b"c", b"rank", b"-2", b"count", b"0"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            ints(&[]),
+            run(&server, &[b"lpos", b"l", b"x", b"count", b"1"])
+                .await
+                .unwrap()
+        );
+        // MAXLEN bounds how many elements are looked at, from whichever end the search starts
+        assert_eq!(
+            ints(&[2, 6]),
+            run(
+                &server,
+                &[b"lpos", b"l", b"c", b"count", b"0", b"maxlen", b"7"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(7),
+            run(
+                &server,
+                &[b"lpos", b"l", b"c", b"rank", b"-1", b"maxlen", b"1"]
+            )
+            .await
+            .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lpos_errors() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"a"]).await;
+        assert_eq!(
+            "ERR RANK can't be zero: use 1 to start from the first match, 2 from the second ... \
+             or use negative to start from the end of the list",
+            run_err(&server, &[b"lpos", b"l", b"a", b"rank", b"0"]).await
+        );
+        assert_eq!(
+            "ERR COUNT can't be negative",
+            run_err(&server, &[b"lpos", b"l", b"a", b"count", b"-1"]).await
+        );
+        assert_eq!(
+            "ERR MAXLEN can't be negative",
+            run_err(&server, &[b"lpos", b"l", b"a", b"maxlen", b"-1"]).await
+        );
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"lpos", b"l", b"a", b"rank"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lmove() {
+        let server = new_server();
+        rpush(&server, b"a", &[b"1", b"2", b"3"]).await;
+        assert_eq!(
+            DataType::bulk_str("1"),
+            run(&server, &[b"lmove", b"a", b"b", b"left", b"right"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("2"),
+            run(&server, &[b"lmove", b"a", b"b", b"LEFT", b"LEFT"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(bulks(&["2", "1"]), lrange(&server, b"b").await);
+        // the same list -> a rotation
+        assert_eq!(
+            DataType::bulk_str("1"),
+            run(&server, &[b"lmove", b"b", b"b", b"right", b"left"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(bulks(&["1", "2"]), lrange(&server, b"b").await);
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"lmove", b"nope", b"b", b"left", b"left"])
+                .await
+                .unwrap()
+        );
+        // moving the last element deletes the source
+        run(&server, &[b"lmove", b"a", b"b", b"left", b"left"])
+            .await
+            .unwrap();
+        assert!(!exists(&server, b"a").await);
+        assert_eq!(bulks(&["3", "1", "2"]), lrange(&server, b"b").await);
+    }
+
+    #[tokio::test]
+    async fn test_lmove_errors() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"1"]).await;
+        run(&server, &[b"set", b"s", b"v"]).await.unwrap();
+        // the destination is checked before anything is popped
+        assert_eq!(
+            CommandError::WrongType.to_string(),
+            run_err(&server, &[b"lmove", b"l", b"s", b"left", b"left"]).await
+        );
+        assert_eq!(bulks(&["1"]), lrange(&server, b"l").await);
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"lmove", b"l", b"s", b"up", b"left"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lmpop() {
+        let server = new_server();
+        rpush(&server, b"b", &[b"1", b"2", b"3"]).await;
+        // from the first key that holds a list
+        assert_eq!(
+            pair("b", &["3"]),
+            run(&server, &[b"lmpop", b"2", b"nope", b"b", b"right"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            pair("b", &["1", "2"]),
+            run(&server, &[b"lmpop", b"1", b"b", b"left", b"count", b"5"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::NullArray,
+            run(&server, &[b"lmpop", b"2", b"nope", b"b", b"left"])
+                .await
+                .unwrap()
+        );
+        // an expired list isn't popped from
+        rpush(&server, b"e", &[b"1"]).await;
+        server
+            .write()
+            .await
+            .store
+            .set_expire_at(Bytes::from("e"), 1);
+        assert_eq!(
+            DataType::NullArray,
+            run(&server, &[b"lmpop", b"1", b"e", b"left"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lmpop_errors() {
+        let server = new_server();
+        rpush(&server, b"l", &[b"1"]).await;
+        run(&server, &[b"set", b"s", b"v"]).await.unwrap();
+        assert_eq!(
+            "ERR numkeys should be greater than 0",
+            run_err(&server, &[b"lmpop", b"0", b"l", b"left"]).await
+        );
+        // fewer keys than numkeys says -> the direction is taken for a key
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"lmpop", b"2", b"l", b"left"]).await
+        );
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"lmpop", b"1", b"l", b"up"]).await
+        );
+        assert_eq!(
+            "ERR count should be greater than 0",
+            run_err(&server, &[b"lmpop", b"1", b"l", b"left", b"count", b"0"]).await
+        );
+        assert_eq!(
+            CommandError::WrongType.to_string(),
+            run_err(&server, &[b"lmpop", b"1", b"s", b"left"]).await
+        );
+        assert_eq!(bulks(&["1"]), lrange(&server, b"l").await);
+    }
+
+    // Runs a command that's expected to block on another task, and waits until it has
+    async fn spawn_blocked(
+        server: &Arc<RwLock<Server>>,
+        cmd: &'static [&'static [u8]],
+    ) -> tokio::task::JoinHandle<DataType> {
+        let blocked = server.read().await.store.blocked.len();
+        let task = {
+            let server = server.clone();
+            tokio::spawn(async move { run(&server, cmd).await.unwrap() })
+        };
+        while server.read().await.store.blocked.len() == blocked {
+            tokio::task::yield_now().await;
+        }
+        task
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pop() {
+        let server = new_server();
+        run(&server, &[b"rpush", b"a", b"1"]).await.unwrap();
+        // BLPOP and BRPOP reply with the key and the element, flat
+        let popped = |key: &str, val: &str| bulks(&[key, val]);
+        // nothing to wait for
+        assert_eq!(
+            popped("a", "1"),
+            run(&server, &[b"blpop", b"nope", b"a", b"0"])
+                .await
+                .unwrap()
+        );
+
+        // served first come first served, atomically with the push
+        let first = spawn_blocked(&server, &[b"blpop", b"k", b"other", b"0"]).await;
+        let second = spawn_blocked(&server, &[b"brpop", b"other", b"k", b"0"]).await;
+        let third = spawn_blocked(&server, &[b"blpop", b"k", b"0"]).await;
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"rpush", b"k", b"x", b"y"]).await.unwrap()
+        );
+        assert_eq!(popped("k", "x"), first.await.unwrap());
+        assert_eq!(popped("k", "y"), second.await.unwrap());
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"k"]).await.unwrap()
+        );
+        assert_eq!(1, server.read().await.store.blocked.len());
+        run(&server, &[b"lpush", b"k", b"z"]).await.unwrap();
+        assert_eq!(popped("k", "z"), third.await.unwrap());
+        assert!(server.read().await.store.blocked.is_empty());
+
+        assert_eq!(
+            DataType::NullArray,
+            run(&server, &[b"brpop", b"k", b"0.01"]).await.unwrap()
+        );
+        assert!(server.read().await.store.blocked.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_timeout_errors() {
+        let server = new_server();
+        assert_eq!(
+            "ERR timeout is negative",
+            run_err(&server, &[b"blpop", b"k", b"-1"]).await
+        );
+        assert_eq!(
+            "ERR timeout is not a float or out of range",
+            run_err(&server, &[b"brpop", b"k", b"soon"]).await
+        );
+        assert_eq!(
+            "ERR timeout is not a float or out of range",
+            run_err(&server, &[b"blpop", b"k", b"inf"]).await
+        );
+        assert_eq!(
+            "ERR timeout is out of range",
+            run_err(
+                &server,
+                &[b"blmove", b"k", b"d", b"left", b"left", b"1e300"]
+            )
+            .await
+        );
+        assert_eq!(
+            "ERR timeout is negative",
+            run_err(&server, &[b"blmpop", b"-0.5", b"1", b"k", b"left"]).await
+        );
+        // nothing was left blocked
+        assert!(server.read().await.store.blocked.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_blmove_blmpop() {
+        let server = new_server();
+        let moved = spawn_blocked(
+            &server,
+            &[b"blmove", b"src", b"dst", b"right", b"left", b"0"],
+        )
+        .await;
+        // popped by the first client, which blocks on the destination of the move
+        let popped = spawn_blocked(
+            &server,
+            &[
+                b"blmpop", b"0", b"2", b"nope", b"dst", b"left", b"count", b"5",
+            ],
+        )
+        .await;
+        run(&server, &[b"rpush", b"src", b"1", b"2"]).await.unwrap();
+        assert_eq!(DataType::bulk_str("2"), moved.await.unwrap());
+        assert_eq!(pair("dst", &["2"]), popped.await.unwrap());
+        assert_eq!(bulks(&["1"]), lrange(&server, b"src").await);
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"dst"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::bulk_str("1"),
+            run(
+                &server,
+                &[b"blmove", b"src", b"dst", b"left", b"left", b"0"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(
+                &server,
+                &[b"blmove", b"src", b"dst", b"left", b"left", b"0.01"]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            DataType::NullArray,
+            run(&server, &[b"blmpop", b"0.01", b"1", b"src", b"left"])
+                .await
+                .unwrap()
+        );
+
+        // the destination turned into a string while the client was blocked
+        let moved =
+            spawn_blocked(&server, &[b"blmove", b"src", b"s", b"left", b"left", b"0"]).await;
+        run(&server, &[b"set", b"s", b"v"]).await.unwrap();
+        run(&server, &[b"rpush", b"src", b"1"]).await.unwrap();
+        assert_eq!(
+            DataType::SimpleError(CommandError::WrongType.to_string().into()),
+            moved.await.unwrap()
+        );
+    }
+}
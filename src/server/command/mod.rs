@@ -0,0 +1,784 @@
+// This module is intended to include leader -> follower commands
+// The follower -> leader commands should be in server/replicate
+pub mod bitmap;
+pub mod connection;
+pub mod expire;
+pub mod hash;
+pub mod keyspace;
+pub mod list;
+pub mod replication;
+pub mod server;
+pub mod set;
+pub mod stream;
+pub mod string;
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use hashbrown::HashMap;
+use tokio::sync::{oneshot, RwLock};
+
+use crate::resp::data::DataType;
+
+use super::client::Client;
+use super::errors::CommandError;
+use super::Server;
+
+pub type R<T> = anyhow::Result<T, CommandError>;
+
+// The args a command was called with, not including its name
+pub type Args = VecDeque<Bytes>;
+
+// What a command hands back to the connection, which serializes it in the client's protocol
+#[derive(Debug, PartialEq)]
+pub enum CommandResult {
+    Reply(DataType),
+    // A full resync -> the reply is followed by the store file
+    Resync(DataType, DataType),
+    // The client waits for its reply, e.g. BLPOP on an empty list
+    Block(Blocking),
+}
+
+// A client blocked on keys (-> Store::blocked) until it's served from one of them, or it times out
+#[derive(Debug)]
+pub struct Blocking {
+    pub id: u64,
+    pub rx: oneshot::Receiver<DataType>,
+    // None -> waits for as long as it takes
+    pub timeout: Option<Duration>,
+    // The reply if nothing turns up in time
+    pub timed_out: DataType,
+}
+
+impl PartialEq for Blocking {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Blocking {
+    // Waits without holding the server's lock, so other clients can get to the keys
+    pub async fn wait(mut self, server: &Arc<RwLock<Server>>) -> DataType {
+        let served = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, &mut self.rx).await.ok(),
+            None => Some((&mut self.rx).await),
+        };
+        if let Some(Ok(reply)) = served {
+            return reply;
+        }
+        // it may have been served between timing out and getting the lock
+        let mut s = server.write().await;
+        s.store.blocked.unblock(self.id);
+        self.rx.try_recv().unwrap_or(self.timed_out)
+    }
+}
+
+// Describes how a command behaves, mostly for clients introspecting the server (-> COMMAND)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandFlags(u8);
+
+impl CommandFlags {
+    pub const NONE: Self = Self(0);
+    pub const WRITE: Self = Self(1);
+    pub const READONLY: Self = Self(1 << 1);
+    pub const ADMIN: Self = Self(1 << 2);
+    pub const BLOCKING: Self = Self(1 << 3);
+    pub const PUBSUB: Self = Self(1 << 4);
+    // where the keys are depends on the args (-> KeySpec::keynum)
+    pub const MOVABLEKEYS: Self = Self(1 << 5);
+
+    const NAMES: [(Self, &'static str); 6] = [
+        (Self::WRITE, "write"),
+        (Self::READONLY, "readonly"),
+        (Self::ADMIN, "admin"),
+        (Self::BLOCKING, "blocking"),
+        (Self::PUBSUB, "pubsub"),
+        (Self::MOVABLEKEYS, "movablekeys"),
+    ];
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl std::ops::BitOr for CommandFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+// Where the keys are in a command's args, the same way redis describes them: the position of the first key,
+// the position of the last key (negative -> counted back from the end) and the step between keys. Positions
+// count the command name as 0, so a command with no keys has a first key of 0.
+//
+// Some commands say how many keys they take instead, e.g. LMPOP numkeys key [key ...]. For those, `first` is
+// the position of the number of keys, and the keys follow it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySpec {
+    pub first: usize,
+    pub last: isize,
+    pub step: usize,
+    pub keynum: bool,
+}
+
+impl KeySpec {
+    pub const NONE: Self = Self::new(0, 0, 0);
+    // e.g. GET key
+    pub const SINGLE: Self = Self::new(1, 1, 1);
+    // e.g. DEL key [key ...]
+    pub const ALL: Self = Self::new(1, -1, 1);
+
+    pub const fn new(first: usize, last: isize, step: usize) -> Self {
+        Self {
+            first,
+            last,
+            step,
+            keynum: false,
+        }
+    }
+
+    // The number of keys is the arg at `index`, and the keys come straight after it
+    pub const fn keynum(index: usize) -> Self {
+        Self {
+            first: index,
+            last: 0,
+            step: 1,
+            keynum: true,
+        }
+    }
+
+    // The keys in `args`, which don't include the command name
+    pub fn keys<'a>(&self, args: &'a Args) -> Vec<&'a Bytes> {
+        if self.first == 0 || self.step == 0 {
+            return Vec::new();
+        }
+        if self.keynum {
+            // a bad number of keys is for the command to complain about
+            let numkeys = args
+                .get(self.first - 1)
+                .and_then(|n| parse_int::<usize>(n).ok())
+                .unwrap_or(0);
+            return args.iter().skip(self.first).take(numkeys).collect();
+        }
+        let total = args.len() as isize + 1;
+        let last = match self.last < 0 {
+            true => total + self.last,
+            false => self.last.min(total - 1),
+        };
+        (self.first as isize..=last)
+            .step_by(self.step)
+            .filter_map(|i| args.get(i as usize - 1))
+            .collect()
+    }
+}
+
+// An option a command accepts after its required args, e.g. SET's PX <milliseconds>
+#[derive(Debug)]
+pub struct OptionEntry {
+    pub name: &'static str,
+    pub args: usize,
+}
+
+impl OptionEntry {
+    pub const fn new(name: &'static str, args: usize) -> Self {
+        Self { name, args }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandOption {
+    pub name: &'static str,
+    pub val: Args,
+}
+
+impl CommandOption {
+    fn new(name: &'static str, val: Args) -> Self {
+        Self { name, val }
+    }
+
+    // The first value of an option that takes args
+    pub fn value(mut self) -> R<Bytes> {
+        self.val.pop_front().ok_or(CommandError::InvalidOption)
+    }
+}
+
+// What a command gets to work with. Commands run while the server is locked, so a command's view of the
+// store can't change part way through.
+pub struct Context<'a> {
+    pub server: &'a mut Server,
+    pub client: &'a mut Client,
+}
+
+// A command the server can run. Anything implementing this can be registered with the server
+// (-> Server::register), including commands defined outside this crate.
+pub trait CommandHandler: Send + Sync {
+    // lowercase
+    fn name(&self) -> &'static str;
+
+    // The number of args including the command name, like redis reports it. A negative arity means at least
+    // that many, e.g. -3 for SET key value [options].
+    fn arity(&self) -> i64;
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::NONE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::NONE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        &[]
+    }
+
+    // One line describing the command (-> COMMAND DOCS)
+    fn summary(&self) -> &'static str {
+        ""
+    }
+
+    // The redis command group, e.g. 'string' or 'generic' for commands that work on any type of key
+    fn group(&self) -> &'static str {
+        "generic"
+    }
+
+    // The ACL categories, going by the flags and the group, e.g. ['write', 'string'] for SET
+    fn acl_categories(&self) -> Vec<&'static str> {
+        let flags = self.flags();
+        let mut categories = Vec::new();
+        for (flag, category) in [
+            (CommandFlags::WRITE, "write"),
+            (CommandFlags::READONLY, "read"),
+            (CommandFlags::ADMIN, "admin"),
+            (CommandFlags::ADMIN, "dangerous"),
+            (CommandFlags::BLOCKING, "blocking"),
+            (CommandFlags::PUBSUB, "pubsub"),
+        ] {
+            if flags.contains(flag) {
+                categories.push(category);
+            }
+        }
+        let group = match self.group() {
+            "generic" => Some("keyspace"),
+            "sorted-set" => Some("sortedset"),
+            "server" => None,
+            group => Some(group),
+        };
+        categories.extend(group);
+        categories
+    }
+
+    // `args` has already been checked against the arity
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult>;
+
+    fn check_arity(&self, args: &Args) -> R<()> {
+        let arity = self.arity();
+        let n = args.len() as i64 + 1;
+        match (arity >= 0 && n == arity) || (arity < 0 && n >= -arity) {
+            true => Ok(()),
+            false => Err(CommandError::InvalidArgs(self.name().to_string())),
+        }
+    }
+
+    // Splits `args` into the options this command accepts. Fails on anything else.
+    fn parse_options(&self, mut args: Args) -> R<VecDeque<CommandOption>> {
+        let mut buffer = VecDeque::new();
+        while let Some(arg) = args.pop_front() {
+            let arg = to_lowercase(&arg);
+            match self.options().iter().find(|o| o.name == arg) {
+                Some(o_entry) => {
+                    if o_entry.args > args.len() {
+                        return Err(CommandError::InvalidOption);
+                    };
+                    let vals = args.drain(..o_entry.args).collect();
+                    buffer.push_back(CommandOption::new(o_entry.name, vals));
+                }
+                None => return Err(CommandError::InvalidOption),
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+// Every command the server knows, by (lowercase) name
+#[derive(Clone)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Arc<dyn CommandHandler>>,
+}
+
+impl std::fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.commands.keys()).finish()
+    }
+}
+
+impl Default for CommandRegistry {
+    // The built-in commands
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(connection::Ping);
+        registry.register(connection::Echo);
+        registry.register(connection::Hello);
+        registry.register(string::Get);
+        registry.register(string::Set);
+        registry.register(string::SetNx);
+        registry.register(string::SetEx);
+        registry.register(string::PSetEx);
+        registry.register(string::GetSet);
+        registry.register(string::GetDel);
+        registry.register(string::GetEx);
+        registry.register(string::Incr);
+        registry.register(string::Decr);
+        registry.register(string::IncrBy);
+        registry.register(string::DecrBy);
+        registry.register(string::IncrByFloat);
+        registry.register(string::Append);
+        registry.register(string::StrLen);
+        registry.register(string::GetRange);
+        registry.register(string::SetRange);
+        registry.register(string::MGet);
+        registry.register(string::MSet);
+        registry.register(string::MSetNx);
+        registry.register(string::Lcs);
+        registry.register(bitmap::SetBit);
+        registry.register(bitmap::GetBit);
+        registry.register(bitmap::BitCount);
+        registry.register(bitmap::BitPos);
+        registry.register(bitmap::BitOp);
+        registry.register(bitmap::BitField);
+        registry.register(bitmap::BitFieldRo);
+        registry.register(list::LPush);
+        registry.register(list::RPush);
+        registry.register(list::LPushX);
+        registry.register(list::RPushX);
+        registry.register(list::LPop);
+        registry.register(list::RPop);
+        registry.register(list::LLen);
+        registry.register(list::LRange);
+        registry.register(list::LIndex);
+        registry.register(list::LSet);
+        registry.register(list::LRem);
+        registry.register(list::LTrim);
+        registry.register(list::LInsert);
+        registry.register(list::LPos);
+        registry.register(list::LMove);
+        registry.register(list::LMPop);
+        registry.register(list::BLPop);
+        registry.register(list::BRPop);
+        registry.register(list::BLMove);
+        registry.register(list::BLMPop);
+        registry.register(hash::HSet);
+        registry.register(hash::HSetNx);
+        registry.register(hash::HGet);
+        registry.register(hash::HMGet);
+        registry.register(hash::HDel);
+        registry.register(hash::HExists);
+        registry.register(hash::HLen);
+        registry.register(hash::HStrLen);
+        registry.register(hash::HKeys);
+        registry.register(hash::HVals);
+        registry.register(hash::HGetAll);
+        registry.register(hash::HIncrBy);
+        registry.register(hash::HIncrByFloat);
+        registry.register(hash::HRandField);
+        registry.register(hash::HScan);
+        registry.register(hash::HExpire);
+        registry.register(hash::HPExpire);
+        registry.register(hash::HTtl);
+        registry.register(hash::HPersist);
+        registry.register(set::SAdd);
+        registry.register(set::SRem);
+        registry.register(set::SIsMember);
+        registry.register(set::SMIsMember);
+        registry.register(set::SMembers);
+        registry.register(set::SCard);
+        registry.register(set::SPop);
+        registry.register(set::SRandMember);
+        registry.register(set::SMove);
+        registry.register(set::SInter);
+        registry.register(set::SInterStore);
+        registry.register(set::SInterCard);
+        registry.register(set::SUnion);
+        registry.register(set::SUnionStore);
+        registry.register(set::SDiff);
+        registry.register(set::SDiffStore);
+        registry.register(set::SScan);
+        registry.register(keyspace::Type);
+        registry.register(keyspace::Del);
+        registry.register(keyspace::Unlink);
+        registry.register(keyspace::Exists);
+        registry.register(keyspace::Keys);
+        registry.register(keyspace::Scan);
+        registry.register(keyspace::DbSize);
+        registry.register(keyspace::RandomKey);
+        registry.register(keyspace::FlushDb);
+        registry.register(keyspace::FlushAll);
+        registry.register(expire::Expire);
+        registry.register(expire::PExpire);
+        registry.register(expire::ExpireAt);
+        registry.register(expire::PExpireAt);
+        registry.register(expire::Ttl);
+        registry.register(expire::PTtl);
+        registry.register(expire::ExpireTime);
+        registry.register(expire::PExpireTime);
+        registry.register(expire::Persist);
+        registry.register(server::Info);
+        registry.register(server::Command);
+        registry.register(replication::ReplConf);
+        registry.register(replication::PSync);
+        registry.register(stream::XAdd);
+        registry.register(stream::XRange);
+        registry
+    }
+}
+
+impl CommandRegistry {
+    pub fn empty() -> Self {
+        Self {
+            commands: HashMap::new(),
+        }
+    }
+
+    // Replaces any command already registered under the same name
+    pub fn register<C: CommandHandler + 'static>(&mut self, handler: C) {
+        self.commands
+            .insert(handler.name().to_ascii_lowercase(), Arc::new(handler));
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn CommandHandler>> {
+        self.commands.get(name).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn CommandHandler>> {
+        self.commands.values()
+    }
+}
+
+// Command names and options are matched case-insensitively. Everything else is passed through untouched.
+#[inline]
+pub fn to_lowercase(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).to_ascii_lowercase()
+}
+
+#[inline]
+pub fn to_utf8(arg: Bytes) -> R<String> {
+    String::from_utf8(arg.to_vec()).map_err(|_| CommandError::InvalidOption)
+}
+
+#[inline]
+pub fn parse_int<T: std::str::FromStr>(arg: &[u8]) -> R<T> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<T>().ok())
+        .ok_or(CommandError::NotAnInteger)
+}
+
+// Takes the next required arg. The arity is checked before a command runs, so this only fails if a
+// command's arity is out of sync with how it reads its args.
+#[inline]
+pub fn next_arg(args: &mut Args, name: &str) -> R<Bytes> {
+    args.pop_front()
+        .ok_or_else(|| CommandError::InvalidArgs(name.to_string()))
+}
+
+// A request is an array of bulk strings, or a single string (e.g. an inline command with no args)
+fn to_args(data: DataType) -> R<Args> {
+    match data {
+        DataType::SimpleString(s) | DataType::BulkString(s) => Ok(VecDeque::from([s])),
+        DataType::Array(arr) => arr
+            .into_iter()
+            .map(|data| match data {
+                DataType::SimpleString(s) | DataType::BulkString(s) => Ok(s),
+                _ => Err(CommandError::Protocol(
+                    "expected a bulk string as a command argument".to_string(),
+                )),
+            })
+            .collect(),
+        _ => Err(CommandError::Protocol(
+            "expected an array of bulk strings".to_string(),
+        )),
+    }
+}
+
+// Looks the request's command up and runs it
+pub async fn dispatch(
+    data: DataType,
+    server: &Arc<RwLock<Server>>,
+    client: &mut Client,
+) -> R<CommandResult> {
+    let mut args = to_args(data)?;
+    // Only the command name gets normalised -> keys and values stay byte for byte
+    let name = match args.pop_front() {
+        Some(name) => name,
+        None => return Err(CommandError::Protocol("empty command".to_string())),
+    };
+    let mut s = server.write().await;
+    let handler = match s.commands.get(&to_lowercase(&name)) {
+        Some(handler) => handler,
+        None => {
+            let name = String::from_utf8_lossy(&name).to_string();
+            return Err(CommandError::NotFound(name, args.into()));
+        }
+    };
+    handler.check_arity(&args)?;
+    // Expired keys are removed when they're next touched, so a command never sees one
+    for key in handler.key_spec().keys(&args) {
+        s.store.expire_if_needed(key);
+    }
+    let mut ctx = Context {
+        server: &mut s,
+        client,
+    };
+    let result = handler.execute(&mut ctx, args);
+    // Clients blocked on keys the command pushed onto are served before anything else runs
+    s.store.serve_blocked();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use tokio::sync::RwLock;
+
+    use super::{
+        dispatch, Args, CommandError, CommandFlags, CommandHandler, CommandResult, Context,
+        KeySpec, R,
+    };
+    use crate::resp::data::DataType;
+    use crate::server::client::Client;
+    use crate::server::Server;
+
+    pub fn to_data(args: &[&[u8]]) -> DataType {
+        let args = args
+            .iter()
+            .map(|arg| DataType::BulkString(Bytes::copy_from_slice(arg)))
+            .collect::<VecDeque<_>>();
+        DataType::Array(args)
+    }
+
+    // Runs a single command, returning the reply or the error it failed with
+    pub async fn run(server: &Arc<RwLock<Server>>, args: &[&[u8]]) -> R<DataType> {
+        let mut client = Client::new();
+        match dispatch(to_data(args), server, &mut client).await? {
+            CommandResult::Reply(reply) | CommandResult::Resync(reply, _) => Ok(reply),
+            CommandResult::Block(blocking) => Ok(blocking.wait(server).await),
+        }
+    }
+
+    // Runs a command that has to fail, returning the error reply it gets
+    pub async fn run_err(server: &Arc<RwLock<Server>>, args: &[&[u8]]) -> String {
+        match run(server, args).await {
+            Ok(reply) => panic!("expected {:?} to fail, got {:?}", args, reply),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    pub fn new_server() -> Arc<RwLock<Server>> {
+        Arc::new(RwLock::new(Server::master(6379)))
+    }
+
+    // Argument lists that don't make sense for most (or any) commands
+    const MALFORMED: &[&[&[u8]]] = &[
+        &[],
+        &[b""],
+        &[b"k"],
+        &[b"k", b"v"],
+        &[b"k", b"v", b"px"],
+        &[b"k", b"v", b"px", b"abc"],
+        &[b"k", b"v", b"px", b"-1"],
+        &[b"k", b"v", b"px", b"0"],
+        &[b"k", b"v", b"nx", b"px", b"10", b"px"],
+        &[b"k", b"*"],
+        &[b"k", b"*", b"f"],
+        &[b"k", b"0-0", b"f", b"v"],
+        &[b"k", b"1-1", b"f", b"v", b"g"],
+        &[
+            b"k",
+            b"18446744073709551615-18446744073709551615",
+            b"f",
+            b"v",
+        ],
+        &[b"k", b"+", b"-"],
+        &[b"k", b"*", b"*"],
+        &[b"k", b"5-0", b"1-0"],
+        &[b"k", b"abc-", b"-abc"],
+        &[b"\xff\xfe", b"\xff", b"\xfe"],
+        &[b"listening-port"],
+        &[b"listening-port", b"99999"],
+        &[b"capa"],
+        &[b"?"],
+        &[b"?", b"x"],
+        &[b"unknown-section", b"replication"],
+        &[b"4"],
+        &[b"three"],
+        &[b"3", b"auth", b"default"],
+        &[b"2", b"setname", b"has space"],
+        &[b"count", b"count", b"count"],
+    ];
+
+    #[tokio::test]
+    async fn test_malformed_args_dont_panic() {
+        let server = new_server();
+        // A stream and a string for the argument lists to collide with
+        run(&server, &[b"xadd", b"k", b"1-1", b"f", b"v"])
+            .await
+            .unwrap();
+        run(&server, &[b"set", b"s", b"v"]).await.unwrap();
+        let mut names = server
+            .read()
+            .await
+            .commands
+            .iter()
+            .map(|handler| handler.name())
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            for args in MALFORMED {
+                let mut cmd = vec![name.as_bytes()];
+                cmd.extend_from_slice(args);
+                // Any reply or error is fine here -> a panic fails the test
+                let _ = run(&server, &cmd).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_arity_checked() {
+        let server = new_server();
+        let handlers = server.read().await.commands.clone();
+        for handler in handlers.iter() {
+            let arity = handler.arity();
+            // one arg short of the minimum, and one over the exact arity
+            let mut lengths = vec![arity.unsigned_abs() as usize - 1];
+            if arity > 0 {
+                lengths.push(arity as usize + 1);
+            }
+            for len in lengths.into_iter().filter(|len| *len > 0) {
+                let mut cmd = vec![handler.name().as_bytes()];
+                cmd.extend(vec![b"x".as_slice(); len - 1]);
+                match run(&server, &cmd).await {
+                    Err(CommandError::InvalidArgs(n)) => assert_eq!(handler.name(), n),
+                    other => panic!(
+                        "{}: expected an arity error, got {:?}",
+                        handler.name(),
+                        other
+                    ),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_command() {
+        let server = new_server();
+        let err = run(&server, &[b"NOPE", b"a"]).await.unwrap_err();
+        assert_eq!(
+            "ERR unknown command 'NOPE', with args beginning with: 'a' ",
+            err.to_string()
+        );
+    }
+
+    struct Incr;
+
+    impl CommandHandler for Incr {
+        fn name(&self) -> &'static str {
+            "myincr"
+        }
+
+        fn arity(&self) -> i64 {
+            2
+        }
+
+        fn flags(&self) -> CommandFlags {
+            CommandFlags::WRITE
+        }
+
+        fn key_spec(&self) -> KeySpec {
+            KeySpec::SINGLE
+        }
+
+        fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+            let key = args.pop_front().unwrap();
+            let n = match ctx.server.store.kv_store.try_read(&key) {
+                Some(v) => super::parse_int::<i64>(&v)? + 1,
+                None => 1,
+            };
+            ctx.server
+                .store
+                .kv_store
+                .try_write(key, Bytes::from(n.to_string()))?;
+            Ok(CommandResult::Reply(DataType::Integer(n)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_command() {
+        let server = new_server();
+        server.write().await.register(Incr);
+        run(&server, &[b"MYINCR", b"n"]).await.unwrap();
+        let reply = run(&server, &[b"myincr", b"n"]).await.unwrap();
+        assert_eq!(DataType::Integer(2), reply);
+        assert!(matches!(
+            run(&server, &[b"myincr"]).await,
+            Err(CommandError::InvalidArgs(_))
+        ));
+    }
+
+    #[test]
+    fn test_key_spec() {
+        let args: Args = ["a", "b", "c", "d"].into_iter().map(Bytes::from).collect();
+        assert!(KeySpec::NONE.keys(&args).is_empty());
+        assert_eq!(vec!["a"], KeySpec::SINGLE.keys(&args));
+        assert_eq!(vec!["a", "b", "c", "d"], KeySpec::ALL.keys(&args));
+        // MSET key value [key value ...]
+        assert_eq!(vec!["a", "c"], KeySpec::new(1, -1, 2).keys(&args));
+        // the last key is past the end of the args
+        assert_eq!(vec!["b", "c", "d"], KeySpec::new(2, 9, 1).keys(&args));
+
+        // BLMPOP timeout numkeys key [key ...] ...
+        let args: Args = ["0", "2", "a", "b", "left"]
+            .into_iter()
+            .map(Bytes::from)
+            .collect();
+        assert_eq!(vec!["a", "b"], KeySpec::keynum(2).keys(&args));
+        let args: Args = ["0", "x", "a"].into_iter().map(Bytes::from).collect();
+        assert!(KeySpec::keynum(2).keys(&args).is_empty());
+        let args: Args = ["0", "5", "a"].into_iter().map(Bytes::from).collect();
+        assert_eq!(vec!["a"], KeySpec::keynum(2).keys(&args));
+    }
+
+    #[test]
+    fn test_flags() {
+        let flags = CommandFlags::WRITE | CommandFlags::BLOCKING;
+        assert!(flags.contains(CommandFlags::WRITE));
+        assert!(!flags.contains(CommandFlags::READONLY));
+        assert_eq!(vec!["write", "blocking"], flags.names());
+    }
+}
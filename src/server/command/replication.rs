@@ -0,0 +1,131 @@
+use bytes::Bytes;
+
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::store::file::empty_store_file_bytes;
+
+use super::{
+    next_arg, parse_int, Args, CommandFlags, CommandHandler, CommandResult, Context, OptionEntry, R,
+};
+
+// REPLCONF [listening-port port] [capa capability]
+pub struct ReplConf;
+
+impl CommandHandler for ReplConf {
+    fn name(&self) -> &'static str {
+        "replconf"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "An internal command for configuring the replication stream."
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::ADMIN
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("listening-port", 1),
+            OptionEntry::new("capa", 1),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, _ctx: &mut Context, args: Args) -> R<CommandResult> {
+        // Neither is used yet -> they're still validated
+        for opt in self.parse_options(args)? {
+            match opt.name {
+                "listening-port" => {
+                    parse_int::<u16>(&opt.value()?)?;
+                }
+                "capa" => {
+                    opt.value()?;
+                }
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        Ok(CommandResult::Reply(DataType::simple_str("OK")))
+    }
+}
+
+// PSYNC replicationid offset
+pub struct PSync;
+
+impl CommandHandler for PSync {
+    fn name(&self) -> &'static str {
+        "psync"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "An internal command used in replication."
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::ADMIN
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let _repl_id = next_arg(&mut args, self.name())?;
+        parse_int::<isize>(&next_arg(&mut args, self.name())?)?;
+        let replica_info = &ctx.server.replica_info;
+        // Only a master has a replication id -> replicas can't be chained yet
+        let master_replid = match replica_info.master_replid.as_ref() {
+            Some(id) => id,
+            None => {
+                return Err(CommandError::CommandFailed(
+                    "ERR Replica can't be a master of another replica".to_string(),
+                ))
+            }
+        };
+        let master_repl_offset = replica_info.master_repl_offset.to_string();
+        // Partial resyncs aren't supported, so every request gets a full one
+        let repl_command = "FULLRESYNC";
+        let command_str = [repl_command, " ", master_replid, " ", &master_repl_offset].concat();
+        let store_file = DataType::StoreFile(Bytes::from(empty_store_file_bytes()));
+        Ok(CommandResult::Resync(
+            DataType::simple_str(&command_str),
+            store_file,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::super::tests::{new_server, to_data};
+    use crate::resp::data::DataType;
+    use crate::server::client::Client;
+    use crate::server::command::{dispatch, CommandResult};
+
+    #[tokio::test]
+    async fn test_psync_resync() {
+        let server = new_server();
+        let mut client = Client::new();
+        let result = dispatch(to_data(&[b"psync", b"?", b"-1"]), &server, &mut client)
+            .await
+            .unwrap();
+        match result {
+            CommandResult::Resync(DataType::SimpleString(resync), DataType::StoreFile(_)) => {
+                assert!(resync.starts_with(b"FULLRESYNC "))
+            }
+            other => panic!("expected a full resync, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,551 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use bytes::Bytes;
+
+use crate::resp::data::{DataType, Protocol};
+use crate::server::errors::CommandError;
+use crate::server::glob::glob_match;
+
+use super::{
+    to_lowercase, Args, CommandFlags, CommandHandler, CommandRegistry, CommandResult, Context,
+    KeySpec, R,
+};
+
+// INFO [section [section ...]]
+pub struct Info;
+
+impl CommandHandler for Info {
+    fn name(&self) -> &'static str {
+        "info"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns information and statistics about the server."
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        // replication is the only section so far. Unknown sections are skipped, like redis does.
+        let replication = args.is_empty()
+            || args.iter().any(|section| {
+                matches!(
+                    to_lowercase(section).as_str(),
+                    "replication" | "all" | "default" | "everything"
+                )
+            });
+        let replica_info = &ctx.server.replica_info;
+        let resp = match (replication, ctx.client.protocol) {
+            (false, _) => DataType::bulk_str(""),
+            (true, Protocol::Resp2) => DataType::bulk_str(&replica_info.to_string()),
+            (true, Protocol::Resp3) => {
+                let fields = replica_info
+                    .fields()
+                    .into_iter()
+                    .map(|(k, v)| (DataType::bulk_str(k), DataType::bulk_str(&v)))
+                    .collect();
+                DataType::Map(fields)
+            }
+        };
+        Ok(CommandResult::Reply(resp))
+    }
+}
+
+// COMMAND [COUNT | INFO [name ...] | DOCS [name ...] | LIST [FILTERBY ...] | GETKEYS command [arg ...]]
+// Everything is generated from the registry, so commands registered at startup show up too.
+pub struct Command;
+
+impl CommandHandler for Command {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn arity(&self) -> i64 {
+        -1
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns detailed information about all commands."
+    }
+
+    fn group(&self) -> &'static str {
+        "server"
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let commands = &ctx.server.commands;
+        let subcommand = match args.pop_front() {
+            Some(subcommand) => to_lowercase(&subcommand),
+            None => {
+                let info = sorted(commands).into_iter().map(command_info).collect();
+                return Ok(CommandResult::Reply(DataType::Array(info)));
+            }
+        };
+        let reply = match subcommand.as_str() {
+            "count" if args.is_empty() => DataType::Integer(commands.len() as i64),
+            "info" if args.is_empty() => {
+                DataType::Array(sorted(commands).into_iter().map(command_info).collect())
+            }
+            "info" => DataType::Array(
+                args.iter()
+                    .map(|name| match commands.get(&to_lowercase(name)) {
+                        Some(handler) => command_info(handler),
+                        None => DataType::Null,
+                    })
+                    .collect(),
+            ),
+            "docs" => {
+                let handlers = match args.is_empty() {
+                    true => sorted(commands),
+                    // unknown commands are left out
+                    false => args
+                        .iter()
+                        .filter_map(|name| commands.get(&to_lowercase(name)))
+                        .collect(),
+                };
+                let docs = handlers
+                    .into_iter()
+                    .map(|handler| (DataType::bulk_str(handler.name()), command_docs(&handler)))
+                    .collect();
+                DataType::Map(docs)
+            }
+            "list" => {
+                let filter = list_filter(args)?;
+                let names = sorted(commands)
+                    .into_iter()
+                    .filter(|handler| filter(handler))
+                    .map(|handler| DataType::bulk_str(handler.name()))
+                    .collect();
+                DataType::Array(names)
+            }
+            "getkeys" if !args.is_empty() => get_keys(commands, args)?,
+            "count" | "getkeys" => {
+                return Err(CommandError::InvalidArgs(format!("command|{}", subcommand)))
+            }
+            "help" => DataType::Array(
+                [
+                    "COMMAND <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+                    "(no subcommand)",
+                    "    Return details about all commands.",
+                    "COUNT",
+                    "    Return the total number of commands in this server.",
+                    "LIST [FILTERBY (MODULE <module-name>|ACLCAT <category>|PATTERN <pattern>)]",
+                    "    Return a list of all commands in this server.",
+                    "INFO [<command-name> ...]",
+                    "    Return details about multiple commands.",
+                    "DOCS [<command-name> ...]",
+                    "    Return documentation details about multiple commands.",
+                    "GETKEYS <full-command>",
+                    "    Return the keys from a full command.",
+                ]
+                .into_iter()
+                .map(DataType::simple_str)
+                .collect(),
+            ),
+            _ => {
+                return Err(CommandError::CommandFailed(format!(
+                    "ERR unknown subcommand '{}'. Try COMMAND HELP.",
+                    subcommand
+                )))
+            }
+        };
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// By name, so the replies don't depend on the registry's hashing
+fn sorted(commands: &CommandRegistry) -> Vec<Arc<dyn CommandHandler>> {
+    let mut handlers = commands.iter().cloned().collect::<Vec<_>>();
+    handlers.sort_by_key(|handler| handler.name());
+    handlers
+}
+
+fn simple_set(strs: Vec<String>) -> DataType {
+    DataType::Set(strs.iter().map(|s| DataType::simple_str(s)).collect())
+}
+
+fn pairs(pairs: Vec<(&str, DataType)>) -> DataType {
+    DataType::Map(
+        pairs
+            .into_iter()
+            .map(|(k, v)| (DataType::bulk_str(k), v))
+            .collect(),
+    )
+}
+
+// The reply for a single command, in the layout redis 7 uses:
+// [name, arity, flags, first key, last key, key step, acl categories, tips, key specs, subcommands]
+fn command_info(handler: Arc<dyn CommandHandler>) -> DataType {
+    let key_spec = handler.key_spec();
+    // like redis, commands with movable keys only describe them in the key specs
+    let (first, last, step) = match key_spec.keynum {
+        true => (0, 0, 0),
+        false => (
+            key_spec.first as i64,
+            key_spec.last as i64,
+            key_spec.step as i64,
+        ),
+    };
+    let flags = handler
+        .flags()
+        .names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let categories = handler
+        .acl_categories()
+        .into_iter()
+        .map(|category| format!("@{}", category))
+        .collect();
+    DataType::Array(VecDeque::from([
+        DataType::bulk_str(handler.name()),
+        DataType::Integer(handler.arity()),
+        simple_set(flags),
+        DataType::Integer(first),
+        DataType::Integer(last),
+        DataType::Integer(step),
+        simple_set(categories),
+        DataType::Array(VecDeque::new()),
+        key_specs(&key_spec),
+        DataType::Array(VecDeque::new()),
+    ]))
+}
+
+// The redis 7 key specs, which describe the same thing as first key/last key/step in a more general way
+fn key_specs(key_spec: &KeySpec) -> DataType {
+    if key_spec.first == 0 {
+        return DataType::Array(VecDeque::new());
+    }
+    let begin_search = pairs(vec![
+        ("type", DataType::bulk_str("index")),
+        (
+            "spec",
+            pairs(vec![("index", DataType::Integer(key_spec.first as i64))]),
+        ),
+    ]);
+    if key_spec.keynum {
+        let find_keys = pairs(vec![
+            ("type", DataType::bulk_str("keynum")),
+            (
+                "spec",
+                pairs(vec![
+                    ("keynumidx", DataType::Integer(0)),
+                    ("firstkey", DataType::Integer(1)),
+                    ("keystep", DataType::Integer(key_spec.step as i64)),
+                ]),
+            ),
+        ]);
+        let spec = pairs(vec![
+            ("flags", DataType::Array(VecDeque::new())),
+            ("begin_search", begin_search),
+            ("find_keys", find_keys),
+        ]);
+        return DataType::Array(VecDeque::from([spec]));
+    }
+    // the last key is relative to the first one, unless it's counted back from the end
+    let last_key = match key_spec.last < 0 {
+        true => key_spec.last as i64,
+        false => key_spec.last as i64 - key_spec.first as i64,
+    };
+    let spec = pairs(vec![
+        ("flags", DataType::Array(VecDeque::new())),
+        ("begin_search", begin_search),
+        (
+            "find_keys",
+            pairs(vec![
+                ("type", DataType::bulk_str("range")),
+                (
+                    "spec",
+                    pairs(vec![
+                        ("lastkey", DataType::Integer(last_key)),
+                        ("keystep", DataType::Integer(key_spec.step as i64)),
+                        ("limit", DataType::Integer(0)),
+                    ]),
+                ),
+            ]),
+        ),
+    ]);
+    DataType::Array(VecDeque::from([spec]))
+}
+
+fn command_docs(handler: &Arc<dyn CommandHandler>) -> DataType {
+    let mut docs = vec![
+        ("summary", DataType::bulk_str(handler.summary())),
+        ("group", DataType::bulk_str(handler.group())),
+    ];
+    let options = handler.options();
+    if !options.is_empty() {
+        let arguments = options
+            .iter()
+            .map(|option| {
+                let tipe = match option.args {
+                    0 => "pure-token",
+                    _ => "block",
+                };
+                pairs(vec![
+                    ("name", DataType::bulk_str(option.name)),
+                    ("type", DataType::bulk_str(tipe)),
+                    ("token", DataType::bulk_str(&option.name.to_uppercase())),
+                    ("flags", simple_set(vec!["optional".to_string()])),
+                ])
+            })
+            .collect();
+        docs.push(("arguments", DataType::Array(arguments)));
+    }
+    pairs(docs)
+}
+
+type Filter = Box<dyn Fn(&Arc<dyn CommandHandler>) -> bool>;
+
+// LIST [FILTERBY MODULE name | ACLCAT category | PATTERN pattern]
+fn list_filter(mut args: Args) -> R<Filter> {
+    if args.is_empty() {
+        return Ok(Box::new(|_| true));
+    }
+    if args.len() != 3 || to_lowercase(&args[0]) != "filterby" {
+        return Err(CommandError::InvalidOption);
+    }
+    let (kind, value) = (to_lowercase(&args[1]), args.pop_back().unwrap_or_default());
+    match kind.as_str() {
+        // there's no module support -> nothing belongs to a module
+        "module" => Ok(Box::new(|_| false)),
+        "aclcat" => {
+            let category = to_lowercase(&value);
+            Ok(Box::new(move |handler| {
+                handler.acl_categories().contains(&category.as_str())
+            }))
+        }
+        "pattern" => Ok(Box::new(move |handler| {
+            glob_match(&value, handler.name().as_bytes(), true)
+        })),
+        _ => Err(CommandError::InvalidOption),
+    }
+}
+
+// GETKEYS command [arg ...]
+fn get_keys(commands: &CommandRegistry, mut args: Args) -> R<DataType> {
+    let name = args.pop_front().unwrap_or_default();
+    let handler = commands
+        .get(&to_lowercase(&name))
+        .ok_or_else(|| CommandError::CommandFailed("ERR Invalid command specified".to_string()))?;
+    if handler.check_arity(&args).is_err() {
+        return Err(CommandError::CommandFailed(
+            "ERR Invalid number of arguments specified for command".to_string(),
+        ));
+    }
+    let keys = handler
+        .key_spec()
+        .keys(&args)
+        .into_iter()
+        .map(|key| DataType::BulkString(Bytes::clone(key)))
+        .collect::<VecDeque<_>>();
+    match keys.is_empty() {
+        true => Err(CommandError::CommandFailed(
+            "ERR The command has no key arguments".to_string(),
+        )),
+        false => Ok(DataType::Array(keys)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::VecDeque;
+
+    use super::super::tests::{new_server, run, run_err};
+    use super::super::CommandFlags;
+    use crate::resp::data::DataType;
+
+    #[tokio::test]
+    async fn test_info_sections() {
+        let server = new_server();
+        let reply = run(&server, &[b"info", b"keyspace"]).await.unwrap();
+        assert_eq!(DataType::bulk_str(""), reply);
+        let reply = run(&server, &[b"info"]).await.unwrap();
+        assert!(reply.try_to_string().unwrap().contains("role:master"));
+        let reply = run(&server, &[b"info", b"REPLICATION"]).await.unwrap();
+        assert!(reply.try_to_string().unwrap().contains("role:master"));
+    }
+
+    fn names(reply: DataType) -> Vec<String> {
+        match reply {
+            DataType::Array(names) => names.iter().map(|n| n.try_to_string().unwrap()).collect(),
+            other => panic!("expected an array, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_count_and_list() {
+        let server = new_server();
+        let count = server.read().await.commands.len() as i64;
+        assert_eq!(
+            DataType::Integer(count),
+            run(&server, &[b"command", b"count"]).await.unwrap()
+        );
+        let all = names(run(&server, &[b"command", b"list"]).await.unwrap());
+        assert_eq!(count as usize, all.len());
+        let pattern = run(
+            &server,
+            &[b"command", b"list", b"filterby", b"pattern", b"x*"],
+        );
+        assert_eq!(vec!["xadd", "xrange"], names(pattern.await.unwrap()));
+        let aclcat = run(
+            &server,
+            &[b"command", b"list", b"FILTERBY", b"aclcat", b"string"],
+        );
+        let aclcat = names(aclcat.await.unwrap());
+        assert!(aclcat.contains(&"get".to_string()) && aclcat.contains(&"set".to_string()));
+        assert!(!aclcat.contains(&"xadd".to_string()));
+        let module = run(
+            &server,
+            &[b"command", b"list", b"filterby", b"module", b"x"],
+        );
+        assert!(names(module.await.unwrap()).is_empty());
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"command", b"list", b"filterby"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_info() {
+        let server = new_server();
+        let reply = run(&server, &[b"command", b"info", b"GET", b"nope"])
+            .await
+            .unwrap();
+        let mut entries = match reply {
+            DataType::Array(entries) => entries,
+            other => panic!("expected an array, got {:?}", other),
+        };
+        assert_eq!(Some(DataType::Null), entries.pop_back());
+        let get = match entries.pop_front() {
+            Some(DataType::Array(get)) => get,
+            other => panic!("expected an array, got {:?}", other),
+        };
+        assert_eq!(10, get.len());
+        assert_eq!(DataType::bulk_str("get"), get[0]);
+        assert_eq!(DataType::Integer(2), get[1]);
+        assert_eq!(
+            DataType::Set(VecDeque::from([DataType::simple_str("readonly")])),
+            get[2]
+        );
+        // first key, last key, step
+        assert_eq!(
+            [
+                DataType::Integer(1),
+                DataType::Integer(1),
+                DataType::Integer(1)
+            ],
+            [get[3].clone(), get[4].clone(), get[5].clone()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_getkeys() {
+        let server = new_server();
+        let reply = run(&server, &[b"command", b"getkeys", b"set", b"k", b"v"])
+            .await
+            .unwrap();
+        assert_eq!(vec!["k"], names(reply));
+        // the number of keys comes first
+        let reply = run(
+            &server,
+            &[b"command", b"getkeys", b"sintercard", b"2", b"a", b"b"],
+        );
+        assert_eq!(vec!["a", "b"], names(reply.await.unwrap()));
+        let reply = run(
+            &server,
+            &[b"command", b"getkeys", b"blmpop", b"0", b"1", b"a", b"left"],
+        );
+        assert_eq!(vec!["a"], names(reply.await.unwrap()));
+        let errors: &[(&[&[u8]], &str)] = &[
+            (
+                &[b"command", b"getkeys", b"nope"],
+                "ERR Invalid command specified",
+            ),
+            (
+                &[b"command", b"getkeys", b"get"],
+                "ERR Invalid number of arguments specified for command",
+            ),
+            (
+                &[b"command", b"getkeys", b"ping"],
+                "ERR The command has no key arguments",
+            ),
+            (
+                &[b"command", b"getkeys"],
+                "ERR wrong number of arguments for 'command|getkeys' command",
+            ),
+        ];
+        for (cmd, expected) in errors {
+            let actual = run(&server, cmd).await.unwrap_err().to_string();
+            assert_eq!(*expected, actual);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_command_docs() {
+        let server = new_server();
+        let reply = run(&server, &[b"command", b"docs", b"set", b"nope"])
+            .await
+            .unwrap();
+        let docs = match reply {
+            DataType::Map(docs) => docs,
+            other => panic!("expected a map, got {:?}", other),
+        };
+        assert_eq!(1, docs.len());
+        assert_eq!(DataType::bulk_str("set"), docs[0].0);
+    }
+
+    #[tokio::test]
+    async fn test_movable_keys() {
+        let server = new_server();
+        // a command's flags and key spec agree on whether its keys move
+        for handler in server.read().await.commands.iter() {
+            assert_eq!(
+                handler.key_spec().keynum,
+                handler.flags().contains(CommandFlags::MOVABLEKEYS),
+                "{}",
+                handler.name()
+            );
+        }
+        let reply = run(&server, &[b"command", b"info", b"lmpop"])
+            .await
+            .unwrap();
+        let lmpop = match reply {
+            DataType::Array(mut entries) => match entries.pop_front() {
+                Some(DataType::Array(lmpop)) => lmpop,
+                other => panic!("expected an array, got {:?}", other),
+            },
+            other => panic!("expected an array, got {:?}", other),
+        };
+        match &lmpop[2] {
+            DataType::Set(flags) => assert!(flags.contains(&DataType::simple_str("movablekeys"))),
+            other => panic!("expected a set, got {:?}", other),
+        }
+        assert_eq!(
+            [
+                DataType::Integer(0),
+                DataType::Integer(0),
+                DataType::Integer(0)
+            ],
+            [lmpop[3].clone(), lmpop[4].clone(), lmpop[5].clone()]
+        );
+        let spec = format!("{:?}", lmpop[8]);
+        assert!(
+            spec.contains("keynum") && spec.contains("keynumidx"),
+            "{}",
+            spec
+        );
+    }
+}
@@ -0,0 +1,1328 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use rand::seq::{IteratorRandom, SliceRandom};
+use rand::{thread_rng, Rng};
+
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::store::set::Set;
+use crate::server::store::{KeyType, Store};
+
+use super::keyspace::ScanArgs;
+use super::string::bulk_or_null;
+use super::{
+    next_arg, parse_int, Args, CommandFlags, CommandHandler, CommandResult, Context, KeySpec,
+    OptionEntry, R,
+};
+
+// The set at `key`, if it holds one
+fn get_set<'a>(store: &'a Store, key: &[u8]) -> R<Option<&'a Set>> {
+    store.check_type(key, KeyType::Set)?;
+    Ok(store.set_store.get(key))
+}
+
+fn get_set_mut<'a>(store: &'a mut Store, key: &[u8]) -> R<Option<&'a mut Set>> {
+    store.check_type(key, KeyType::Set)?;
+    Ok(store.set_store.get_mut(key))
+}
+
+// A set that's been emptied doesn't exist anymore
+fn remove_if_empty(store: &mut Store, key: &[u8]) {
+    if store.set_store.get(key).is_some_and(Set::is_empty) {
+        store.remove(key);
+    }
+}
+
+fn to_set_reply(members: impl Iterator<Item = Bytes>) -> DataType {
+    DataType::Set(members.map(DataType::BulkString).collect())
+}
+
+// The sets at `keys`, where a key that doesn't exist is an empty set
+fn get_sets<'a>(store: &'a Store, keys: &[Bytes]) -> R<Vec<Option<&'a Set>>> {
+    keys.iter().map(|key| get_set(store, key)).collect()
+}
+
+// The members that are in every one of the sets. Only the smallest of them is walked.
+fn intersection<'a>(mut sets: Vec<&'a Set>) -> impl Iterator<Item = Bytes> + 'a {
+    sets.sort_by_key(|set| set.len());
+    let smallest = sets.first().copied();
+    smallest
+        .into_iter()
+        .flat_map(Set::iter)
+        .filter(move |member| sets[1..].iter().all(|set| set.contains(member)))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SetOp {
+    Inter,
+    Union,
+    Diff,
+}
+
+impl SetOp {
+    // The result of the op over the sets at `keys`
+    fn apply(self, store: &Store, keys: &[Bytes]) -> R<Set> {
+        let sets = get_sets(store, keys)?;
+        let mut result = Set::new();
+        let members: Box<dyn Iterator<Item = Bytes>> = match self {
+            // any set that doesn't exist empties the intersection
+            Self::Inter => match sets.into_iter().collect::<Option<Vec<_>>>() {
+                Some(sets) => Box::new(intersection(sets)),
+                None => Box::new(std::iter::empty()),
+            },
+            Self::Union => Box::new(sets.into_iter().flatten().flat_map(Set::iter)),
+            Self::Diff => {
+                let (first, rest) = sets.split_first().unwrap();
+                let rest = rest.iter().flatten().copied().collect::<Vec<_>>();
+                Box::new(
+                    first
+                        .iter()
+                        .flat_map(|set| set.iter())
+                        .filter(move |member| !rest.iter().any(|set| set.contains(member))),
+                )
+            }
+        };
+        for member in members {
+            result.insert(member);
+        }
+        Ok(result)
+    }
+
+    // SINTER, SUNION and SDIFF -> the members of the result
+    fn execute(self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let keys = Vec::from(args);
+        let result = self.apply(&ctx.server.store, &keys)?;
+        Ok(CommandResult::Reply(to_set_reply(result.iter())))
+    }
+
+    // SINTERSTORE, SUNIONSTORE and SDIFFSTORE -> the size of the result, which replaces whatever was at the
+    // destination
+    fn store(self, ctx: &mut Context, mut args: Args, name: &str) -> R<CommandResult> {
+        let dest = next_arg(&mut args, name)?;
+        let keys = Vec::from(args);
+        let store = &mut ctx.server.store;
+        let result = self.apply(store, &keys)?;
+        let len = result.len();
+        store.remove(&dest);
+        if !result.is_empty() {
+            store.set_store.inner.insert(dest, result);
+        }
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// SADD key member [member ...]
+pub struct SAdd;
+
+impl CommandHandler for SAdd {
+    fn name(&self) -> &'static str {
+        "sadd"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Adds one or more members to a set. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        get_set(store, &key)?;
+        let set = store.set_store.get_or_create(key);
+        let added = args
+            .into_iter()
+            .filter(|member| set.insert(member.clone()))
+            .count();
+        Ok(CommandResult::Reply(DataType::Integer(added as i64)))
+    }
+}
+
+// SREM key member [member ...]
+pub struct SRem;
+
+impl CommandHandler for SRem {
+    fn name(&self) -> &'static str {
+        "srem"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Removes one or more members from a set. Deletes the set if the last member was removed."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        let removed = match get_set_mut(store, &key)? {
+            Some(set) => args.iter().filter(|member| set.remove(member)).count(),
+            None => 0,
+        };
+        remove_if_empty(store, &key);
+        Ok(CommandResult::Reply(DataType::Integer(removed as i64)))
+    }
+}
+
+// SISMEMBER key member
+pub struct SIsMember;
+
+impl CommandHandler for SIsMember {
+    fn name(&self) -> &'static str {
+        "sismember"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Determines whether a member belongs to a set."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let member = next_arg(&mut args, self.name())?;
+        let is_member = get_set(&ctx.server.store, &key)?.is_some_and(|set| set.contains(&member));
+        Ok(CommandResult::Reply(DataType::Integer(is_member as i64)))
+    }
+}
+
+// SMISMEMBER key member [member ...]
+pub struct SMIsMember;
+
+impl CommandHandler for SMIsMember {
+    fn name(&self) -> &'static str {
+        "smismember"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Determines whether multiple members belong to a set."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let set = get_set(&ctx.server.store, &key)?;
+        let replies = args
+            .iter()
+            .map(|member| DataType::Integer(set.is_some_and(|set| set.contains(member)) as i64))
+            .collect();
+        Ok(CommandResult::Reply(DataType::Array(replies)))
+    }
+}
+
+// SMEMBERS key
+pub struct SMembers;
+
+impl CommandHandler for SMembers {
+    fn name(&self) -> &'static str {
+        "smembers"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns all members of a set."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let set = get_set(&ctx.server.store, &key)?;
+        Ok(CommandResult::Reply(to_set_reply(
+            set.into_iter().flat_map(Set::iter),
+        )))
+    }
+}
+
+// SCARD key
+pub struct SCard;
+
+impl CommandHandler for SCard {
+    fn name(&self) -> &'static str {
+        "scard"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the number of members in a set."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let len = get_set(&ctx.server.store, &key)?.map_or(0, Set::len);
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// SPOP key [count]
+pub struct SPop;
+
+impl CommandHandler for SPop {
+    fn name(&self) -> &'static str {
+        "spop"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns one or more random members from a set after removing them. Deletes the set if the last member \
+         was popped."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let count = match args.pop_front() {
+            Some(count) => match parse_int::<i64>(&count) {
+                Ok(count) if count >= 0 => Some(count as usize),
+                _ => {
+                    return Err(CommandError::CommandFailed(
+                        "ERR value is out of range, must be positive".to_string(),
+                    ))
+                }
+            },
+            None => None,
+        };
+        if !args.is_empty() {
+            return Err(CommandError::InvalidArgs(self.name().to_string()));
+        }
+        let store = &mut ctx.server.store;
+        let set = get_set_mut(store, &key)?;
+        let reply = match (set, count) {
+            (set, None) => bulk_or_null(set.and_then(Set::pop_random)),
+            (None, Some(_)) => to_set_reply(std::iter::empty()),
+            // popping all of it -> there's nothing to pick
+            (Some(set), Some(count)) if count >= set.len() => {
+                let reply = to_set_reply(set.iter());
+                store.remove(&key);
+                reply
+            }
+            (Some(set), Some(count)) => to_set_reply(set.pop_random_many(count).into_iter()),
+        };
+        remove_if_empty(store, &key);
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// SRANDMEMBER key [count]
+pub struct SRandMember;
+
+impl CommandHandler for SRandMember {
+    fn name(&self) -> &'static str {
+        "srandmember"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Get one or multiple random members from a set"
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let count = match args.pop_front() {
+            // redis' limit, so the reply can't be a silly size
+            Some(count) => match parse_int::<i64>(&count)? {
+                count if count < -(i64::MAX / 2) => {
+                    return Err(CommandError::CommandFailed(
+                        "ERR value is out of range".to_string(),
+                    ))
+                }
+                count => Some(count),
+            },
+            None => None,
+        };
+        if !args.is_empty() {
+            return Err(CommandError::InvalidArgs(self.name().to_string()));
+        }
+        let set = get_set(&ctx.server.store, &key)?;
+        let mut rng = thread_rng();
+        let count = match (set, count) {
+            (set, None) => {
+                return Ok(CommandResult::Reply(bulk_or_null(
+                    set.and_then(Set::random),
+                )))
+            }
+            (None, Some(_)) => return Ok(CommandResult::Reply(DataType::Array(VecDeque::new()))),
+            (Some(_), Some(count)) => count,
+        };
+        let members = set.into_iter().flat_map(Set::iter);
+        // positive -> distinct members, negative -> the same member can come up more than once
+        let picked = match count >= 0 {
+            true => {
+                let mut picked = members.choose_multiple(&mut rng, count as usize);
+                picked.shuffle(&mut rng);
+                picked
+            }
+            false => {
+                let members = members.collect::<Vec<_>>();
+                (0..count.unsigned_abs())
+                    .map(|_| members[rng.gen_range(0..members.len())].clone())
+                    .collect()
+            }
+        };
+        let reply = picked.into_iter().map(DataType::BulkString).collect();
+        Ok(CommandResult::Reply(DataType::Array(reply)))
+    }
+}
+
+// SMOVE source destination member
+pub struct SMove;
+
+impl CommandHandler for SMove {
+    fn name(&self) -> &'static str {
+        "smove"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Moves a member from one set to another."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, 2, 1)
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let source = next_arg(&mut args, self.name())?;
+        let dest = next_arg(&mut args, self.name())?;
+        let member = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        get_set(store, &dest)?;
+        let moved = match get_set_mut(store, &source)? {
+            Some(set) if source == dest => set.contains(&member),
+            Some(set) => set.remove(&member),
+            None => false,
+        };
+        if moved && source != dest {
+            remove_if_empty(store, &source);
+            store.set_store.get_or_create(dest).insert(member);
+        }
+        Ok(CommandResult::Reply(DataType::Integer(moved as i64)))
+    }
+}
+
+// SINTER key [key ...]
+pub struct SInter;
+
+impl CommandHandler for SInter {
+    fn name(&self) -> &'static str {
+        "sinter"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the intersect of multiple sets."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        SetOp::Inter.execute(ctx, args)
+    }
+}
+
+// SINTERSTORE destination key [key ...]
+pub struct SInterStore;
+
+impl CommandHandler for SInterStore {
+    fn name(&self) -> &'static str {
+        "sinterstore"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Stores the intersect of multiple sets in a key."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        SetOp::Inter.store(ctx, args, self.name())
+    }
+}
+
+// SINTERCARD numkeys key [key ...] [LIMIT limit]
+pub struct SInterCard;
+
+impl CommandHandler for SInterCard {
+    fn name(&self) -> &'static str {
+        "sintercard"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the number of members of the intersect of multiple sets."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY | CommandFlags::MOVABLEKEYS
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::keynum(1)
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[OptionEntry::new("limit", 1)];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let numkeys = next_arg(&mut args, self.name())?;
+        let numkeys = match parse_int::<usize>(&numkeys) {
+            Ok(n) if n > 0 => n,
+            _ => {
+                return Err(CommandError::CommandFailed(
+                    "ERR numkeys should be greater than 0".to_string(),
+                ))
+            }
+        };
+        if numkeys > args.len() {
+            return Err(CommandError::CommandFailed(
+                "ERR Number of keys can't be greater than number of args".to_string(),
+            ));
+        }
+        let keys = args.drain(..numkeys).collect::<Vec<_>>();
+        // 0 -> no limit
+        let limit = match self.parse_options(args)?.pop_back() {
+            Some(option) => match parse_int::<usize>(&option.value()?) {
+                Ok(0) => usize::MAX,
+                Ok(limit) => limit,
+                Err(_) => {
+                    return Err(CommandError::CommandFailed(
+                        "ERR LIMIT can't be negative".to_string(),
+                    ))
+                }
+            },
+            None => usize::MAX,
+        };
+        let store = &ctx.server.store;
+        let count = match get_sets(store, &keys)?
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+        {
+            // stops counting at the limit
+            Some(sets) => intersection(sets).take(limit).count(),
+            None => 0,
+        };
+        Ok(CommandResult::Reply(DataType::Integer(count as i64)))
+    }
+}
+
+// SUNION key [key ...]
+pub struct SUnion;
+
+impl CommandHandler for SUnion {
+    fn name(&self) -> &'static str {
+        "sunion"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the union of multiple sets."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        SetOp::Union.execute(ctx, args)
+    }
+}
+
+// SUNIONSTORE destination key [key ...]
+pub struct SUnionStore;
+
+impl CommandHandler for SUnionStore {
+    fn name(&self) -> &'static str {
+        "sunionstore"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Stores the union of multiple sets in a key."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        SetOp::Union.store(ctx, args, self.name())
+    }
+}
+
+// SDIFF key [key ...]
+pub struct SDiff;
+
+impl CommandHandler for SDiff {
+    fn name(&self) -> &'static str {
+        "sdiff"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the difference of multiple sets."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        SetOp::Diff.execute(ctx, args)
+    }
+}
+
+// SDIFFSTORE destination key [key ...]
+pub struct SDiffStore;
+
+impl CommandHandler for SDiffStore {
+    fn name(&self) -> &'static str {
+        "sdiffstore"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Stores the difference of multiple sets in a key."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        SetOp::Diff.store(ctx, args, self.name())
+    }
+}
+
+// SSCAN key cursor [MATCH pattern] [COUNT count]
+pub struct SScan;
+
+impl CommandHandler for SScan {
+    fn name(&self) -> &'static str {
+        "sscan"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Iterates over members of a set."
+    }
+
+    fn group(&self) -> &'static str {
+        "set"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] =
+            &[OptionEntry::new("match", 1), OptionEntry::new("count", 1)];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let scan = ScanArgs::parse(self, args)?;
+        let (next, members) = match get_set(&ctx.server.store, &key)? {
+            Some(set) => set.scan(scan.cursor, scan.count),
+            None => (0, Vec::new()),
+        };
+        let members = members.into_iter().filter(|member| scan.matches(member));
+        Ok(CommandResult::Reply(ScanArgs::reply(next, members)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use tokio::sync::RwLock;
+
+    use super::super::tests::{new_server, run, run_err};
+    use crate::resp::data::DataType;
+    use crate::server::errors::CommandError;
+    use crate::server::Server;
+
+    // The members of a set reply, sorted
+    fn members(reply: DataType) -> Vec<Bytes> {
+        let mut members = match reply {
+            DataType::Set(members) | DataType::Array(members) => members
+                .into_iter()
+                .map(|m| m.try_to_bytes().unwrap())
+                .collect::<Vec<_>>(),
+            other => panic!("expected a set, got {:?}", other),
+        };
+        members.sort();
+        members
+    }
+
+    fn bytes(vals: &[&str]) -> Vec<Bytes> {
+        vals.iter()
+            .map(|v| Bytes::copy_from_slice(v.as_bytes()))
+            .collect()
+    }
+
+    fn len(reply: DataType) -> usize {
+        match reply {
+            DataType::Array(items) | DataType::Set(items) => items.len(),
+            other => panic!("expected an array, got {:?}", other),
+        }
+    }
+
+    async fn smembers(server: &Arc<RwLock<Server>>, key: &[u8]) -> Vec<Bytes> {
+        members(run(server, &[b"smembers", key]).await.unwrap())
+    }
+
+    async fn exists(server: &Arc<RwLock<Server>>, key: &[u8]) -> bool {
+        run(server, &[b"exists", key]).await.unwrap() == DataType::Integer(1)
+    }
+
+    async fn is_intset(server: &Arc<RwLock<Server>>, key: &[u8]) -> bool {
+        server
+            .read()
+            .await
+            .store
+            .set_store
+            .get(key)
+            .unwrap()
+            .is_intset()
+    }
+
+    // A set of m0..m<n>
+    fn sadd_n(key: &[u8], n: usize) -> Vec<Vec<u8>> {
+        let mut cmd: Vec<Vec<u8>> = vec![b"sadd".to_vec(), key.to_vec()];
+        cmd.extend((0..n).map(|i| format!("m{}", i).into_bytes()));
+        cmd
+    }
+
+    async fn run_owned(server: &Arc<RwLock<Server>>, cmd: &[Vec<u8>]) -> DataType {
+        let cmd = cmd.iter().map(|c| c.as_slice()).collect::<Vec<_>>();
+        run(server, &cmd).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sadd_srem() {
+        let server = new_server();
+        // duplicates, in the command or already in the set, aren't counted
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"sadd", b"s", b"1", b"2", b"a", b"1"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"sadd", b"s", b"2", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("set"),
+            run(&server, &[b"type", b"s"]).await.unwrap()
+        );
+        assert_eq!(bytes(&["1", "2", "a", "b"]), smembers(&server, b"s").await);
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"srem", b"s", b"1", b"c"]).await.unwrap()
+        );
+        // removing the last members deletes the set
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"srem", b"s", b"2", b"a", b"b"])
+                .await
+                .unwrap()
+        );
+        assert!(!exists(&server, b"s").await);
+        assert!(smembers(&server, b"s").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scard_sismember() {
+        let server = new_server();
+        run(&server, &[b"sadd", b"s", b"1", b"a"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"scard", b"s"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"scard", b"nope"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"sismember", b"s", b"a"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"sismember", b"s", b"c"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"sismember", b"nope", b"a"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Array(
+                [DataType::Integer(0), DataType::Integer(1)]
+                    .into_iter()
+                    .collect()
+            ),
+            run(&server, &[b"smismember", b"s", b"c", b"1"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_smove() {
+        let server = new_server();
+        run(&server, &[b"sadd", b"s", b"a", b"b"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"smove", b"s", b"t", b"a"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"smove", b"s", b"t", b"a"]).await.unwrap()
+        );
+        // onto itself -> nothing moves, but the member is there
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"smove", b"s", b"s", b"b"]).await.unwrap()
+        );
+        assert_eq!(bytes(&["b"]), smembers(&server, b"s").await);
+        assert_eq!(bytes(&["a"]), smembers(&server, b"t").await);
+        run(&server, &[b"smove", b"s", b"t", b"b"]).await.unwrap();
+        assert!(!exists(&server, b"s").await);
+    }
+
+    #[tokio::test]
+    async fn test_wrong_type() {
+        let server = new_server();
+        run(&server, &[b"sadd", b"t", b"a"]).await.unwrap();
+        run(&server, &[b"set", b"str", b"v"]).await.unwrap();
+        let wrong_type = CommandError::WrongType.to_string();
+        assert_eq!(wrong_type, run_err(&server, &[b"sadd", b"str", b"a"]).await);
+        assert_eq!(wrong_type, run_err(&server, &[b"smembers", b"str"]).await);
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"smove", b"t", b"str", b"a"]).await
+        );
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"smove", b"str", b"t", b"a"]).await
+        );
+        assert_eq!(wrong_type, run_err(&server, &[b"get", b"t"]).await);
+        // the failed move left the member where it was
+        assert_eq!(bytes(&["a"]), smembers(&server, b"t").await);
+    }
+
+    async fn algebra_server() -> Arc<RwLock<Server>> {
+        let server = new_server();
+        run(&server, &[b"sadd", b"a", b"1", b"2", b"3", b"x"])
+            .await
+            .unwrap();
+        run(&server, &[b"sadd", b"b", b"2", b"3", b"4"])
+            .await
+            .unwrap();
+        run(&server, &[b"sadd", b"c", b"3", b"x", b"5"])
+            .await
+            .unwrap();
+        server
+    }
+
+    #[tokio::test]
+    async fn test_sinter_sunion_sdiff() {
+        let server = algebra_server().await;
+        let cases: &[(&[&[u8]], &[&str])] = &[
+            (&[b"sinter", b"a", b"b"], &["2", "3"]),
+            (&[b"sinter", b"a", b"b", b"c"], &["3"]),
+            (&[b"sinter", b"a", b"nope"], &[]),
+            (
+                &[b"sunion", b"b", b"c", b"nope"],
+                &["2", "3", "4", "5", "x"],
+            ),
+            (&[b"sdiff", b"a", b"b"], &["1", "x"]),
+            (&[b"sdiff", b"a", b"nope", b"c"], &["1", "2"]),
+            (&[b"sdiff", b"nope", b"a"], &[]),
+        ];
+        for (cmd, expected) in cases {
+            let actual = members(run(&server, cmd).await.unwrap());
+            assert_eq!(bytes(expected), actual, "{:?}", cmd);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sintercard() {
+        let server = algebra_server().await;
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"sintercard", b"2", b"a", b"b"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"sintercard", b"2", b"a", b"b", b"limit", b"1"])
+                .await
+                .unwrap()
+        );
+        // LIMIT 0 -> no limit
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"sintercard", b"2", b"a", b"b", b"limit", b"0"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"sintercard", b"1", b"nope"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store() {
+        let server = algebra_server().await;
+        assert_eq!(
+            DataType::Integer(2),
+            run(&server, &[b"sinterstore", b"d", b"a", b"b"])
+                .await
+                .unwrap()
+        );
+        // the destination can be one of the sources
+        assert_eq!(
+            DataType::Integer(4),
+            run(&server, &[b"sunionstore", b"d", b"d", b"c"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(bytes(&["2", "3", "5", "x"]), smembers(&server, b"d").await);
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"sdiffstore", b"e", b"d", b"a"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(bytes(&["5"]), smembers(&server, b"e").await);
+        // the result is an intset if it can be
+        assert!(is_intset(&server, b"e").await);
+        assert!(!is_intset(&server, b"d").await);
+    }
+
+    #[tokio::test]
+    async fn test_store_replaces_dest() {
+        let server = algebra_server().await;
+        run(&server, &[b"set", b"str", b"v"]).await.unwrap();
+        run(&server, &[b"expire", b"str", b"100"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"sunionstore", b"str", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("set"),
+            run(&server, &[b"type", b"str"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(-1),
+            run(&server, &[b"ttl", b"str"]).await.unwrap()
+        );
+        // and an empty result deletes it
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"sinterstore", b"str", b"a", b"nope"])
+                .await
+                .unwrap()
+        );
+        assert!(!exists(&server, b"str").await);
+    }
+
+    #[tokio::test]
+    async fn test_algebra_errors() {
+        let server = algebra_server().await;
+        run(&server, &[b"set", b"str", b"v"]).await.unwrap();
+        let wrong_type = CommandError::WrongType.to_string();
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"sinter", b"a", b"str"]).await
+        );
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"sunionstore", b"d", b"a", b"str"]).await
+        );
+        assert!(!exists(&server, b"d").await);
+        assert_eq!(
+            "ERR numkeys should be greater than 0",
+            run_err(&server, &[b"sintercard", b"0", b"a"]).await
+        );
+        assert_eq!(
+            "ERR Number of keys can't be greater than number of args",
+            run_err(&server, &[b"sintercard", b"3", b"a", b"b"]).await
+        );
+        assert_eq!(
+            "ERR LIMIT can't be negative",
+            run_err(&server, &[b"sintercard", b"1", b"a", b"limit", b"-1"]).await
+        );
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"sintercard", b"1", b"a", b"b"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_srandmember() {
+        let server = new_server();
+        run_owned(&server, &sadd_n(b"s", 100)).await;
+        assert!(matches!(
+            run(&server, &[b"srandmember", b"s"]).await.unwrap(),
+            DataType::BulkString(_)
+        ));
+        assert_eq!(
+            5,
+            len(run(&server, &[b"srandmember", b"s", b"5"]).await.unwrap())
+        );
+        // a positive count gets distinct members, so no more than there are
+        assert_eq!(
+            100,
+            len(run(&server, &[b"srandmember", b"s", b"500"]).await.unwrap())
+        );
+        // a negative one can repeat them
+        assert_eq!(
+            500,
+            len(run(&server, &[b"srandmember", b"s", b"-500"])
+                .await
+                .unwrap())
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"srandmember", b"nope"]).await.unwrap()
+        );
+        // it doesn't remove anything
+        assert_eq!(
+            DataType::Integer(100),
+            run(&server, &[b"scard", b"s"]).await.unwrap()
+        );
+        assert_eq!(
+            "ERR value is out of range",
+            run_err(&server, &[b"srandmember", b"s", b"-9223372036854775807"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spop() {
+        let server = new_server();
+        run_owned(&server, &sadd_n(b"s", 100)).await;
+        let popped = members(run(&server, &[b"spop", b"s", b"10"]).await.unwrap());
+        assert_eq!(10, popped.len());
+        assert_eq!(
+            DataType::Integer(90),
+            run(&server, &[b"scard", b"s"]).await.unwrap()
+        );
+        // the popped members are gone
+        assert!(smembers(&server, b"s")
+            .await
+            .iter()
+            .all(|m| !popped.contains(m)));
+        assert!(matches!(
+            run(&server, &[b"spop", b"s"]).await.unwrap(),
+            DataType::BulkString(_)
+        ));
+        assert_eq!(
+            89,
+            len(run(&server, &[b"spop", b"s", b"100"]).await.unwrap())
+        );
+        assert!(!exists(&server, b"s").await);
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"spop", b"s"]).await.unwrap()
+        );
+        assert_eq!(
+            "ERR value is out of range, must be positive",
+            run_err(&server, &[b"spop", b"s", b"-1"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_spop_most_of_the_set() {
+        let server = new_server();
+        // both kinds of set, popping more than 4/5 of them
+        run_owned(&server, &sadd_n(b"s", 100)).await;
+        let mut ints: Vec<Vec<u8>> = vec![b"sadd".to_vec(), b"ints".to_vec()];
+        ints.extend((0..100).map(|i| i.to_string().into_bytes()));
+        run_owned(&server, &ints).await;
+        for key in [b"s" as &[u8], b"ints"] {
+            let popped = members(run(&server, &[b"spop", key, b"95"]).await.unwrap());
+            let left = smembers(&server, key).await;
+            assert_eq!(95, popped.len());
+            assert_eq!(5, left.len());
+            assert!(left.iter().all(|m| !popped.contains(m)));
+        }
+        assert!(is_intset(&server, b"ints").await);
+    }
+
+    #[tokio::test]
+    async fn test_sscan() {
+        let server = new_server();
+        run_owned(&server, &sadd_n(b"s", 100)).await;
+        let (mut cursor, mut seen) = (b"0".to_vec(), Vec::new());
+        loop {
+            let reply = run(&server, &[b"sscan", b"s", &cursor, b"match", b"m1*"])
+                .await
+                .unwrap();
+            let (next, items) = match reply {
+                DataType::Array(mut reply) => {
+                    (reply.pop_front().unwrap(), reply.pop_front().unwrap())
+                }
+                other => panic!("expected an array, got {:?}", other),
+            };
+            seen.extend(members(items));
+            cursor = next.try_to_bytes().unwrap().to_vec();
+            if cursor == b"0" {
+                break;
+            }
+        }
+        seen.sort();
+        let mut expected = (10..20).map(|i| format!("m{}", i)).collect::<Vec<_>>();
+        expected.push("m1".to_string());
+        expected.sort();
+        assert_eq!(
+            bytes(&expected.iter().map(String::as_str).collect::<Vec<_>>()),
+            seen
+        );
+        // NOVALUES is only for HSCAN
+        assert_eq!(
+            CommandError::InvalidOption.to_string(),
+            run_err(&server, &[b"sscan", b"s", b"0", b"novalues"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sscan_intset() {
+        let server = new_server();
+        // an intset comes back whole, however small the count
+        run(&server, &[b"sadd", b"ints", b"1", b"2", b"3"])
+            .await
+            .unwrap();
+        match run(&server, &[b"sscan", b"ints", b"0", b"count", b"1"])
+            .await
+            .unwrap()
+        {
+            DataType::Array(reply) => {
+                assert_eq!(DataType::bulk_str("0"), reply[0]);
+                assert_eq!(bytes(&["1", "2", "3"]), members(reply[1].clone()));
+            }
+            other => panic!("expected an array, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::store::KeyType;
+use crate::stream::errors::StreamError;
+use crate::stream::parse::StreamIDParser;
+use crate::stream::serialize::StreamSerializer;
+
+use super::{
+    next_arg, parse_int, to_utf8, Args, CommandFlags, CommandHandler, CommandResult, Context,
+    KeySpec, OptionEntry, R,
+};
+
+// XADD key id field value [field value ...]
+pub struct XAdd;
+
+impl CommandHandler for XAdd {
+    fn name(&self) -> &'static str {
+        "xadd"
+    }
+
+    fn arity(&self) -> i64 {
+        -5
+    }
+
+    fn summary(&self) -> &'static str {
+        "Appends a new message to a stream. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "stream"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let next = to_utf8(next_arg(&mut args, self.name())?)?;
+        let stream_id = StreamIDParser::split_initial(next)?;
+        // stream values need to be in a 'key: value' format
+        if args.is_empty() || args.len() & 1 != 0 {
+            return Err(CommandError::InvalidArgs(self.name().to_string()));
+        }
+        let mut values = Vec::with_capacity(args.len() / 2);
+        while let (Some(k), Some(v)) = (args.pop_front(), args.pop_front()) {
+            values.push((k, v))
+        }
+        let store = &mut ctx.server.store;
+        store.check_type(&key, KeyType::Stream)?;
+        let resp = match store.stream_store.try_write(key, values, stream_id) {
+            Ok((id, seq)) => DataType::bulk_str(&format!("{}-{}", id, seq)),
+            Err(StreamError::InvalidStreamID) => return Err(CommandError::CommandFailed(
+                "ERR The ID specified in XADD is equal or smaller than the target stream top item"
+                    .to_string(),
+            )),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(CommandResult::Reply(resp))
+    }
+}
+
+// XRANGE key start end [COUNT count]
+pub struct XRange;
+
+impl CommandHandler for XRange {
+    fn name(&self) -> &'static str {
+        "xrange"
+    }
+
+    fn arity(&self) -> i64 {
+        -4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the messages from a stream within a range of IDs."
+    }
+
+    fn group(&self) -> &'static str {
+        "stream"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[OptionEntry::new("count", 1)];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let start = StreamIDParser::split_initial(to_utf8(next_arg(&mut args, self.name())?)?)?;
+        let end = StreamIDParser::split_initial(to_utf8(next_arg(&mut args, self.name())?)?)?;
+        let (start_id, start_seq) = start;
+        let (end_id, end_seq) = end;
+        let start = StreamIDParser::to_stream_id(start_id, start_seq)?;
+        let end = StreamIDParser::to_stream_id(end_id, end_seq)?;
+        let mut count = usize::MAX;
+        for opt in self.parse_options(args)? {
+            match opt.name {
+                // a negative count is the same as 0
+                "count" => count = parse_int::<i64>(&opt.value()?)?.max(0) as usize,
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        let store = &ctx.server.store;
+        store.check_type(&key, KeyType::Stream)?;
+        let resp = match store.stream_store.try_read(&key) {
+            Some(v) => {
+                // BTreeMap::range panics on a reversed range
+                let range = match start <= end {
+                    true => v.range(start..=end).take(count).collect(),
+                    false => Vec::new(),
+                };
+                StreamSerializer::to_data(&range)
+            }
+            None => DataType::NullBulkString,
+        };
+        Ok(CommandResult::Reply(resp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::VecDeque;
+
+    use super::super::tests::{new_server, run};
+    use crate::resp::data::DataType;
+
+    #[tokio::test]
+    async fn test_xadd_xrange_malformed() {
+        let server = new_server();
+        let cases: &[(&[&[u8]], &str)] = &[
+            (
+                &[b"xadd", b"k", b"1-1", b"f"],
+                "ERR wrong number of arguments for 'xadd' command",
+            ),
+            (
+                &[b"xadd", b"k", b"1-1", b"f", b"v", b"g"],
+                "ERR wrong number of arguments for 'xadd' command",
+            ),
+            (
+                &[b"xrange", b"k", b"*", b"+"],
+                "ERR Invalid stream ID specified as stream command argument",
+            ),
+            (&[b"xrange", b"k", b"-", b"+", b"count"], "ERR syntax error"),
+        ];
+        for (cmd, expected) in cases {
+            let actual = run(&server, cmd).await.unwrap_err().to_string();
+            assert_eq!(*expected, actual);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_xrange() {
+        let server = new_server();
+        for id in [b"1-1", b"1-2", b"2-1"] {
+            run(&server, &[b"xadd", b"k", id, b"f", b"v"])
+                .await
+                .unwrap();
+        }
+        let len = |reply: DataType| match reply {
+            DataType::Array(entries) => entries.len(),
+            other => panic!("expected an array, got {:?}", other),
+        };
+        let all = run(&server, &[b"xrange", b"k", b"-", b"+"]).await.unwrap();
+        assert_eq!(3, len(all));
+        let count = run(&server, &[b"xrange", b"k", b"-", b"+", b"COUNT", b"2"])
+            .await
+            .unwrap();
+        assert_eq!(2, len(count));
+        // BTreeMap::range would panic on this
+        let reversed = run(&server, &[b"xrange", b"k", b"5-0", b"1-0"])
+            .await
+            .unwrap();
+        assert_eq!(DataType::Array(VecDeque::new()), reversed);
+    }
+}
@@ -0,0 +1,1856 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::resp::data::DataType;
+use crate::server::errors::CommandError;
+use crate::server::store::{now_ms, KeyType, Store};
+
+use super::expire::{to_expire_at, Unit};
+use super::{
+    next_arg, parse_int, Args, CommandFlags, CommandHandler, CommandResult, Context, KeySpec,
+    OptionEntry, R,
+};
+
+// GET key
+pub struct Get;
+
+impl CommandHandler for Get {
+    fn name(&self) -> &'static str {
+        "get"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the string value of a key."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let val = get_string(&ctx.server.store, &key)?;
+        Ok(CommandResult::Reply(bulk_or_null(val)))
+    }
+}
+
+// What a write does to the key's expiry
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Expiry {
+    Clear,
+    Keep,
+    At(i64),
+}
+
+// EX, PX, EXAT and PXAT -> when the key expires. Unlike EXPIRE, the time has to be positive.
+fn parse_expiry(name: &str, option: &str, val: &[u8]) -> R<Expiry> {
+    let (unit, absolute) = match option {
+        "ex" => (Unit::Seconds, false),
+        "px" => (Unit::Millis, false),
+        "exat" => (Unit::Seconds, true),
+        "pxat" => (Unit::Millis, true),
+        _ => return Err(CommandError::InvalidOption),
+    };
+    let time = parse_int::<i64>(val)?;
+    if time <= 0 {
+        return Err(CommandError::CommandFailed(format!(
+            "ERR invalid expire time in '{}' command",
+            name
+        )));
+    }
+    to_expire_at(name, time, unit, absolute).map(Expiry::At)
+}
+
+// The key's value, if it holds a string
+pub(super) fn get_string(store: &Store, key: &[u8]) -> R<Option<Bytes>> {
+    store.check_type(key, KeyType::String)?;
+    Ok(store.kv_store.try_read(key))
+}
+
+// Sets the key to a string, whatever type it held before
+fn set_string(store: &mut Store, key: Bytes, val: Bytes, expiry: Expiry) -> R<()> {
+    let expire_at = match expiry {
+        Expiry::Clear => None,
+        Expiry::Keep => store.expire_at(&key),
+        Expiry::At(at) => Some(at),
+    };
+    store.remove(&key);
+    store.kv_store.try_write(key.clone(), val)?;
+    if let Some(at) = expire_at {
+        store.set_expire_at(key, at);
+    }
+    Ok(())
+}
+
+pub(super) fn bulk_or_null(val: Option<Bytes>) -> DataType {
+    match val {
+        Some(val) => DataType::BulkString(val),
+        None => DataType::NullBulkString,
+    }
+}
+
+// SET key value [NX | XX] [GET] [EX seconds | PX milliseconds | EXAT unix-time-seconds |
+//   PXAT unix-time-milliseconds | KEEPTTL]
+pub struct Set;
+
+impl CommandHandler for Set {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("nx", 0),
+            OptionEntry::new("xx", 0),
+            OptionEntry::new("get", 0),
+            OptionEntry::new("ex", 1),
+            OptionEntry::new("px", 1),
+            OptionEntry::new("exat", 1),
+            OptionEntry::new("pxat", 1),
+            OptionEntry::new("keepttl", 0),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let val = next_arg(&mut args, self.name())?;
+        let (mut nx, mut xx, mut get) = (false, false, false);
+        let mut expiry = None;
+        for opt in self.parse_options(args)? {
+            // NX and XX, or more than one of the expiry options, is a syntax error
+            match opt.name {
+                "nx" if !xx => nx = true,
+                "xx" if !nx => xx = true,
+                "get" => get = true,
+                "keepttl" if expiry.is_none() => expiry = Some(Expiry::Keep),
+                "ex" | "px" | "exat" | "pxat" if expiry.is_none() => {
+                    let name = opt.name;
+                    expiry = Some(parse_expiry(self.name(), name, &opt.value()?)?);
+                }
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        let store = &mut ctx.server.store;
+        // with GET, the key has to hold a string (or nothing), whether or not it's then set
+        let old = match get {
+            true => get_string(store, &key)?,
+            false => None,
+        };
+        let exists = store.contains_key(&key);
+        let skipped = (nx && exists) || (xx && !exists);
+        if !skipped {
+            set_string(store, key, val, expiry.unwrap_or(Expiry::Clear))?;
+        }
+        let reply = match (get, skipped) {
+            (true, _) => bulk_or_null(old),
+            (false, true) => DataType::NullBulkString,
+            (false, false) => DataType::simple_str("OK"),
+        };
+        Ok(CommandResult::Reply(reply))
+    }
+}
+
+// SETNX key value
+pub struct SetNx;
+
+impl CommandHandler for SetNx {
+    fn name(&self) -> &'static str {
+        "setnx"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Set the string value of a key only when the key doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let val = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        if store.contains_key(&key) {
+            return Ok(CommandResult::Reply(DataType::Integer(0)));
+        }
+        set_string(store, key, val, Expiry::Clear)?;
+        Ok(CommandResult::Reply(DataType::Integer(1)))
+    }
+}
+
+// SETEX and PSETEX
+fn set_with_expiry(
+    ctx: &mut Context,
+    handler: &dyn CommandHandler,
+    mut args: Args,
+    option: &str,
+) -> R<CommandResult> {
+    let key = next_arg(&mut args, handler.name())?;
+    let time = next_arg(&mut args, handler.name())?;
+    let val = next_arg(&mut args, handler.name())?;
+    let expiry = parse_expiry(handler.name(), option, &time)?;
+    set_string(&mut ctx.server.store, key, val, expiry)?;
+    Ok(CommandResult::Reply(DataType::simple_str("OK")))
+}
+
+// SETEX key seconds value
+pub struct SetEx;
+
+impl CommandHandler for SetEx {
+    fn name(&self) -> &'static str {
+        "setex"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets the string value and expiration time of a key. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        set_with_expiry(ctx, self, args, "ex")
+    }
+}
+
+// PSETEX key milliseconds value
+pub struct PSetEx;
+
+impl CommandHandler for PSetEx {
+    fn name(&self) -> &'static str {
+        "psetex"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Sets both string value and expiration time in milliseconds of a key. The key is created if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        set_with_expiry(ctx, self, args, "px")
+    }
+}
+
+// GETSET key value
+pub struct GetSet;
+
+impl CommandHandler for GetSet {
+    fn name(&self) -> &'static str {
+        "getset"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the previous string value of a key after setting it to a new value."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let val = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        let old = get_string(store, &key)?;
+        set_string(store, key, val, Expiry::Clear)?;
+        Ok(CommandResult::Reply(bulk_or_null(old)))
+    }
+}
+
+// GETDEL key
+pub struct GetDel;
+
+impl CommandHandler for GetDel {
+    fn name(&self) -> &'static str {
+        "getdel"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the string value of a key after deleting the key."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        let val = get_string(store, &key)?;
+        if val.is_some() {
+            store.remove(&key);
+        }
+        Ok(CommandResult::Reply(bulk_or_null(val)))
+    }
+}
+
+// GETEX key [EX seconds | PX milliseconds | EXAT unix-time-seconds | PXAT unix-time-milliseconds | PERSIST]
+pub struct GetEx;
+
+impl CommandHandler for GetEx {
+    fn name(&self) -> &'static str {
+        "getex"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the string value of a key after setting its expiration time."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("ex", 1),
+            OptionEntry::new("px", 1),
+            OptionEntry::new("exat", 1),
+            OptionEntry::new("pxat", 1),
+            OptionEntry::new("persist", 0),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let mut expiry = None;
+        for opt in self.parse_options(args)? {
+            match opt.name {
+                "persist" if expiry.is_none() => expiry = Some(Expiry::Clear),
+                "ex" | "px" | "exat" | "pxat" if expiry.is_none() => {
+                    let name = opt.name;
+                    expiry = Some(parse_expiry(self.name(), name, &opt.value()?)?);
+                }
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        let store = &mut ctx.server.store;
+        let val = get_string(store, &key)?;
+        if val.is_some() {
+            match expiry {
+                Some(Expiry::Clear) => {
+                    store.persist(&key);
+                }
+                // a time that's already passed deletes the key, like EXPIREAT
+                Some(Expiry::At(at)) if at <= now_ms() => {
+                    store.remove(&key);
+                }
+                Some(Expiry::At(at)) => {
+                    store.set_expire_at(key, at);
+                }
+                Some(Expiry::Keep) | None => {}
+            }
+        }
+        Ok(CommandResult::Reply(bulk_or_null(val)))
+    }
+}
+
+// INCR and co. -> the value the key ends up with. A missing key counts as 0.
+fn incr_by(store: &mut Store, key: Bytes, by: i64) -> R<i64> {
+    store.check_type(&key, KeyType::String)?;
+    let current = match store.kv_store.get(&key) {
+        Some(val) => val.to_int().ok_or(CommandError::NotAnInteger)?,
+        None => 0,
+    };
+    let n = current.checked_add(by).ok_or_else(|| {
+        CommandError::CommandFailed("ERR increment or decrement would overflow".to_string())
+    })?;
+    // the value's replaced in place, so the key keeps its expiry
+    store.kv_store.write_int(key, n);
+    Ok(n)
+}
+
+pub(super) fn parse_float(val: &[u8]) -> R<f64> {
+    std::str::from_utf8(val)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| f.is_finite())
+        .ok_or_else(|| CommandError::CommandFailed("ERR value is not a valid float".to_string()))
+}
+
+// Formats a float the way redis replies to INCRBYFLOAT: no exponent, and no trailing zeros, e.g. 10.5 + 0.5
+// is '11'. Redis works in long double and prints 17 decimal places. An f64 only holds about 15 significant
+// digits, so it's rounded to those (and no more than 17 decimal places), which keeps 0.1 + 0.2 at '0.3'
+// rather than '0.30000000000000004'.
+pub(super) fn format_float(n: f64) -> String {
+    const SIGNIFICANT: i64 = 15;
+    const MAX_DECIMALS: i64 = 17;
+    // the power of ten of the first significant digit
+    let exp = format!("{:e}", n)
+        .split_once('e')
+        .and_then(|(_, exp)| exp.parse::<i64>().ok())
+        .unwrap_or(0);
+    let decimals = (SIGNIFICANT - 1 - exp).clamp(0, MAX_DECIMALS) as usize;
+    let mut s = format!("{:.*}", decimals, n);
+    if s.contains('.') {
+        s.truncate(s.trim_end_matches('0').trim_end_matches('.').len());
+    }
+    // something too small to show rounds to 0, not -0
+    match s.as_str() {
+        "-0" => "0".to_string(),
+        _ => s,
+    }
+}
+
+// INCR key
+pub struct Incr;
+
+impl CommandHandler for Incr {
+    fn name(&self) -> &'static str {
+        "incr"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Increments the integer value of a key by one. Uses 0 as initial value if the key doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let n = incr_by(&mut ctx.server.store, key, 1)?;
+        Ok(CommandResult::Reply(DataType::Integer(n)))
+    }
+}
+
+// DECR key
+pub struct Decr;
+
+impl CommandHandler for Decr {
+    fn name(&self) -> &'static str {
+        "decr"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Decrements the integer value of a key by one. Uses 0 as initial value if the key doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let n = incr_by(&mut ctx.server.store, key, -1)?;
+        Ok(CommandResult::Reply(DataType::Integer(n)))
+    }
+}
+
+// INCRBY key increment
+pub struct IncrBy;
+
+impl CommandHandler for IncrBy {
+    fn name(&self) -> &'static str {
+        "incrby"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Increments the integer value of a key by a number. Uses 0 as initial value if the key doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let by = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let n = incr_by(&mut ctx.server.store, key, by)?;
+        Ok(CommandResult::Reply(DataType::Integer(n)))
+    }
+}
+
+// DECRBY key decrement
+pub struct DecrBy;
+
+impl CommandHandler for DecrBy {
+    fn name(&self) -> &'static str {
+        "decrby"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Decrements a number from the integer value of a key. Uses 0 as initial value if the key doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let by = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        // -i64::MIN doesn't fit
+        let by = by.checked_neg().ok_or_else(|| {
+            CommandError::CommandFailed("ERR decrement would overflow".to_string())
+        })?;
+        let n = incr_by(&mut ctx.server.store, key, by)?;
+        Ok(CommandResult::Reply(DataType::Integer(n)))
+    }
+}
+
+// INCRBYFLOAT key increment
+pub struct IncrByFloat;
+
+impl CommandHandler for IncrByFloat {
+    fn name(&self) -> &'static str {
+        "incrbyfloat"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Increment the floating point value of a key by a number. Uses 0 as initial value if the key doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let by = parse_float(&next_arg(&mut args, self.name())?)?;
+        let store = &mut ctx.server.store;
+        let current = match get_string(store, &key)? {
+            Some(val) => parse_float(&val)?,
+            None => 0.0,
+        };
+        let n = current + by;
+        if !n.is_finite() {
+            return Err(CommandError::CommandFailed(
+                "ERR increment would produce NaN or Infinity".to_string(),
+            ));
+        }
+        let val = Bytes::from(format_float(n));
+        store.kv_store.try_write(key, val.clone())?;
+        Ok(CommandResult::Reply(DataType::BulkString(val)))
+    }
+}
+
+// Like redis' proto-max-bulk-len -> the longest a string can get through APPEND or SETRANGE
+const MAX_STRING_LEN: usize = 512 * 1024 * 1024;
+
+pub(super) fn check_len(len: usize) -> R<()> {
+    match len > MAX_STRING_LEN {
+        true => Err(CommandError::CommandFailed(
+            "ERR string exceeds maximum allowed size (proto-max-bulk-len)".to_string(),
+        )),
+        false => Ok(()),
+    }
+}
+
+// APPEND key value
+pub struct Append;
+
+impl CommandHandler for Append {
+    fn name(&self) -> &'static str {
+        "append"
+    }
+
+    fn arity(&self) -> i64 {
+        3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Appends a string to the value of a key. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let val = next_arg(&mut args, self.name())?;
+        let store = &mut ctx.server.store;
+        let mut appended = get_string(store, &key)?.map_or_else(Vec::new, |v| v.to_vec());
+        check_len(appended.len() + val.len())?;
+        appended.extend_from_slice(&val);
+        let len = appended.len();
+        // written in place, so the key keeps its expiry
+        store.kv_store.try_write(key, Bytes::from(appended))?;
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// STRLEN key
+pub struct StrLen;
+
+impl CommandHandler for StrLen {
+    fn name(&self) -> &'static str {
+        "strlen"
+    }
+
+    fn arity(&self) -> i64 {
+        2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns the length of a string value."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let len = get_string(&ctx.server.store, &key)?.map_or(0, |v| v.len());
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// GETRANGE key start end -> both ends are inclusive, and negative offsets count back from the end
+pub struct GetRange;
+
+impl CommandHandler for GetRange {
+    fn name(&self) -> &'static str {
+        "getrange"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Returns a substring of the string stored at a key."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let start = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let end = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let val = get_string(&ctx.server.store, &key)?.unwrap_or_default();
+        let len = val.len() as i64;
+        let range = match (start < 0 && end < 0 && start > end, len) {
+            (true, _) | (_, 0) => None,
+            _ => {
+                let start = if start < 0 { len + start } else { start }.max(0);
+                let end = if end < 0 { len + end } else { end }.clamp(0, len - 1);
+                (start <= end).then_some(start as usize..end as usize + 1)
+            }
+        };
+        let reply = match range {
+            Some(range) => val.slice(range),
+            None => Bytes::new(),
+        };
+        Ok(CommandResult::Reply(DataType::BulkString(reply)))
+    }
+}
+
+// SETRANGE key offset value -> the string is padded with zero bytes if it's shorter than the offset
+pub struct SetRange;
+
+impl CommandHandler for SetRange {
+    fn name(&self) -> &'static str {
+        "setrange"
+    }
+
+    fn arity(&self) -> i64 {
+        4
+    }
+
+    fn summary(&self) -> &'static str {
+        "Overwrites a part of a string value with another by an offset. Creates the key if it doesn't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::SINGLE
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key = next_arg(&mut args, self.name())?;
+        let offset = parse_int::<i64>(&next_arg(&mut args, self.name())?)?;
+        let val = next_arg(&mut args, self.name())?;
+        if offset < 0 {
+            return Err(CommandError::CommandFailed(
+                "ERR offset is out of range".to_string(),
+            ));
+        }
+        let offset = offset as usize;
+        let store = &mut ctx.server.store;
+        let current = get_string(store, &key)?;
+        // nothing to write -> the key isn't created, or changed
+        if val.is_empty() {
+            let len = current.map_or(0, |v| v.len());
+            return Ok(CommandResult::Reply(DataType::Integer(len as i64)));
+        }
+        check_len(offset.saturating_add(val.len()))?;
+        let mut updated = current.map_or_else(Vec::new, |v| v.to_vec());
+        if updated.len() < offset + val.len() {
+            updated.resize(offset + val.len(), 0);
+        }
+        updated[offset..offset + val.len()].copy_from_slice(&val);
+        let len = updated.len();
+        store.kv_store.try_write(key, Bytes::from(updated))?;
+        Ok(CommandResult::Reply(DataType::Integer(len as i64)))
+    }
+}
+
+// MGET key [key ...] -> a key that doesn't hold a string is nil, rather than an error
+pub struct MGet;
+
+impl CommandHandler for MGet {
+    fn name(&self) -> &'static str {
+        "mget"
+    }
+
+    fn arity(&self) -> i64 {
+        -2
+    }
+
+    fn summary(&self) -> &'static str {
+        "Atomically returns the string values of one or more keys."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::ALL
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let store = &ctx.server.store;
+        let vals = args
+            .iter()
+            .map(|key| bulk_or_null(get_string(store, key).unwrap_or(None)))
+            .collect();
+        Ok(CommandResult::Reply(DataType::Array(vals)))
+    }
+}
+
+// The (key, value) pairs MSET and MSETNX take
+fn pairs(handler: &dyn CommandHandler, mut args: Args) -> R<Vec<(Bytes, Bytes)>> {
+    if args.len() & 1 != 0 {
+        return Err(CommandError::InvalidArgs(handler.name().to_string()));
+    }
+    let mut pairs = Vec::with_capacity(args.len() / 2);
+    while let (Some(key), Some(val)) = (args.pop_front(), args.pop_front()) {
+        pairs.push((key, val));
+    }
+    Ok(pairs)
+}
+
+// MSET key value [key value ...]
+pub struct MSet;
+
+impl CommandHandler for MSet {
+    fn name(&self) -> &'static str {
+        "mset"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Atomically creates or modifies the string values of one or more keys."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, -1, 2)
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let store = &mut ctx.server.store;
+        for (key, val) in pairs(self, args)? {
+            set_string(store, key, val, Expiry::Clear)?;
+        }
+        Ok(CommandResult::Reply(DataType::simple_str("OK")))
+    }
+}
+
+// MSETNX key value [key value ...] -> nothing is set if any of the keys exist
+pub struct MSetNx;
+
+impl CommandHandler for MSetNx {
+    fn name(&self) -> &'static str {
+        "msetnx"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Atomically modifies the string values of one or more keys only when all keys don't exist."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::WRITE
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, -1, 2)
+    }
+
+    fn execute(&self, ctx: &mut Context, args: Args) -> R<CommandResult> {
+        let pairs = pairs(self, args)?;
+        let store = &mut ctx.server.store;
+        if pairs.iter().any(|(key, _)| store.contains_key(key)) {
+            return Ok(CommandResult::Reply(DataType::Integer(0)));
+        }
+        for (key, val) in pairs {
+            set_string(store, key, val, Expiry::Clear)?;
+        }
+        Ok(CommandResult::Reply(DataType::Integer(1)))
+    }
+}
+
+// The lengths of the longest common subsequences of every pair of prefixes of `a` and `b`. The entry for
+// a[..i] and b[..j] is at i * (b.len() + 1) + j.
+fn lcs_table(a: &[u8], b: &[u8]) -> Vec<u32> {
+    let width = b.len() + 1;
+    let mut table = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = match a[i - 1] == b[j - 1] {
+                true => table[(i - 1) * width + j - 1] + 1,
+                false => table[(i - 1) * width + j].max(table[i * width + j - 1]),
+            };
+        }
+    }
+    table
+}
+
+// A run of bytes both strings have in common -> (start, end) in `a`, the same in `b`, both inclusive
+type LcsMatch = ((usize, usize), (usize, usize));
+
+// Walks the table back from the end, returning the LCS and the matching ranges, last range first. This is
+// the same walk redis does, so the ranges are the ones it would report.
+fn lcs_matches(a: &[u8], b: &[u8], table: &[u32]) -> (Vec<u8>, Vec<LcsMatch>) {
+    let width = b.len() + 1;
+    let mut lcs = vec![0u8; table[a.len() * width + b.len()] as usize];
+    let mut idx = lcs.len();
+    let mut matches = Vec::new();
+    let (mut i, mut j) = (a.len(), b.len());
+    // the range being tracked, as it grows backwards
+    let mut range: Option<LcsMatch> = None;
+    while i > 0 && j > 0 {
+        let mut emit = false;
+        if a[i - 1] == b[j - 1] {
+            lcs[idx - 1] = a[i - 1];
+            range = match range {
+                None => Some(((i - 1, i - 1), (j - 1, j - 1))),
+                Some(((a_start, a_end), (b_start, b_end))) if a_start == i && b_start == j => {
+                    Some(((a_start - 1, a_end), (b_start - 1, b_end)))
+                }
+                Some(r) => {
+                    emit = true;
+                    Some(r)
+                }
+            };
+            // the range can't grow any further once it's reached the start of either string
+            if let Some(((0, _), _)) | Some((_, (0, _))) = range {
+                emit = true;
+            }
+            idx -= 1;
+            i -= 1;
+            j -= 1;
+        } else {
+            if table[(i - 1) * width + j] > table[i * width + j - 1] {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            emit = range.is_some();
+        }
+        if emit {
+            matches.extend(range.take());
+        }
+    }
+    (lcs, matches)
+}
+
+// LCS key1 key2 [LEN] [IDX] [MINMATCHLEN min-match-len] [WITHMATCHLEN]
+pub struct Lcs;
+
+impl CommandHandler for Lcs {
+    fn name(&self) -> &'static str {
+        "lcs"
+    }
+
+    fn arity(&self) -> i64 {
+        -3
+    }
+
+    fn summary(&self) -> &'static str {
+        "Finds the longest common substring."
+    }
+
+    fn group(&self) -> &'static str {
+        "string"
+    }
+
+    fn flags(&self) -> CommandFlags {
+        CommandFlags::READONLY
+    }
+
+    fn key_spec(&self) -> KeySpec {
+        KeySpec::new(1, 2, 1)
+    }
+
+    fn options(&self) -> &'static [OptionEntry] {
+        const OPTIONS: &[OptionEntry] = &[
+            OptionEntry::new("len", 0),
+            OptionEntry::new("idx", 0),
+            OptionEntry::new("minmatchlen", 1),
+            OptionEntry::new("withmatchlen", 0),
+        ];
+        OPTIONS
+    }
+
+    fn execute(&self, ctx: &mut Context, mut args: Args) -> R<CommandResult> {
+        let key1 = next_arg(&mut args, self.name())?;
+        let key2 = next_arg(&mut args, self.name())?;
+        let (mut len, mut idx, mut with_match_len) = (false, false, false);
+        let mut min_match_len = 0;
+        for opt in self.parse_options(args)? {
+            match opt.name {
+                "len" => len = true,
+                "idx" => idx = true,
+                "withmatchlen" => with_match_len = true,
+                // a negative length is the same as none
+                "minmatchlen" => min_match_len = parse_int::<i64>(&opt.value()?)?.max(0) as usize,
+                _ => return Err(CommandError::InvalidOption),
+            }
+        }
+        if len && idx {
+            return Err(CommandError::CommandFailed(
+                "ERR If you want both the length and indexes, please just use IDX.".to_string(),
+            ));
+        }
+        let store = &ctx.server.store;
+        let (a, b) = match (get_string(store, &key1), get_string(store, &key2)) {
+            (Ok(a), Ok(b)) => (a.unwrap_or_default(), b.unwrap_or_default()),
+            _ => {
+                return Err(CommandError::CommandFailed(
+                    "ERR The specified keys must contain string values".to_string(),
+                ))
+            }
+        };
+        // the table takes 4 bytes per pair of prefixes
+        let cells = (a.len() + 1)
+            .checked_mul(b.len() + 1)
+            .filter(|cells| *cells <= MAX_STRING_LEN / 4);
+        if cells.is_none() {
+            return Err(CommandError::CommandFailed(
+                "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len"
+                    .to_string(),
+            ));
+        }
+        let table = lcs_table(&a, &b);
+        let total = table[table.len() - 1] as i64;
+        if len {
+            return Ok(CommandResult::Reply(DataType::Integer(total)));
+        }
+        let (lcs, matches) = lcs_matches(&a, &b, &table);
+        if !idx {
+            return Ok(CommandResult::Reply(DataType::BulkString(Bytes::from(lcs))));
+        }
+        let pair = |start: usize, end: usize| {
+            DataType::Array(VecDeque::from([
+                DataType::Integer(start as i64),
+                DataType::Integer(end as i64),
+            ]))
+        };
+        let matches = matches
+            .into_iter()
+            .filter(|((start, end), _)| end - start + 1 >= min_match_len)
+            .map(|((a_start, a_end), (b_start, b_end))| {
+                let mut entry = VecDeque::from([pair(a_start, a_end), pair(b_start, b_end)]);
+                if with_match_len {
+                    entry.push_back(DataType::Integer((a_end - a_start + 1) as i64));
+                }
+                DataType::Array(entry)
+            })
+            .collect();
+        Ok(CommandResult::Reply(DataType::Map(VecDeque::from([
+            (DataType::bulk_str("matches"), DataType::Array(matches)),
+            (DataType::bulk_str("len"), DataType::Integer(total)),
+        ]))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use tokio::sync::RwLock;
+
+    use super::super::tests::{new_server, run, run_err};
+    use crate::resp::data::DataType;
+    use crate::server::errors::CommandError;
+    use crate::server::store::now_ms;
+    use crate::server::Server;
+
+    #[tokio::test]
+    async fn test_get_set() {
+        let server = new_server();
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"get", b"k"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("OK"),
+            run(&server, &[b"set", b"k", b"\x00v"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::BulkString(Bytes::from_static(b"\x00v")),
+            run(&server, &[b"get", b"k"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::simple_str("string"),
+            run(&server, &[b"type", b"k"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_malformed_options() {
+        let server = new_server();
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"set", b"k", b"v", b"px"]).await
+        );
+        assert_eq!(
+            "ERR value is not an integer or out of range",
+            run_err(&server, &[b"set", b"k", b"v", b"px", b"abc"]).await
+        );
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"set", b"k", b"v", b"foo"]).await
+        );
+        // options that can't go together
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"set", b"k", b"v", b"nx", b"xx"]).await
+        );
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"set", b"k", b"v", b"ex", b"1", b"px", b"1"]).await
+        );
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"set", b"k", b"v", b"px", b"1", b"keepttl"]).await
+        );
+        assert_eq!(
+            "ERR syntax error",
+            run_err(&server, &[b"getex", b"k", b"persist", b"ex", b"1"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_invalid_expire_time() {
+        let server = new_server();
+        let invalid = "ERR invalid expire time in 'set' command";
+        assert_eq!(
+            invalid,
+            run_err(&server, &[b"set", b"k", b"v", b"px", b"0"]).await
+        );
+        assert_eq!(
+            invalid,
+            run_err(&server, &[b"set", b"k", b"v", b"ex", b"-1"]).await
+        );
+        // too large to be a time in ms
+        assert_eq!(
+            invalid,
+            run_err(
+                &server,
+                &[b"set", b"k", b"v", b"ex", b"9223372036854775807"]
+            )
+            .await
+        );
+        assert_eq!(
+            "ERR invalid expire time in 'setex' command",
+            run_err(&server, &[b"setex", b"k", b"0", b"v"]).await
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"k"]).await.unwrap()
+        );
+    }
+
+    fn ttl(reply: DataType) -> i64 {
+        match reply {
+            DataType::Integer(n) => n,
+            other => panic!("expected an integer, got {:?}", other),
+        }
+    }
+
+    fn bulk(s: &str) -> DataType {
+        DataType::BulkString(Bytes::from(s.to_string()))
+    }
+
+    #[tokio::test]
+    async fn test_set_nx_xx() {
+        let server = new_server();
+        let ok = DataType::simple_str("OK");
+        // e.g. a lock
+        assert_eq!(
+            ok,
+            run(&server, &[b"set", b"lock", b"a", b"NX", b"PX", b"30000"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"set", b"lock", b"b", b"nx", b"px", b"30000"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"set", b"nope", b"b", b"xx"]).await.unwrap()
+        );
+        assert_eq!(
+            ok,
+            run(&server, &[b"set", b"lock", b"c", b"xx"]).await.unwrap()
+        );
+        assert_eq!(bulk("c"), run(&server, &[b"get", b"lock"]).await.unwrap());
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"nope"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_get() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"a"]).await.unwrap();
+        assert_eq!(
+            bulk("a"),
+            run(&server, &[b"set", b"k", b"b", b"xx", b"get"])
+                .await
+                .unwrap()
+        );
+        // the old value's returned even when the condition stops the write
+        assert_eq!(
+            bulk("b"),
+            run(&server, &[b"set", b"k", b"c", b"nx", b"get"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(bulk("b"), run(&server, &[b"get", b"k"]).await.unwrap());
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"set", b"new", b"a", b"get"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_setnx_getset_getdel() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"a"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"setnx", b"k", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"setnx", b"other", b"b"]).await.unwrap()
+        );
+        assert_eq!(
+            bulk("b"),
+            run(&server, &[b"getset", b"other", b"c"]).await.unwrap()
+        );
+        assert_eq!(
+            bulk("c"),
+            run(&server, &[b"getdel", b"other"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::NullBulkString,
+            run(&server, &[b"getdel", b"other"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_get_wrong_type() {
+        let server = new_server();
+        run(&server, &[b"xadd", b"s", b"1-1", b"f", b"v"])
+            .await
+            .unwrap();
+        let wrong_type = CommandError::WrongType.to_string();
+        assert_eq!(
+            wrong_type,
+            run_err(&server, &[b"set", b"s", b"v", b"get"]).await
+        );
+        assert_eq!(wrong_type, run_err(&server, &[b"getset", b"s", b"v"]).await);
+        // without GET there's nothing to read, so the stream's just replaced
+        assert_eq!(
+            DataType::simple_str("OK"),
+            run(&server, &[b"set", b"s", b"v"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_expiry() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"v", b"ex", b"100"])
+            .await
+            .unwrap();
+        assert_eq!(100, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        // a plain SET clears the expiry, KEEPTTL holds on to it
+        run(&server, &[b"set", b"k", b"v", b"ex", b"100"])
+            .await
+            .unwrap();
+        run(&server, &[b"set", b"k", b"w", b"keepttl"])
+            .await
+            .unwrap();
+        assert_eq!(100, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        run(&server, &[b"set", b"k", b"v"]).await.unwrap();
+        assert_eq!(-1, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+
+        let at = (now_ms() + 60_000).to_string();
+        run(&server, &[b"set", b"k", b"v", b"pxat", at.as_bytes()])
+            .await
+            .unwrap();
+        assert_eq!(60, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        run(&server, &[b"psetex", b"k", b"5000", b"v"])
+            .await
+            .unwrap();
+        assert_eq!(5, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        run(&server, &[b"setex", b"k", b"7", b"v"]).await.unwrap();
+        assert_eq!(7, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+
+        let v = DataType::BulkString(Bytes::from("v"));
+        assert_eq!(
+            v,
+            run(&server, &[b"getex", b"k", b"ex", b"9"]).await.unwrap()
+        );
+        assert_eq!(9, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        assert_eq!(v, run(&server, &[b"getex", b"k"]).await.unwrap());
+        assert_eq!(9, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        assert_eq!(
+            v,
+            run(&server, &[b"getex", b"k", b"persist"]).await.unwrap()
+        );
+        assert_eq!(-1, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+        assert_eq!(
+            v,
+            run(&server, &[b"getex", b"k", b"exat", b"1"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(-2, ttl(run(&server, &[b"ttl", b"k"]).await.unwrap()));
+    }
+
+    #[tokio::test]
+    async fn test_incr_decr() {
+        let server = new_server();
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"incr", b"n"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(42),
+            run(&server, &[b"incrby", b"n", b"41"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(41),
+            run(&server, &[b"decr", b"n"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(50),
+            run(&server, &[b"decrby", b"n", b"-9"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(-5),
+            run(&server, &[b"decrby", b"new", b"5"]).await.unwrap()
+        );
+        assert_eq!(bulk("50"), run(&server, &[b"get", b"n"]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_incr_keeps_expiry() {
+        let server = new_server();
+        run(&server, &[b"set", b"n", b"1", b"ex", b"100"])
+            .await
+            .unwrap();
+        run(&server, &[b"incr", b"n"]).await.unwrap();
+        assert_eq!(100, ttl(run(&server, &[b"ttl", b"n"]).await.unwrap()));
+        assert_eq!(bulk("2"), run(&server, &[b"get", b"n"]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_incr_errors() {
+        let server = new_server();
+        let max = i64::MAX.to_string();
+        run(&server, &[b"set", b"big", max.as_bytes()])
+            .await
+            .unwrap();
+        assert_eq!(
+            "ERR increment or decrement would overflow",
+            run_err(&server, &[b"incr", b"big"]).await
+        );
+        assert_eq!(
+            "ERR decrement would overflow",
+            run_err(&server, &[b"decrby", b"n", b"-9223372036854775808"]).await
+        );
+        // only the canonical form of an integer counts
+        run(&server, &[b"set", b"padded", b"007"]).await.unwrap();
+        assert_eq!(
+            "ERR value is not an integer or out of range",
+            run_err(&server, &[b"incr", b"padded"]).await
+        );
+        assert_eq!(
+            "ERR value is not an integer or out of range",
+            run_err(&server, &[b"incrby", b"n", b"1.5"]).await
+        );
+        run(&server, &[b"rpush", b"l", b"a"]).await.unwrap();
+        assert_eq!(
+            CommandError::WrongType.to_string(),
+            run_err(&server, &[b"incr", b"l"]).await
+        );
+    }
+
+    async fn incrbyfloat(server: &Arc<RwLock<Server>>, key: &str, by: &str) -> String {
+        let cmd: &[&[u8]] = &[b"incrbyfloat", key.as_bytes(), by.as_bytes()];
+        run(server, cmd).await.unwrap().try_to_string().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_incrbyfloat() {
+        let server = new_server();
+        run(&server, &[b"set", b"f", b"10.50"]).await.unwrap();
+        assert_eq!("10.6", incrbyfloat(&server, "f", "0.1").await);
+        assert_eq!("5.6", incrbyfloat(&server, "f", "-5").await);
+        // trailing zeros (and the point) are dropped
+        assert_eq!("6", incrbyfloat(&server, "f", "0.4").await);
+        assert_eq!("5006", incrbyfloat(&server, "f", "5.0e3").await);
+        assert_eq!("1.5", incrbyfloat(&server, "new", "1.5").await);
+        assert_eq!(bulk("1.5"), run(&server, &[b"get", b"new"]).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_incrbyfloat_rounding() {
+        let server = new_server();
+        run(&server, &[b"set", b"f", b"0.1"]).await.unwrap();
+        // no f64 rounding error creeps into the reply
+        assert_eq!("0.3", incrbyfloat(&server, "f", "0.2").await);
+        assert_eq!("0", incrbyfloat(&server, "f", "-0.3").await);
+        assert_eq!("0", incrbyfloat(&server, "f", "1e-20").await);
+        assert_eq!("0.00001", incrbyfloat(&server, "f", "1e-5").await);
+        assert_eq!(
+            "100000000000000000000",
+            incrbyfloat(&server, "f", "1e20").await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_incrbyfloat_errors() {
+        let server = new_server();
+        run(&server, &[b"set", b"s", b"abc"]).await.unwrap();
+        assert_eq!(
+            "ERR value is not a valid float",
+            run_err(&server, &[b"incrbyfloat", b"s", b"1"]).await
+        );
+        assert_eq!(
+            "ERR value is not a valid float",
+            run_err(&server, &[b"incrbyfloat", b"f", b"inf"]).await
+        );
+        run(&server, &[b"set", b"f", b"1.7e308"]).await.unwrap();
+        assert_eq!(
+            "ERR increment would produce NaN or Infinity",
+            run_err(&server, &[b"incrbyfloat", b"f", b"1.7e308"]).await
+        );
+        assert_eq!(
+            bulk("1.7e308"),
+            run(&server, &[b"get", b"f"]).await.unwrap()
+        );
+    }
+
+    #[test]
+    fn test_format_float() {
+        for (n, expected) in [
+            (0.1 + 0.2, "0.3"),
+            (1.0 / 3.0, "0.333333333333333"),
+            (-2.5, "-2.5"),
+            (11.0, "11"),
+            (1e-20, "0"),
+            (-1e-20, "0"),
+            (1.5e-17, "0.00000000000000002"),
+            (123456.789, "123456.789"),
+            (1e15, "1000000000000000"),
+        ] {
+            assert_eq!(expected, super::format_float(n), "{}", n);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_append_strlen() {
+        let server = new_server();
+        assert_eq!(
+            DataType::Integer(5),
+            run(&server, &[b"append", b"k", b"Hello"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(11),
+            run(&server, &[b"append", b"k", b" World"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(11),
+            run(&server, &[b"strlen", b"k"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"strlen", b"nope"]).await.unwrap()
+        );
+        // appending to a counter turns it back into a plain string
+        run(&server, &[b"set", b"n", b"10"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(3),
+            run(&server, &[b"append", b"n", b"5"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(106),
+            run(&server, &[b"incr", b"n"]).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_getrange() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"Hello World"]).await.unwrap();
+        assert_eq!(
+            bulk("Hello"),
+            run(&server, &[b"getrange", b"k", b"0", b"4"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulk("World"),
+            run(&server, &[b"getrange", b"k", b"-5", b"-1"])
+                .await
+                .unwrap()
+        );
+        // ranges are clamped to the string, and come out empty if they're backwards
+        assert_eq!(
+            bulk(""),
+            run(&server, &[b"getrange", b"k", b"-3", b"-5"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulk(" World"),
+            run(&server, &[b"getrange", b"k", b"5", b"100"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulk("He"),
+            run(&server, &[b"getrange", b"k", b"-100", b"1"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulk(""),
+            run(&server, &[b"getrange", b"nope", b"0", b"-1"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_setrange() {
+        let server = new_server();
+        run(&server, &[b"set", b"k", b"Hello World"]).await.unwrap();
+        assert_eq!(
+            DataType::Integer(11),
+            run(&server, &[b"setrange", b"k", b"6", b"Redis"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulk("Hello Redis"),
+            run(&server, &[b"get", b"k"]).await.unwrap()
+        );
+        // the gap is padded with zero bytes
+        assert_eq!(
+            DataType::Integer(4),
+            run(&server, &[b"setrange", b"pad", b"3", b"x"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            bulk("\0\0\0x"),
+            run(&server, &[b"get", b"pad"]).await.unwrap()
+        );
+        // nothing to write -> the key isn't created
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"setrange", b"empty", b"3", b""])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"empty"]).await.unwrap()
+        );
+
+        assert_eq!(
+            "ERR offset is out of range",
+            run_err(&server, &[b"setrange", b"k", b"-1", b"x"]).await
+        );
+        assert_eq!(
+            "ERR string exceeds maximum allowed size (proto-max-bulk-len)",
+            run_err(&server, &[b"setrange", b"k", b"536870912", b"x"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mset_msetnx() {
+        let server = new_server();
+        assert_eq!(
+            DataType::simple_str("OK"),
+            run(&server, &[b"mset", b"a", b"1", b"b", b"2"])
+                .await
+                .unwrap()
+        );
+        // all or nothing
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"msetnx", b"b", b"3", b"c", b"3"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(0),
+            run(&server, &[b"exists", b"c"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(1),
+            run(&server, &[b"msetnx", b"c", b"3", b"d", b"4"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            "ERR wrong number of arguments for 'mset' command",
+            run_err(&server, &[b"mset", b"a", b"1", b"b"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mget() {
+        let server = new_server();
+        run(&server, &[b"mset", b"a", b"1", b"b", b"2"])
+            .await
+            .unwrap();
+        run(&server, &[b"xadd", b"s", b"1-1", b"f", b"v"])
+            .await
+            .unwrap();
+        // a key that doesn't hold a string is a null, not an error
+        let expected = DataType::Array(
+            [
+                bulk("1"),
+                bulk("2"),
+                DataType::NullBulkString,
+                DataType::NullBulkString,
+            ]
+            .into(),
+        );
+        assert_eq!(
+            expected,
+            run(&server, &[b"mget", b"a", b"b", b"s", b"nope"])
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lcs() {
+        let server = new_server();
+        run(
+            &server,
+            &[b"mset", b"key1", b"ohmytext", b"key2", b"mynewtext"],
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            bulk("mytext"),
+            run(&server, &[b"lcs", b"key1", b"key2"]).await.unwrap()
+        );
+        assert_eq!(
+            DataType::Integer(6),
+            run(&server, &[b"lcs", b"key1", b"key2", b"len"])
+                .await
+                .unwrap()
+        );
+        let range =
+            |a: i64, b: i64| DataType::Array([DataType::Integer(a), DataType::Integer(b)].into());
+        let idx = |matches: Vec<DataType>| {
+            DataType::Map(
+                [
+                    (bulk("matches"), DataType::Array(matches.into())),
+                    (bulk("len"), DataType::Integer(6)),
+                ]
+                .into(),
+            )
+        };
+        assert_eq!(
+            idx(vec![
+                DataType::Array([range(4, 7), range(5, 8)].into()),
+                DataType::Array([range(2, 3), range(0, 1)].into()),
+            ]),
+            run(&server, &[b"lcs", b"key1", b"key2", b"idx"])
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            idx(vec![DataType::Array(
+                [range(4, 7), range(5, 8), DataType::Integer(4)].into()
+            )]),
+            run(
+                &server,
+                &[
+                    b"lcs",
+                    b"key1",
+                    b"key2",
+                    b"IDX",
+                    b"MINMATCHLEN",
+                    b"4",
+                    b"WITHMATCHLEN"
+                ]
+            )
+            .await
+            .unwrap()
+        );
+        assert_eq!(
+            bulk(""),
+            run(&server, &[b"lcs", b"key1", b"nope"]).await.unwrap()
+        );
+        assert_eq!(
+            "ERR If you want both the length and indexes, please just use IDX.",
+            run_err(&server, &[b"lcs", b"key1", b"key2", b"len", b"idx"]).await
+        );
+        run(&server, &[b"rpush", b"l", b"a"]).await.unwrap();
+        assert_eq!(
+            "ERR The specified keys must contain string values",
+            run_err(&server, &[b"lcs", b"key1", b"l"]).await
+        );
+    }
+}
@@ -1,57 +1,197 @@
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
 use crate::resp::data::DataType;
+use crate::resp::errors::RESPError;
 use crate::resp::parse::Parser;
 
 type R<T> = anyhow::Result<T>;
 
-pub async fn read_exact(_stream: &mut TcpStream) -> R<Option<DataType>> {
-    todo!()
+const READ_CHUNK: usize = 4096;
+
+// Accumulates bytes from a stream until they add up to a complete RESP frame. Anything past the end of the
+// frame stays buffered for the next call, so frames larger than a single read, or split across reads, are
+// handled the same way as small ones.
+#[derive(Debug)]
+pub struct FrameReader {
+    buffer: BytesMut,
+}
+
+impl Default for FrameReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameReader {
+    pub fn new() -> Self {
+        let buffer = BytesMut::with_capacity(READ_CHUNK);
+        Self { buffer }
+    }
+
+    // Ok(None) -> the buffer doesn't hold a complete frame yet
+    fn try_parse<F>(&mut self, parse: F) -> anyhow::Result<Option<DataType>, RESPError>
+    where
+        F: FnOnce(&mut Parser) -> anyhow::Result<DataType, RESPError>,
+    {
+        let mut parser = Parser::new(&self.buffer);
+        match parse(&mut parser) {
+            Ok(data) => {
+                let consumed = parser.position();
+                self.buffer.advance(consumed);
+                Ok(Some(data))
+            }
+            Err(RESPError::Incomplete) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Reads more bytes into the buffer. Returns false once the peer has closed the connection.
+    async fn fill<S: AsyncRead + Unpin>(&mut self, stream: &mut S) -> R<bool> {
+        self.buffer.reserve(READ_CHUNK);
+        let bytes_read = stream.read_buf(&mut self.buffer).await?;
+        if bytes_read == 0 && !self.buffer.is_empty() {
+            // the connection closed part way through a frame
+            return Err(RESPError::Incomplete.into());
+        }
+        Ok(bytes_read != 0)
+    }
+
+    // Returns the next frame off the stream, or None if the connection was closed cleanly.
+    pub async fn read_frame<S: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> R<Option<DataType>> {
+        loop {
+            if let Some(data) = self.try_parse(|p| p.parse())? {
+                return Ok(Some(data));
+            }
+            if !self.fill(stream).await? {
+                return Ok(None);
+            }
+        }
+    }
+
+    // Waits for at least one complete frame, then drains every other frame that is already buffered. A
+    // pipelining client (or a master propagating a burst of writes) gets all of them handled in one go. If a
+    // malformed frame follows them, the frames before it are still returned, along with the error -> like
+    // redis, the commands that came first get run before the connection is given up on.
+    pub async fn read_frames<S: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> R<Option<(Vec<DataType>, Option<RESPError>)>> {
+        let first = match self.read_frame(stream).await? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let mut frames = vec![first];
+        loop {
+            match self.try_parse(|p| p.parse()) {
+                Ok(Some(data)) => frames.push(data),
+                Ok(None) => return Ok(Some((frames, None))),
+                Err(e) => return Ok(Some((frames, Some(e)))),
+            }
+        }
+    }
+
+    // Resolves once the peer has closed the connection (or broken it off part way through a frame). Anything
+    // it sends in the meantime stays buffered for the next read.
+    pub async fn closed<S: AsyncRead + Unpin>(&mut self, stream: &mut S) {
+        while let Ok(true) = self.fill(stream).await {}
+    }
+
+    pub async fn read_store_file<S: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> R<Option<DataType>> {
+        loop {
+            if let Some(data) = self.try_parse(|p| p.parse_store_file())? {
+                return Ok(Some(data));
+            }
+            if !self.fill(stream).await? {
+                return Ok(None);
+            }
+        }
+    }
 }
 
 pub async fn multi_read(stream: &mut TcpStream) -> R<Vec<DataType>> {
     // We know the capacity expected for these tests
     let mut data = Vec::with_capacity(3);
-    loop {
-        let mut buffer = [0u8; 1024];
-        match stream.read(&mut buffer).await {
-            Ok(bytes) => {
-                if bytes == 0 {
-                    break;
-                }
-                let resp = Parser::new(&buffer).parse().unwrap();
-                data.push(resp);
-            }
-            Err(_) => panic!("Read failed!"),
-        }
+    let mut reader = FrameReader::new();
+    while let Some(resp) = reader.read_frame(stream).await? {
+        data.push(resp);
     }
     Ok(data)
 }
 
-pub async fn expect_resp(stream: &mut TcpStream, expected: &str) -> R<()> {
-    loop {
-        let mut buffer = [0u8; 1024];
-        match stream.read(&mut buffer).await {
-            Ok(bytes) => {
-                if bytes == 0 {
-                    break;
-                }
-                let resp = Parser::new(&buffer).parse().unwrap();
-                assert!(resp.cmp_str(expected));
-                return Ok(());
-            }
-            Err(_) => panic!("Read failed!"),
+pub async fn expect_resp(
+    stream: &mut TcpStream,
+    reader: &mut FrameReader,
+    expected: &str,
+) -> R<()> {
+    match reader.read_frame(stream).await? {
+        Some(resp) => {
+            assert!(resp.cmp_str(expected));
+            Ok(())
         }
+        None => panic!("No bytes received!"),
     }
-    panic!("No bytes received!")
 }
 
-pub async fn write(stream: &mut TcpStream, msg: String) -> R<()> {
-    stream
-        .write_all(msg.as_bytes())
-        .await
-        .expect("Write failed!");
+pub async fn write(stream: &mut TcpStream, msg: Vec<u8>) -> R<()> {
+    stream.write_all(&msg).await.expect("Write failed!");
     stream.flush().await.expect("Flush failed!");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::FrameReader;
+    use crate::resp::data::DataType;
+    use crate::resp::errors::RESPError;
+
+    #[tokio::test]
+    async fn test_read_frames_drains_pipeline() {
+        let mut data: &[u8] = b"+PING\r\n$4\r\nTEST\r\n*1\r\n+OK\r\n";
+        let mut reader = FrameReader::new();
+        let (frames, error) = reader.read_frames(&mut data).await.unwrap().unwrap();
+        assert_eq!(frames.len(), 3);
+        assert!(error.is_none());
+        assert!(reader.read_frames(&mut data).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_read_frames_keeps_frames_before_malformed_one() {
+        let mut data: &[u8] = b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nPING\r\n$x\r\n";
+        let mut reader = FrameReader::new();
+        let (frames, error) = reader.read_frames(&mut data).await.unwrap().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(matches!(error, Some(RESPError::InvalidData)));
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_across_reads() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let payload = "x".repeat(4096);
+        let frame = format!("${}\r\n{}\r\n", payload.len(), payload);
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            client.write_all(frame.as_bytes()).await.unwrap();
+        });
+        let mut reader = FrameReader::new();
+        let actual = reader.read_frame(&mut server).await.unwrap().unwrap();
+        assert_eq!(DataType::BulkString(Bytes::from(payload)), actual);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frame_is_err() {
+        let mut data: &[u8] = b"*2\r\n$3\r\nGET\r\n";
+        let mut reader = FrameReader::new();
+        assert!(reader.read_frame(&mut data).await.is_err());
+    }
+}
@@ -1,27 +1,55 @@
+use bytes::Bytes;
+
 use crate::stream::errors::StreamError;
 
+use super::store::errors::StoreError;
+
+// Displays as the error reply sent back to the client
 #[derive(Debug)]
 pub enum CommandError {
-    NotFound,
-    InvalidArgs,
+    NotFound(String, Vec<Bytes>), // (name, args)
+    InvalidArgs(String),          // (name) -> wrong number of args
     InvalidOption,
-    CommandFailed,
+    NotAnInteger,
+    WrongType,
+    Protocol(String),
+    CommandFailed(String), // the full reply, including the error code, e.g. 'NOPROTO ...'
 }
 
 impl std::fmt::Display for CommandError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::NotFound => {
-                write!(f, "Command Error: Not found!")
+            Self::NotFound(name, args) => {
+                write!(
+                    f,
+                    "ERR unknown command '{}', with args beginning with: ",
+                    name
+                )?;
+                for arg in args {
+                    write!(f, "'{}' ", String::from_utf8_lossy(arg))?;
+                }
+                Ok(())
             }
-            Self::InvalidArgs => {
-                write!(f, "Command Error: Invalid args!")
+            Self::InvalidArgs(name) => {
+                write!(f, "ERR wrong number of arguments for '{}' command", name)
             }
             Self::InvalidOption => {
-                write!(f, "Command Error: Invalid option!")
+                write!(f, "ERR syntax error")
+            }
+            Self::NotAnInteger => {
+                write!(f, "ERR value is not an integer or out of range")
+            }
+            Self::WrongType => {
+                write!(
+                    f,
+                    "WRONGTYPE Operation against a key holding the wrong kind of value"
+                )
+            }
+            Self::Protocol(msg) => {
+                write!(f, "ERR Protocol error: {}", msg)
             }
-            Self::CommandFailed => {
-                write!(f, "Command Error: Command failed!")
+            Self::CommandFailed(msg) => {
+                write!(f, "{}", msg)
             }
         }
     }
@@ -30,7 +58,23 @@ impl std::fmt::Display for CommandError {
 impl std::error::Error for CommandError {}
 
 impl From<StreamError> for CommandError {
-    fn from(_value: StreamError) -> Self {
-        Self::InvalidArgs
+    fn from(value: StreamError) -> Self {
+        match value {
+            StreamError::StreamIDZero => Self::CommandFailed(
+                "ERR The ID specified in XADD must be greater than 0-0".to_string(),
+            ),
+            StreamError::InvalidStreamID => Self::CommandFailed(
+                "ERR Invalid stream ID specified as stream command argument".to_string(),
+            ),
+        }
+    }
+}
+
+impl From<StoreError> for CommandError {
+    fn from(value: StoreError) -> Self {
+        match value {
+            StoreError::WrongType => Self::WrongType,
+            e => Self::CommandFailed(format!("ERR {}", e)),
+        }
     }
 }
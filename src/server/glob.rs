@@ -0,0 +1,148 @@
+// Glob-style matching with the same rules as redis (-> KEYS, SCAN MATCH, COMMAND LIST FILTERBY PATTERN):
+//   *      any run of bytes, including none
+//   ?      any single byte
+//   [abc]  any byte in the set. [^abc] negates it, [a-z] is a range.
+//   \x     x, literally
+// Matching is done on bytes, so keys don't need to be valid UTF-8.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| match nocase {
+        true => a.eq_ignore_ascii_case(&b),
+        false => a == b,
+    };
+    let (mut p, mut s) = (0, 0);
+    // Where to resume from when the last '*' needs to swallow one more byte
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s < string.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                // runs of '*' are the same as a single one
+                while pattern.get(p) == Some(&b'*') {
+                    p += 1;
+                }
+                if p == pattern.len() {
+                    return true;
+                }
+                backtrack = Some((p, s));
+                continue;
+            }
+            Some(b'?') => {
+                p += 1;
+                true
+            }
+            Some(b'[') => {
+                let (matched, next) = match_class(pattern, p + 1, string[s], nocase);
+                p = next;
+                matched
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                p += 2;
+                eq(pattern[p - 1], string[s])
+            }
+            Some(c) => {
+                p += 1;
+                eq(*c, string[s])
+            }
+            None => false,
+        };
+        if matched {
+            s += 1;
+            continue;
+        }
+        match backtrack {
+            Some((bp, bs)) => {
+                p = bp;
+                s = bs + 1;
+                backtrack = Some((bp, bs + 1));
+            }
+            None => return false,
+        }
+    }
+    // Whatever is left of the pattern has to be able to match nothing
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+// Matches `c` against the class starting at pattern[start] (just past the '['). Returns whether it matched
+// and where the rest of the pattern starts. An unterminated class runs to the end of the pattern, like in
+// redis.
+fn match_class(pattern: &[u8], start: usize, c: u8, nocase: bool) -> (bool, usize) {
+    let fold = |b: u8| match nocase {
+        true => b.to_ascii_lowercase(),
+        false => b,
+    };
+    let c = fold(c);
+    let mut p = start;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            p += 1;
+            matched |= fold(pattern[p]) == c;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let (mut lo, mut hi) = (fold(pattern[p]), fold(pattern[p + 2]));
+            if lo > hi {
+                std::mem::swap(&mut lo, &mut hi);
+            }
+            matched |= (lo..=hi).contains(&c);
+            p += 2;
+        } else {
+            matched |= fold(pattern[p]) == c;
+        }
+        p += 1;
+    }
+    // skip the closing ']'
+    let next = (p + 1).min(pattern.len());
+    (matched != negate, next)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::glob_match;
+
+    #[test]
+    fn test_glob_match() {
+        let cases: &[(&str, &str, bool)] = &[
+            ("*", "", true),
+            ("*", "anything", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("h*llo", "hllo", true),
+            ("h*llo", "heeeello", true),
+            ("h*llo", "hellox", false),
+            ("h[ae]llo", "hallo", true),
+            ("h[ae]llo", "hillo", false),
+            ("h[^e]llo", "hallo", true),
+            ("h[^e]llo", "hello", false),
+            ("h[a-b]llo", "hbllo", true),
+            ("h[b-a]llo", "hallo", true),
+            ("h[a-b]llo", "hcllo", false),
+            ("h\\*llo", "h*llo", true),
+            ("h\\*llo", "hello", false),
+            ("*a*b*", "xxaxxbxx", true),
+            ("*a*b", "xxaxxbxx", false),
+            ("user:*:name", "user:42:name", true),
+            ("a**", "a", true),
+            ("[abc", "a", true),
+        ];
+        for (pattern, string, expected) in cases {
+            assert_eq!(
+                *expected,
+                glob_match(pattern.as_bytes(), string.as_bytes(), false),
+                "'{}' against '{}'",
+                pattern,
+                string
+            );
+        }
+    }
+
+    #[test]
+    fn test_glob_match_nocase_and_bytes() {
+        assert!(glob_match(b"HEL*", b"hello", true));
+        assert!(!glob_match(b"HEL*", b"hello", false));
+        assert!(glob_match(b"[A-C]x", b"bx", true));
+        assert!(glob_match(b"\xff*", b"\xff\x00\x01", false));
+    }
+}
@@ -0,0 +1,283 @@
+use std::ffi::{c_int, c_void};
+use std::fs::Permissions;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use tokio::net::{TcpListener, TcpSocket, UnixListener};
+
+type R<T> = anyhow::Result<T>;
+
+// Matches the redis default -> loopback only, over IPv6 as well if the host has it
+pub const DEFAULT_BIND: &[&str] = &["127.0.0.1", "-::1"];
+
+// Like redis' default tcp-backlog
+const BACKLOG: u32 = 511;
+
+// An address to listen on. Like redis, a leading '-' marks the address as optional, so if it can't be bound
+// (e.g. the host has no IPv6) it's skipped instead of stopping the server from starting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindAddr {
+    pub addr: SocketAddr,
+    pub optional: bool,
+}
+
+impl BindAddr {
+    // Accepts IPv4 and IPv6 literals, '*' (every IPv4 interface), '::*' (every IPv6 interface) and host
+    // names. A host name can resolve to more than one address -> all of them are bound.
+    pub fn parse(arg: &str, port: u16) -> R<Vec<Self>> {
+        let (optional, host) = match arg.strip_prefix('-') {
+            Some(host) => (true, host),
+            None => (false, arg),
+        };
+        let ips = match host {
+            "*" => vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)],
+            "::*" => vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)],
+            host => resolve_all(host, port)?
+                .into_iter()
+                .map(|addr| addr.ip())
+                .collect(),
+        };
+        Ok(ips
+            .into_iter()
+            .map(|ip| Self {
+                addr: SocketAddr::new(ip, port),
+                optional,
+            })
+            .collect())
+    }
+}
+
+fn resolve_all(host: &str, port: u16) -> R<Vec<SocketAddr>> {
+    // IPv6 literals may come wrapped in brackets, e.g. [::1]
+    let host = host
+        .strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host);
+    if let Ok(ip) = IpAddr::from_str(host) {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .with_context(|| format!("Failed to resolve '{}'", host))?
+        .collect::<Vec<_>>();
+    match addrs.is_empty() {
+        true => Err(anyhow!("'{}' didn't resolve to any address", host)),
+        false => Ok(addrs),
+    }
+}
+
+// Resolves a host name (or an IP literal) to a single address, e.g. for connecting to a master
+pub fn resolve(host: &str, port: u16) -> R<SocketAddr> {
+    let addrs = resolve_all(host, port)?;
+    // prefer IPv4, since that's what a server bound with the defaults is most likely listening on
+    let addr = addrs
+        .iter()
+        .find(|addr| addr.is_ipv4())
+        .unwrap_or(&addrs[0]);
+    Ok(*addr)
+}
+
+// IPv6 sockets are IPv6 only, like redis' -> a listener on [::] doesn't also claim the IPv4 wildcard, so
+// '0.0.0.0' and '::' can both be bound. Neither std nor tokio can set the option, hence the setsockopt call.
+fn set_only_v6(socket: &TcpSocket) -> io::Result<()> {
+    const IPPROTO_IPV6: c_int = 41;
+    #[cfg(target_os = "linux")]
+    const IPV6_V6ONLY: c_int = 26;
+    #[cfg(not(target_os = "linux"))]
+    const IPV6_V6ONLY: c_int = 27;
+    extern "C" {
+        fn setsockopt(
+            fd: c_int,
+            level: c_int,
+            name: c_int,
+            value: *const c_void,
+            len: u32,
+        ) -> c_int;
+    }
+    let on: c_int = 1;
+    // SAFETY: the fd is the socket's own, and the value is a c_int that outlives the call
+    let ret = unsafe {
+        setsockopt(
+            socket.as_raw_fd(),
+            IPPROTO_IPV6,
+            IPV6_V6ONLY,
+            &on as *const c_int as *const c_void,
+            std::mem::size_of::<c_int>() as u32,
+        )
+    };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn bind_tcp(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => {
+            let socket = TcpSocket::new_v6()?;
+            set_only_v6(&socket)?;
+            socket
+        }
+    };
+    // as TcpListener::bind does, so a restarted server doesn't have to wait out TIME_WAIT
+    socket.set_reuseaddr(true)?;
+    socket.bind(addr)?;
+    socket.listen(BACKLOG)
+}
+
+// Binds a listener for each address. Fails if a required address can't be bound, or if nothing was bound.
+pub async fn bind(addrs: &[BindAddr]) -> R<Vec<TcpListener>> {
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for bind_addr in addrs {
+        match bind_tcp(bind_addr.addr) {
+            Ok(listener) => listeners.push(listener),
+            Err(e) if bind_addr.optional => {
+                eprintln!("Skipping optional address {}: {}", bind_addr.addr, e);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to bind to {}", bind_addr.addr));
+            }
+        }
+    }
+    match listeners.is_empty() {
+        true => Err(anyhow!("Failed to bind to any address")),
+        false => Ok(listeners),
+    }
+}
+
+// `--unixsocketperm` takes the mode in octal, e.g. 700
+pub fn parse_perm(arg: &str) -> R<u32> {
+    match u32::from_str_radix(arg, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(anyhow!("Invalid unix socket permissions '{}'", arg)),
+    }
+}
+
+// Binds a unix socket at `path`. Like redis, a file left over at the path (e.g. by a server that didn't shut
+// down cleanly) is removed first.
+pub fn bind_unix(path: &Path, perm: Option<u32>) -> R<UnixListener> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+        }
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind to {}", path.display()))?;
+    if let Some(mode) = perm {
+        std::fs::set_permissions(path, Permissions::from_mode(mode))
+            .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+    }
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::{bind, bind_unix, parse_perm, resolve, BindAddr};
+
+    #[test]
+    fn test_parse_bind_addrs() {
+        let v4 = BindAddr::parse("10.0.0.1", 6379).unwrap();
+        assert_eq!(
+            vec![BindAddr {
+                addr: "10.0.0.1:6379".parse().unwrap(),
+                optional: false
+            }],
+            v4
+        );
+        let v6 = BindAddr::parse("-::1", 6379).unwrap();
+        assert_eq!(
+            vec![BindAddr {
+                addr: SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 6379),
+                optional: true
+            }],
+            v6
+        );
+        let any = BindAddr::parse("*", 1234).unwrap();
+        assert_eq!(
+            SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 1234),
+            any[0].addr
+        );
+        let any_v6 = BindAddr::parse("::*", 1234).unwrap();
+        assert_eq!(
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 1234),
+            any_v6[0].addr
+        );
+        assert!(BindAddr::parse("not a host!", 6379).is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(
+            "127.0.0.1:6380".parse::<SocketAddr>().unwrap(),
+            resolve("127.0.0.1", 6380).unwrap()
+        );
+        assert_eq!(
+            "[::1]:6380".parse::<SocketAddr>().unwrap(),
+            resolve("[::1]", 6380).unwrap()
+        );
+        assert!(resolve("localhost", 6380).unwrap().ip().is_loopback());
+    }
+
+    #[tokio::test]
+    async fn test_bind_skips_optional() {
+        let taken = bind(&BindAddr::parse("127.0.0.1", 0).unwrap())
+            .await
+            .unwrap();
+        let port = taken[0].local_addr().unwrap().port();
+        // the port's in use -> the optional address is skipped, the required one is an error
+        let mut addrs = BindAddr::parse("-127.0.0.1", port).unwrap();
+        addrs.extend(BindAddr::parse("127.0.0.2", 0).unwrap());
+        assert_eq!(1, bind(&addrs).await.unwrap().len());
+        assert!(bind(&BindAddr::parse("127.0.0.1", port).unwrap())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_dual_stack() {
+        let probe = bind(&BindAddr::parse("::", 0).unwrap()).await.unwrap();
+        let port = probe[0].local_addr().unwrap().port();
+        drop(probe);
+        // the IPv6 wildcard leaves the IPv4 one alone, whichever is bound first
+        let mut addrs = BindAddr::parse("0.0.0.0", port).unwrap();
+        addrs.extend(BindAddr::parse("::", port).unwrap());
+        let listeners = bind(&addrs).await.unwrap();
+        assert_eq!(2, listeners.len());
+        drop(listeners);
+        addrs.reverse();
+        assert_eq!(2, bind(&addrs).await.unwrap().len());
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        assert_eq!(0o700, parse_perm("700").unwrap());
+        assert_eq!(0o755, parse_perm("0755").unwrap());
+        assert!(parse_perm("800").is_err());
+        assert!(parse_perm("77777").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix() {
+        let path = std::env::temp_dir().join(format!("redis-test-{}.sock", std::process::id()));
+        // a stale file at the path gets replaced
+        std::fs::write(&path, b"stale").unwrap();
+        let listener = bind_unix(&path, Some(0o700)).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o700, mode & 0o7777);
+        let client = tokio::net::UnixStream::connect(&path).await;
+        assert!(client.is_ok());
+        assert!(listener.accept().await.is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
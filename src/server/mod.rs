@@ -1,41 +1,54 @@
+pub mod client;
 pub mod command;
 pub mod connect;
 pub mod errors;
+pub mod glob;
+pub mod listen;
 pub mod replicate;
 pub mod store;
 
-use std::net::{Ipv4Addr, SocketAddrV4};
-use std::str::FromStr;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
 
-use tokio::io::AsyncReadExt;
+use anyhow::anyhow;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::sync::{Mutex, RwLock};
 
-use crate::resp::parse::Parser;
+use crate::resp::data::DataType;
+use crate::resp::errors::RESPError;
+use crate::resp::serialize::Serializer;
 
-use command::Command;
+use client::Client;
+use command::{CommandHandler, CommandRegistry, CommandResult};
+use connect::FrameReader;
+use errors::CommandError;
 use replicate::info::ReplicaInfo;
 use store::Store;
 
 use self::replicate::Replica;
 
+// The redis version we aim to be compatible with. Reported to clients through HELLO.
+pub const REDIS_VERSION: &str = "7.2.0";
+
 #[derive(Debug)]
 pub struct Server {
     pub port: u16,
-    pub master_ip: Option<Ipv4Addr>,
-    pub master_port: Option<u16>,
+    pub master_addr: Option<SocketAddr>,
     pub store: Store,
     pub replica_info: ReplicaInfo,
     pub replicas: Option<Vec<Replica>>,
     pub repl_queue: Option<Vec<String>>,
+    pub commands: CommandRegistry,
 }
 
 impl Server {
     pub fn new(
         port: u16,
-        master_ip: Option<Ipv4Addr>,
-        master_port: Option<u16>,
+        master_addr: Option<SocketAddr>,
         store: Store,
         replica_info: ReplicaInfo,
         replicas: Option<Vec<Replica>>,
@@ -43,32 +56,23 @@ impl Server {
     ) -> Self {
         Self {
             port,
-            master_ip,
-            master_port,
+            master_addr,
             store,
             replica_info,
             replicas,
             repl_queue,
+            commands: CommandRegistry::default(),
         }
     }
 
     pub fn master(port: u16) -> Self {
-        Self::new(
-            port,
-            None,
-            None,
-            Store::new(),
-            ReplicaInfo::master(),
-            None,
-            None,
-        )
+        Self::new(port, None, Store::new(), ReplicaInfo::master(), None, None)
     }
 
-    pub fn replica(port: u16, master_ip: Ipv4Addr, master_port: u16) -> Self {
+    pub fn replica(port: u16, master_addr: SocketAddr) -> Self {
         Self::new(
             port,
-            Some(master_ip),
-            Some(master_port),
+            Some(master_addr),
             Store::new(),
             ReplicaInfo::replica(),
             None,
@@ -76,11 +80,10 @@ impl Server {
         )
     }
 
-    pub fn master_addr(&self) -> Option<SocketAddrV4> {
-        match (self.master_ip, self.master_port) {
-            (Some(ip), Some(port)) => Some(SocketAddrV4::new(ip, port)),
-            _ => None,
-        }
+    // Adds a command on top of the built-in ones (or replaces one with the same name). Meant to be called at
+    // startup, before any connections are served.
+    pub fn register<C: CommandHandler + 'static>(&mut self, handler: C) {
+        self.commands.register(handler);
     }
 
     pub async fn propagate(&mut self, _stream: &Arc<Mutex<TcpStream>>) -> anyhow::Result<()> {
@@ -88,45 +91,293 @@ impl Server {
     }
 }
 
-pub fn init_on_startup(port: Option<u16>, replica_of: Option<Vec<String>>) -> Arc<RwLock<Server>> {
-    const DEFAULT_PORT: u16 = 6379;
+pub const DEFAULT_PORT: u16 = 6379;
+
+// `replica_of` is either ["<host>", "<port>"], or a single "<host> <port>" string (the form redis takes in
+// its config file). The host can be an IP literal or a name, which is resolved here.
+pub fn init_on_startup(
+    port: Option<u16>,
+    replica_of: Option<Vec<String>>,
+) -> anyhow::Result<Arc<RwLock<Server>>> {
     let port = port.unwrap_or(DEFAULT_PORT);
-    match replica_of {
-        // clap handles the parsing for the command args. We can unwrap repl_info safely, because if the arg
-        // format is incorrect, this function won't be called.
-        Some(mut repl_info) => {
-            // the unwraps here can panic
-            let master_port = repl_info.pop().unwrap().parse::<u16>().unwrap();
-            let master_ip = match repl_info.pop().unwrap().to_lowercase().as_str() {
-                "localhost" => Ipv4Addr::LOCALHOST,
-                x => Ipv4Addr::from_str(x).unwrap(),
+    let server = match replica_of {
+        Some(repl_info) => {
+            let parts = repl_info
+                .iter()
+                .flat_map(|arg| arg.split_whitespace())
+                .collect::<Vec<_>>();
+            let (host, master_port) = match parts.as_slice() {
+                [host, master_port] => (*host, *master_port),
+                _ => return Err(anyhow!("--replicaof expects <host> <port>")),
             };
-            Arc::new(RwLock::new(Server::replica(port, master_ip, master_port)))
+            let master_port = master_port
+                .parse::<u16>()
+                .map_err(|_| anyhow!("Invalid master port '{}'", master_port))?;
+            let master_addr = listen::resolve(host, master_port)?;
+            Server::replica(port, master_addr)
         }
-        None => Arc::new(RwLock::new(Server::master(port))),
-    }
+        None => Server::master(port),
+    };
+    Ok(Arc::new(RwLock::new(server)))
 }
 
-pub async fn handle_connection(
-    stream: &Arc<Mutex<TcpStream>>,
+// Serves a single client over any byte stream -> a TCP connection, a unix socket, or an in-memory pipe in tests
+pub async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &Arc<Mutex<S>>,
     server: &Arc<RwLock<Server>>,
 ) -> anyhow::Result<()> {
-    let mut buffer = [0; 1024];
+    let mut reader = FrameReader::new();
+    let mut client = Client::new();
+    let mut replies = Vec::with_capacity(1024);
     loop {
         let mut stream_lock = stream.lock().await;
-        let bytes_read = stream_lock
-            .read(&mut buffer)
-            .await
-            .expect("Failed to read from client stream!");
-        if bytes_read == 0 {
+        let (frames, error) = match reader.read_frames(&mut *stream_lock).await {
+            Ok(Some(read)) => read,
+            Ok(None) => break,
+            // Like redis, a malformed request gets an error reply, then the connection is closed -> there's
+            // no way to tell where the next frame would start.
+            Err(e) => {
+                if let Some(reason) = protocol_error(&e) {
+                    let err =
+                        DataType::SimpleError(CommandError::Protocol(reason).to_string().into());
+                    stream_lock
+                        .write_all(&Serializer::serialize(&err, client.protocol))
+                        .await?;
+                }
+                break;
+            }
+        };
+        // a client that's blocked is still watched, so it stops waiting if it goes away
+        let open = {
+            let closed = reader.closed(&mut *stream_lock);
+            tokio::pin!(closed);
+            run_frames(frames, &mut replies, server, &mut client, closed).await?
+        };
+        if !open {
             break;
         }
-
-        let mut parser = Parser::new(&buffer);
-        let data = parser.parse()?;
-        if let Some(cmd) = Command::new(data) {
-            cmd.execute(&mut stream_lock, server).await?;
+        stream_lock.write_all(&replies).await?;
+        replies.clear();
+        // the frames before a malformed one have been answered -> now it's the error's turn
+        if let Some(reason) = error.as_ref().and_then(protocol_reason) {
+            let err = DataType::SimpleError(CommandError::Protocol(reason).to_string().into());
+            stream_lock
+                .write_all(&Serializer::serialize(&err, client.protocol))
+                .await?;
+            break;
         }
     }
     Ok(())
 }
+
+// Runs each frame as a command, in the order received, with the replies written to `out`. Replies are
+// buffered by the caller so a whole pipeline gets answered with a single write.
+pub async fn execute_frames<W: AsyncWrite + Unpin>(
+    frames: Vec<DataType>,
+    out: &mut W,
+    server: &Arc<RwLock<Server>>,
+    client: &mut Client,
+) -> anyhow::Result<()> {
+    let never = std::future::pending();
+    tokio::pin!(never);
+    run_frames(frames, out, server, client, never).await?;
+    Ok(())
+}
+
+// Like execute_frames, but a command that blocks also stops waiting once `closed` resolves, i.e. the client
+// has gone away. Returns false if it did.
+async fn run_frames<W: AsyncWrite + Unpin, C: Future<Output = ()>>(
+    frames: Vec<DataType>,
+    out: &mut W,
+    server: &Arc<RwLock<Server>>,
+    client: &mut Client,
+    mut closed: Pin<&mut C>,
+) -> anyhow::Result<bool> {
+    for data in frames {
+        // redis ignores empty requests, e.g. a blank inline line
+        if matches!(&data, DataType::Array(arr) if arr.is_empty()) {
+            continue;
+        }
+        let replies = match command::dispatch(data, server, client).await {
+            Ok(CommandResult::Reply(reply)) => vec![reply],
+            Ok(CommandResult::Resync(reply, store_file)) => vec![reply, store_file],
+            Ok(CommandResult::Block(blocking)) => {
+                let id = blocking.id;
+                tokio::select! {
+                    // a reply that's been popped for the client goes to it if it can
+                    biased;
+                    reply = blocking.wait(server) => vec![reply],
+                    _ = closed.as_mut() => {
+                        server.write().await.store.blocked.unblock(id);
+                        return Ok(false);
+                    }
+                }
+            }
+            Err(e) => vec![DataType::SimpleError(e.to_string().into())],
+        };
+        // Serialized after the command has run -> HELLO's reply already uses the protocol it switched to
+        for reply in replies {
+            out.write_all(&Serializer::serialize(&reply, client.protocol))
+                .await?;
+        }
+    }
+    Ok(true)
+}
+
+// None -> the read failed for some reason other than the client sending a malformed frame
+fn protocol_error(e: &anyhow::Error) -> Option<String> {
+    protocol_reason(e.downcast_ref::<RESPError>()?)
+}
+
+fn protocol_reason(e: &RESPError) -> Option<String> {
+    match e {
+        RESPError::InvalidType => Some("invalid type byte".to_string()),
+        RESPError::InvalidData => Some("invalid frame".to_string()),
+        RESPError::Incomplete => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use tokio::sync::RwLock;
+
+    use super::{execute_frames, Client, Server};
+    use crate::resp::data::DataType;
+
+    fn command(args: &[&str]) -> DataType {
+        let args = args
+            .iter()
+            .map(|arg| DataType::BulkString(Bytes::copy_from_slice(arg.as_bytes())))
+            .collect::<VecDeque<_>>();
+        DataType::Array(args)
+    }
+
+    async fn run(server: &Arc<RwLock<Server>>, frames: Vec<DataType>) -> String {
+        let mut out = Vec::new();
+        let mut client = Client::new();
+        execute_frames(frames, &mut out, server, &mut client)
+            .await
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unknown_command_reply() {
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let frames = vec![command(&["FOO", "bar", "baz"]), command(&["PING"])];
+        assert_eq!(
+            "-ERR unknown command 'FOO', with args beginning with: 'bar' 'baz' \r\n+PONG\r\n",
+            run(&server, frames).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wrong_arity_reply() {
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let frames = vec![command(&["GET"]), command(&["set", "k"])];
+        assert_eq!(
+            "-ERR wrong number of arguments for 'get' command\r\n\
+             -ERR wrong number of arguments for 'set' command\r\n",
+            run(&server, frames).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wrong_type_reply() {
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let frames = vec![
+            command(&["XADD", "s", "1-1", "f", "v"]),
+            command(&["GET", "s"]),
+            command(&["SET", "s", "v"]),
+            command(&["XADD", "s", "1-2", "f", "v"]),
+            command(&["TYPE", "s"]),
+        ];
+        assert_eq!(
+            "$3\r\n1-1\r\n\
+             -WRONGTYPE Operation against a key holding the wrong kind of value\r\n\
+             +OK\r\n\
+             -WRONGTYPE Operation against a key holding the wrong kind of value\r\n\
+             +string\r\n",
+            run(&server, frames).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_request_ignored() {
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let frames = vec![DataType::Array(VecDeque::new()), command(&["PING"])];
+        assert_eq!("+PONG\r\n", run(&server, frames).await);
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_over_any_stream() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::sync::Mutex;
+
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let (mut client, conn) = tokio::io::duplex(1024);
+        let conn = Arc::new(Mutex::new(conn));
+        let handle = tokio::spawn(async move { super::handle_connection(&conn, &server).await });
+        client.write_all(b"*1\r\n$4\r\nPING\r\n").await.unwrap();
+        let mut buf = [0; 7];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"+PONG\r\n", &buf);
+        drop(client);
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_answered_before_protocol_error() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::sync::Mutex;
+
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let (mut client, conn) = tokio::io::duplex(1024);
+        let conn = Arc::new(Mutex::new(conn));
+        let handle = tokio::spawn(async move { super::handle_connection(&conn, &server).await });
+        client
+            .write_all(b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nPING\r\n$x\r\n")
+            .await
+            .unwrap();
+        // the connection is closed after the error
+        let mut out = String::new();
+        client.read_to_string(&mut out).await.unwrap();
+        assert_eq!(
+            "+PONG\r\n+PONG\r\n-ERR Protocol error: invalid frame\r\n",
+            out
+        );
+        assert!(handle.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_blocked_client_disconnects() {
+        use tokio::io::AsyncWriteExt;
+        use tokio::sync::Mutex;
+
+        let server = Arc::new(RwLock::new(Server::master(6379)));
+        let (mut client, conn) = tokio::io::duplex(1024);
+        let conn = Arc::new(Mutex::new(conn));
+        let handle = {
+            let server = server.clone();
+            tokio::spawn(async move { super::handle_connection(&conn, &server).await })
+        };
+        client
+            .write_all(b"*3\r\n$5\r\nBLPOP\r\n$1\r\nk\r\n$1\r\n0\r\n")
+            .await
+            .unwrap();
+        while server.read().await.store.blocked.is_empty() {
+            tokio::task::yield_now().await;
+        }
+        drop(client);
+        assert!(handle.await.unwrap().is_ok());
+        assert!(server.read().await.store.blocked.is_empty());
+        // nothing gets popped for the client that's gone
+        let frames = vec![command(&["RPUSH", "k", "v"]), command(&["LLEN", "k"])];
+        assert_eq!(":1\r\n:1\r\n", run(&server, frames).await);
+    }
+}
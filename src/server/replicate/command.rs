@@ -3,56 +3,104 @@ use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio::sync::RwLock;
 
+use crate::resp::data::DataType;
 use crate::resp::serialize::Serializer;
-use crate::server::connect::{expect_resp, write};
-use crate::server::Server;
+use crate::server::client::Client;
+use crate::server::connect::{expect_resp, write, FrameReader};
+use crate::server::{execute_frames, Server};
 
 use super::errors::ReplError;
 
 type R<T> = anyhow::Result<T, ReplError>;
 
-async fn do_follower_ping(s: &mut TcpStream) -> R<()> {
+async fn do_follower_ping(s: &mut TcpStream, reader: &mut FrameReader) -> R<()> {
     let ping = Serializer::to_arr(Vec::from(["ping"]));
     write(s, ping).await.expect("Write failed!");
-    expect_resp(s, "pong").await.expect("Read Failed!");
+    expect_resp(s, reader, "PONG").await.expect("Read Failed!");
     Ok(())
 }
 
-async fn do_follower_listen(s: &mut TcpStream, server: &Arc<RwLock<Server>>) -> R<()> {
+async fn do_follower_listen(
+    s: &mut TcpStream,
+    reader: &mut FrameReader,
+    server: &Arc<RwLock<Server>>,
+) -> R<()> {
     let listen = Serializer::to_arr(Vec::from([
         "REPLCONF",
         "listening-port",
         &server.read().await.port.to_string(),
     ]));
     write(s, listen).await.expect("Write failed!");
-    expect_resp(s, "ok").await.expect("Read failed!");
+    expect_resp(s, reader, "OK").await.expect("Read failed!");
     Ok(())
 }
 
-async fn do_follower_capa(s: &mut TcpStream) -> R<()> {
+async fn do_follower_capa(s: &mut TcpStream, reader: &mut FrameReader) -> R<()> {
     let capa = Serializer::to_arr(Vec::from(["REPLCONF", "capa", "psync2"]));
     write(s, capa).await.expect("Write failed!");
-    expect_resp(s, "ok").await.expect("Read failed!");
+    expect_resp(s, reader, "OK").await.expect("Read failed!");
     Ok(())
 }
 
-async fn do_follower_psync(s: &mut TcpStream, _server: &Arc<RwLock<Server>>) -> R<()> {
+async fn do_follower_psync(
+    s: &mut TcpStream,
+    reader: &mut FrameReader,
+    _server: &Arc<RwLock<Server>>,
+) -> R<()> {
     let psync = Serializer::to_arr(Vec::from(["PSYNC", "?", "-1"]));
     write(s, psync).await.expect("Write failed!");
-    expect_resp(s, "ok").await.expect("Read failed!");
-    Ok(())
+    // +FULLRESYNC <replid> <offset>, followed by the store file
+    match reader.read_frame(s).await {
+        Ok(Some(DataType::SimpleString(resync))) if resync.starts_with(b"FULLRESYNC") => {}
+        Ok(Some(_)) => return Err(ReplError::UnexpectedResponse),
+        _ => return Err(ReplError::InvalidResponse),
+    }
+    match reader.read_store_file(s).await {
+        Ok(Some(_)) => Ok(()),
+        _ => Err(ReplError::InvalidResponse),
+    }
 }
 
-pub async fn do_repl_handshake(server: Arc<RwLock<Server>>) -> R<()> {
-    // TODO -> The rest of the repl_handshake
-    let mut stream = TcpStream::connect(server.read().await.master_addr().unwrap())
+pub async fn do_repl_handshake(server: &Arc<RwLock<Server>>) -> R<(TcpStream, FrameReader)> {
+    let master_addr = server
+        .read()
+        .await
+        .master_addr
+        .ok_or(ReplError::FailedToConnect)?;
+    let mut stream = TcpStream::connect(master_addr)
         .await
-        .expect("Failed to connect!");
-    do_follower_ping(&mut stream).await?;
-    do_follower_listen(&mut stream, &server).await?;
-    do_follower_capa(&mut stream).await?;
-    do_follower_psync(&mut stream, &server).await?;
+        .map_err(|_| ReplError::FailedToConnect)?;
+    // The reader is shared across the handshake, so anything the master sends straight after the store
+    // file is kept for the replication loop.
+    let mut reader = FrameReader::new();
+    do_follower_ping(&mut stream, &mut reader).await?;
+    do_follower_listen(&mut stream, &mut reader, server).await?;
+    do_follower_capa(&mut stream, &mut reader).await?;
+    do_follower_psync(&mut stream, &mut reader, server).await?;
     // handshake complete!
 
+    Ok((stream, reader))
+}
+
+// Applies the writes the master propagates to us. Bursts are handled the same way a pipelining client's
+// commands are, except that the replies are discarded -> the master doesn't expect them.
+pub async fn follow_master(server: Arc<RwLock<Server>>) -> R<()> {
+    let (mut stream, mut reader) = do_repl_handshake(&server).await?;
+    let mut client = Client::new();
+    let mut sink = tokio::io::sink();
+    loop {
+        let (frames, error) = match reader.read_frames(&mut stream).await {
+            Ok(Some(read)) => read,
+            Ok(None) => break,
+            Err(_) => return Err(ReplError::InvalidResponse),
+        };
+        execute_frames(frames, &mut sink, &server, &mut client)
+            .await
+            .map_err(|_| ReplError::UnexpectedResponse)?;
+        // the writes before the malformed frame are applied, but there's no telling where the next one starts
+        if error.is_some() {
+            return Err(ReplError::InvalidResponse);
+        }
+    }
     Ok(())
 }
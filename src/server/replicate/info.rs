@@ -60,14 +60,25 @@ impl ReplicaInfo {
         Self::new(Role::Slave, 0, None, -1)
     }
 
+    pub fn role(&self) -> &str {
+        self.role.to_str()
+    }
+
+    pub fn fields(&self) -> Vec<(&str, String)> {
+        let master_replid = self.master_replid.as_deref().unwrap_or("?");
+        Vec::from([
+            ("role", self.role.to_str().to_string()),
+            ("master_replid", master_replid.to_string()),
+            ("master_repl_offset", self.master_repl_offset.to_string()),
+        ])
+    }
+
     #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
-        let role = format("role", self.role.to_str());
-        let master_replid = format(
-            "master_replid",
-            self.master_replid.as_ref().unwrap_or(&"?".to_string()),
-        );
-        let master_repl_offset = format("master_repl_offset", &self.master_repl_offset.to_string());
-        [&role, "\r\n", &master_replid, "\r\n", &master_repl_offset].concat()
+        self.fields()
+            .iter()
+            .map(|(k, v)| format(k, v))
+            .collect::<Vec<String>>()
+            .join("\r\n")
     }
 }
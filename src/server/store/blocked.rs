@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+use hashbrown::HashMap;
+use tokio::sync::oneshot;
+
+use crate::resp::data::DataType;
+
+use super::Store;
+
+// Clients blocked on keys (-> BLPOP and co). A blocked client waits on a channel, and is served from the
+// first of its keys that something gets pushed to. Every write that pushes onto a key marks it as ready, and
+// once the command that did it is done, the clients blocked on the ready keys are served in the order they
+// blocked. That all happens while the server is still locked, so a client is handed exactly what was popped
+// for it, and nothing else gets to the key first.
+
+// Tries to serve a blocked client from a key that's ready -> the client's reply, or None if there's nothing
+// there for it
+pub type Serve = Box<dyn Fn(&mut Store, &Bytes) -> Option<DataType> + Send + Sync>;
+
+struct Waiter {
+    keys: Vec<Bytes>,
+    serve: Serve,
+    tx: oneshot::Sender<DataType>,
+}
+
+#[derive(Default)]
+pub struct Blocked {
+    next_id: u64,
+    waiters: HashMap<u64, Waiter>,
+    // the clients blocked on each key, first come first served
+    queues: HashMap<Bytes, VecDeque<u64>>,
+    // keys pushed onto since blocked clients were last served
+    ready: VecDeque<Bytes>,
+}
+
+impl std::fmt::Debug for Blocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Blocked")
+            .field("clients", &self.waiters.len())
+            .field("keys", &self.queues.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Blocked {
+    // Blocks a client on `keys`. Returns the client's id in the registry, and where its reply will turn up.
+    pub fn block(&mut self, keys: Vec<Bytes>, serve: Serve) -> (u64, oneshot::Receiver<DataType>) {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut unique = Vec::with_capacity(keys.len());
+        for key in keys {
+            if !unique.contains(&key) {
+                self.queues.entry(key.clone()).or_default().push_back(id);
+                unique.push(key);
+            }
+        }
+        let (tx, rx) = oneshot::channel();
+        let waiter = Waiter {
+            keys: unique,
+            serve,
+            tx,
+        };
+        self.waiters.insert(id, waiter);
+        (id, rx)
+    }
+
+    // Stops a client waiting, e.g. when it times out. Returns false if it isn't blocked (anymore).
+    pub fn unblock(&mut self, id: u64) -> bool {
+        match self.waiters.remove(&id) {
+            Some(waiter) => {
+                self.dequeue(id, &waiter.keys);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn dequeue(&mut self, id: u64, keys: &[Bytes]) {
+        for key in keys {
+            if let Some(queue) = self.queues.get_mut(key) {
+                queue.retain(|w| *w != id);
+                if queue.is_empty() {
+                    self.queues.remove(key);
+                }
+            }
+        }
+    }
+
+    // The number of blocked clients
+    pub fn len(&self) -> usize {
+        self.waiters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+
+    // Marks a key as pushed onto. Only keys someone is blocked on are worth remembering.
+    pub fn signal(&mut self, key: &Bytes) {
+        if self.queues.contains_key(key) && !self.ready.contains(key) {
+            self.ready.push_back(key.clone());
+        }
+    }
+}
+
+impl Store {
+    // Serves the clients blocked on the keys that are ready. Serving one client can ready another key (e.g.
+    // BLMOVE pushing onto its destination), which gets served in the same go.
+    pub fn serve_blocked(&mut self) {
+        while let Some(key) = self.blocked.ready.pop_front() {
+            while let Some(id) = self
+                .blocked
+                .queues
+                .get(&key)
+                .and_then(|q| q.front().copied())
+            {
+                let waiter = self.blocked.waiters.remove(&id).unwrap();
+                // the client went away -> don't pop anything for it
+                if waiter.tx.is_closed() {
+                    self.blocked.dequeue(id, &waiter.keys);
+                    continue;
+                }
+                match (waiter.serve)(self, &key) {
+                    Some(reply) => {
+                        self.blocked.dequeue(id, &waiter.keys);
+                        let _ = waiter.tx.send(reply);
+                    }
+                    // the key's run dry -> the rest of its clients carry on waiting
+                    None => {
+                        self.blocked.waiters.insert(id, waiter);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::Serve;
+    use crate::resp::data::DataType;
+    use crate::server::store::Store;
+
+    // Pops the head of the list at the ready key
+    fn pop() -> Serve {
+        Box::new(|store: &mut Store, key: &Bytes| {
+            let list = store.list_store.get_mut(key)?;
+            let val = list.pop_front()?;
+            if list.is_empty() {
+                store.remove(key);
+            }
+            Some(DataType::BulkString(val))
+        })
+    }
+
+    fn push(store: &mut Store, key: &str, vals: &[&str]) {
+        let list = store.list_for_push(Bytes::copy_from_slice(key.as_bytes()));
+        for val in vals {
+            list.push_back(Bytes::copy_from_slice(val.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_served_in_order() {
+        let mut store = Store::new();
+        let (_, mut first) = store
+            .blocked
+            .block(vec![Bytes::from("a"), Bytes::from("b")], pop());
+        let (_, mut second) = store.blocked.block(vec![Bytes::from("b")], pop());
+        let (_, mut third) = store.blocked.block(vec![Bytes::from("b")], pop());
+        assert_eq!(3, store.blocked.len());
+
+        push(&mut store, "b", &["1", "2"]);
+        store.serve_blocked();
+        assert_eq!(DataType::bulk_str("1"), first.try_recv().unwrap());
+        assert_eq!(DataType::bulk_str("2"), second.try_recv().unwrap());
+        assert!(third.try_recv().is_err());
+        assert!(!store.contains_key(b"b"));
+        // the first client isn't waiting on 'a' anymore either
+        assert_eq!(1, store.blocked.len());
+        push(&mut store, "a", &["x"]);
+        store.serve_blocked();
+        assert!(store.contains_key(b"a"));
+    }
+
+    #[test]
+    fn test_unblock_and_closed_clients() {
+        let mut store = Store::new();
+        let (id, _) = store.blocked.block(vec![Bytes::from("k")], pop());
+        let (gone, rx) = store.blocked.block(vec![Bytes::from("k")], pop());
+        drop(rx);
+        let (_, mut waiting) = store.blocked.block(vec![Bytes::from("k")], pop());
+        assert!(store.blocked.unblock(id));
+        assert!(!store.blocked.unblock(id));
+
+        push(&mut store, "k", &["1"]);
+        store.serve_blocked();
+        // the client that went away was skipped, not handed the element
+        assert!(!store.blocked.unblock(gone));
+        assert_eq!(DataType::bulk_str("1"), waiting.try_recv().unwrap());
+        assert!(store.blocked.is_empty());
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+use bytes::Bytes;
+use rand::{thread_rng, Rng};
+
+// The fewest buckets a dict that isn't empty has
+const MIN_BUCKETS: usize = 4;
+
+// A hash map that can be walked with a cursor (-> SCAN) while it's being changed.
+//
+// Like redis' dict, it's a table of buckets (a power of two of them), each a chain of the entries whose hash
+// ends in the bucket's index. A cursor is a bucket index, incremented from its highest bit down rather than
+// from its lowest bit up. When the table doubles, a bucket splits into two whose indexes are it with a new
+// high bit, and those come up together, right where the bucket did -> so a key that's in the dict for the
+// whole of a scan is returned, however the table grows or shrinks in the meantime. A key can come up twice
+// if the table shrinks, never on a table that only grows.
+#[derive(Debug)]
+pub struct Dict<V> {
+    hasher: RandomState,
+    buckets: Vec<Option<Box<Entry<V>>>>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    hash: u64,
+    key: Bytes,
+    val: V,
+    next: Option<Box<Entry<V>>>,
+}
+
+// The entries in a bucket
+fn chain<V>(head: &Option<Box<Entry<V>>>) -> impl Iterator<Item = &Entry<V>> {
+    std::iter::successors(head.as_deref(), |entry| entry.next.as_deref())
+}
+
+impl<V> Default for Dict<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Dict<V> {
+    pub fn new() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            buckets: Vec::new(),
+            len: 0,
+        }
+    }
+
+    #[inline]
+    fn hash(&self, key: &[u8]) -> u64 {
+        self.hasher.hash_one(key)
+    }
+
+    #[inline]
+    fn bucket(&self, hash: u64) -> usize {
+        hash as usize & (self.buckets.len() - 1)
+    }
+
+    fn find(&self, key: &[u8]) -> Option<&Entry<V>> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let hash = self.hash(key);
+        chain(&self.buckets[self.bucket(hash)]).find(|entry| entry.hash == hash && entry.key == key)
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.find(key).map(|entry| &entry.val)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut V> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let hash = self.hash(key);
+        let bucket = self.bucket(hash);
+        let mut next = self.buckets[bucket].as_deref_mut();
+        while let Some(entry) = next {
+            if entry.hash == hash && entry.key == key {
+                return Some(&mut entry.val);
+            }
+            next = entry.next.as_deref_mut();
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.find(key).is_some()
+    }
+
+    pub fn insert(&mut self, key: Bytes, val: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(std::mem::replace(old, val));
+        }
+        if self.len >= self.buckets.len() {
+            self.resize((self.buckets.len() * 2).max(MIN_BUCKETS));
+        }
+        let hash = self.hash(&key);
+        let bucket = self.bucket(hash);
+        let next = self.buckets[bucket].take();
+        self.buckets[bucket] = Some(Box::new(Entry {
+            hash,
+            key,
+            val,
+            next,
+        }));
+        self.len += 1;
+        None
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry(&mut self, key: &[u8]) -> Option<(Bytes, V)> {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        let hash = self.hash(key);
+        let bucket = self.bucket(hash);
+        let mut link = &mut self.buckets[bucket];
+        while link
+            .as_ref()
+            .is_some_and(|entry| entry.hash != hash || entry.key != key)
+        {
+            link = &mut link.as_mut().unwrap().next;
+        }
+        let mut entry = link.take()?;
+        *link = entry.next.take();
+        self.len -= 1;
+        self.shrink_if_sparse();
+        Some((entry.key, entry.val))
+    }
+
+    // Keeps the entries `keep` returns true for, and drops the rest, in one walk of the table. Only the
+    // entries that go are touched, and the table's resized once at the end rather than as it empties.
+    pub fn retain(&mut self, mut keep: impl FnMut(&Bytes, &mut V) -> bool) {
+        let mut removed = 0;
+        for head in &mut self.buckets {
+            let mut rest = head.take();
+            let mut tail = head;
+            while let Some(mut entry) = rest {
+                rest = entry.next.take();
+                if keep(&entry.key, &mut entry.val) {
+                    tail = &mut tail.insert(entry).next;
+                } else {
+                    removed += 1;
+                }
+            }
+        }
+        self.len -= removed;
+        self.shrink_if_sparse();
+    }
+
+    // Like redis, the table shrinks once it's less than 1/8 full -> a dict that's emptied frees it all
+    fn shrink_if_sparse(&mut self) {
+        if self.len == 0 {
+            self.buckets = Vec::new();
+        } else if self.buckets.len() > MIN_BUCKETS && self.len * 8 < self.buckets.len() {
+            self.resize(self.len.next_power_of_two().max(MIN_BUCKETS));
+        }
+    }
+
+    // Moves the entries into a table of `size` buckets. The entries themselves stay where they are.
+    fn resize(&mut self, size: usize) {
+        let empty = std::iter::repeat_with(|| None).take(size).collect();
+        let old = std::mem::replace(&mut self.buckets, empty);
+        for mut head in old {
+            while let Some(mut entry) = head {
+                head = entry.next.take();
+                let bucket = self.bucket(entry.hash);
+                entry.next = self.buckets[bucket].take();
+                self.buckets[bucket] = Some(entry);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &V)> {
+        self.buckets
+            .iter()
+            .flat_map(chain)
+            .map(|entry| (&entry.key, &entry.val))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Bytes> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, val)| val)
+    }
+
+    // An entry picked at random, without walking the dict: a random bucket that isn't empty, then a random
+    // entry in it, like redis' dictGetRandomKey. The odds aren't even -> an entry that shares its bucket comes
+    // up less often than one that has a bucket to itself.
+    pub fn random(&self) -> Option<(&Bytes, &V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let mut rng = thread_rng();
+        // at least 1/8 of the buckets are in use, so this doesn't take many tries
+        loop {
+            let head = &self.buckets[rng.gen_range(0..self.buckets.len())];
+            let len = chain(head).count();
+            if len > 0 {
+                let entry = chain(head).nth(rng.gen_range(0..len)).unwrap();
+                return Some((&entry.key, &entry.val));
+            }
+        }
+    }
+
+    // Returns the entries in the buckets from `cursor` on, until there are at least `count` of them, and the
+    // cursor to carry on from. A scan starts at 0, and is done when 0 is returned. Like redis, empty buckets
+    // count towards a limit too, so a call on a sparse table doesn't walk all of it.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, impl Iterator<Item = (&Bytes, &V)>) {
+        let mut entries = Vec::new();
+        if self.buckets.is_empty() {
+            return (0, entries.into_iter());
+        }
+        let mask = self.buckets.len() as u64 - 1;
+        let mut cursor = cursor;
+        let mut visits = count.max(1) * 10;
+        loop {
+            let head = &self.buckets[(cursor & mask) as usize];
+            entries.extend(chain(head).map(|entry| (&entry.key, &entry.val)));
+            // the bits above the mask are set, so adding 1 to the reversed cursor carries into its top bit
+            cursor = (cursor | !mask)
+                .reverse_bits()
+                .wrapping_add(1)
+                .reverse_bits();
+            visits -= 1;
+            if cursor == 0 || entries.len() >= count || visits == 0 {
+                return (cursor, entries.into_iter());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+
+    use bytes::Bytes;
+
+    use super::Dict;
+
+    fn key(i: usize) -> Bytes {
+        Bytes::from(format!("key:{}", i))
+    }
+
+    // Runs a full scan, calling `between` after every call
+    fn scan_all(
+        dict: &mut Dict<usize>,
+        count: usize,
+        mut between: impl FnMut(&mut Dict<usize>),
+    ) -> Vec<Bytes> {
+        let (mut cursor, mut seen) = (0, Vec::new());
+        loop {
+            let (next, batch) = dict.scan(cursor, count);
+            seen.extend(batch.map(|(k, _)| k.clone()));
+            between(dict);
+            if next == 0 {
+                return seen;
+            }
+            cursor = next;
+        }
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut dict = Dict::new();
+        assert_eq!(None, dict.insert(key(1), 1));
+        assert_eq!(Some(1), dict.insert(key(1), 2));
+        assert_eq!(Some(&2), dict.get(b"key:1"));
+        *dict.get_mut(b"key:1").unwrap() += 1;
+        assert_eq!(1, dict.len());
+        assert_eq!(Some(3), dict.remove(b"key:1"));
+        assert_eq!(None, dict.remove(b"key:1"));
+        assert!(dict.is_empty());
+        assert_eq!(0, dict.scan(0, 10).0);
+
+        for i in 0..1000 {
+            dict.insert(key(i), i);
+        }
+        assert_eq!(1024, dict.buckets.len());
+        assert!((0..1000).all(|i| dict.get(&key(i)) == Some(&i)));
+        for i in 10..1000 {
+            assert_eq!(Some(i), dict.remove(&key(i)));
+        }
+        // it shrinks as it empties
+        assert_eq!(16, dict.buckets.len());
+        assert_eq!(10, dict.iter().count());
+        assert!((0..10).all(|i| dict.contains_key(&key(i))));
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut dict = Dict::new();
+        for i in 0..1000 {
+            dict.insert(key(i), i);
+        }
+        dict.retain(|_, val| {
+            *val += 1;
+            *val % 100 == 0
+        });
+        assert_eq!(10, dict.len());
+        assert_eq!(16, dict.buckets.len());
+        assert!((1..=10).all(|i| dict.get(&key(i * 100 - 1)) == Some(&(i * 100))));
+        dict.retain(|_, _| false);
+        assert!(dict.is_empty());
+        assert!(dict.buckets.is_empty());
+    }
+
+    #[test]
+    fn test_random() {
+        let mut dict = Dict::new();
+        assert!(dict.random().is_none());
+        for i in 0..100 {
+            dict.insert(key(i), i);
+        }
+        let picked = (0..1000)
+            .map(|_| *dict.random().unwrap().1)
+            .collect::<HashSet<_>>();
+        // not evenly, but it gets around
+        assert!(picked.len() > 50);
+        dict.remove(b"key:0");
+        assert!((0..100).all(|_| dict.random().unwrap().1 != &0));
+    }
+
+    #[test]
+    fn test_scan_returns_every_key_once() {
+        let mut dict = Dict::new();
+        for i in 0..1000 {
+            dict.insert(key(i), i);
+        }
+        for count in [1, 7, 1000, 5000] {
+            let seen = scan_all(&mut dict, count, |_| {});
+            assert_eq!(1000, seen.len());
+            assert_eq!(1000, seen.iter().collect::<HashSet<_>>().len());
+        }
+    }
+
+    #[test]
+    fn test_scan_while_resizing() {
+        let mut dict = Dict::new();
+        for i in 0..100 {
+            dict.insert(key(i), i);
+        }
+        // grow the table well past its size while scanning, then shrink it again
+        let mut next = 100;
+        let seen = scan_all(&mut dict, 10, |dict| {
+            for _ in 0..200 {
+                if next < 2000 {
+                    dict.insert(key(next), next);
+                    next += 1;
+                }
+            }
+        });
+        // growing doesn't return anything twice
+        assert_eq!(seen.len(), seen.iter().collect::<HashSet<_>>().len());
+        assert!((0..100).all(|i| seen.contains(&key(i))));
+
+        let seen = scan_all(&mut dict, 10, |dict| {
+            let keys = dict
+                .keys()
+                .filter(|k| k.len() > 6)
+                .take(300)
+                .cloned()
+                .collect::<Vec<_>>();
+            for k in keys {
+                dict.remove(&k);
+            }
+        })
+        .into_iter()
+        .collect::<HashSet<_>>();
+        assert!((0..100).all(|i| seen.contains(&key(i))));
+    }
+}
@@ -2,6 +2,7 @@
 pub enum StoreError {
     ReadFailed,
     WriteFailed,
+    WrongType,
 }
 
 impl std::fmt::Display for StoreError {
@@ -13,6 +14,9 @@ impl std::fmt::Display for StoreError {
             Self::WriteFailed => {
                 write!(f, "Store Error: Write failed!")
             }
+            Self::WrongType => {
+                write!(f, "Store Error: Key holds a value of another type!")
+            }
         }
     }
 }
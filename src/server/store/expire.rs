@@ -0,0 +1,276 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use tokio::sync::RwLock;
+
+use crate::server::Server;
+
+use super::{now_ms, Hash, Store};
+
+// Active expiry. Keys are removed when a command touches them after they've expired, but a key nobody
+// touches again would hold on to its memory forever. So, like redis, the keys with an expiry are also swept
+// in the background: `hz` times a second, a cycle walks them a few at a time, and keeps going while enough
+// of what it sees has expired, up to a time budget. The hashes with fields that expire are swept the same way
+// (-> Store::hash_expires).
+
+pub const DEFAULT_HZ: u32 = 10;
+pub const DEFAULT_EFFORT: u32 = 1;
+
+// At effort 1, like redis' defaults. Each step of effort checks more keys per loop, settles for fewer stale
+// keys left behind, and allows a cycle more time.
+const KEYS_PER_LOOP: usize = 20;
+const ACCEPTABLE_STALE_PERCENT: usize = 10;
+const CYCLE_TIME_PERCENT: u32 = 25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveExpire {
+    pub hz: u32,
+    keys_per_loop: usize,
+    acceptable_stale: usize,
+    time_limit: Duration,
+}
+
+impl Default for ActiveExpire {
+    fn default() -> Self {
+        Self::new(DEFAULT_HZ, DEFAULT_EFFORT)
+    }
+}
+
+impl ActiveExpire {
+    // `hz` is clamped to 1..=500 and `effort` to 1..=10, like redis
+    pub fn new(hz: u32, effort: u32) -> Self {
+        let hz = hz.clamp(1, 500);
+        let effort = effort.clamp(1, 10) - 1;
+        let time_percent = CYCLE_TIME_PERCENT + 2 * effort;
+        Self {
+            hz,
+            keys_per_loop: KEYS_PER_LOOP + KEYS_PER_LOOP / 4 * effort as usize,
+            acceptable_stale: ACCEPTABLE_STALE_PERCENT - effort as usize,
+            // the share of each tick a cycle may spend
+            time_limit: Duration::from_micros(10_000 * time_percent as u64 / hz as u64),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_micros(1_000_000 / self.hz as u64)
+    }
+}
+
+// Where the sweep is up to. The cursors carry over between cycles, so every key with an expiry (and every hash
+// with fields that expire) gets checked in turn, however many there are.
+#[derive(Debug)]
+pub struct ExpireCycle {
+    config: ActiveExpire,
+    cursor: u64,
+    field_cursor: u64,
+}
+
+// A step of a sweep -> (Store, cursor, count) to (next cursor, checked, expired)
+type Step = fn(&mut Store, u64, usize) -> (u64, usize, usize);
+
+impl ExpireCycle {
+    pub fn new(config: ActiveExpire) -> Self {
+        Self {
+            config,
+            cursor: 0,
+            field_cursor: 0,
+        }
+    }
+
+    // Runs a cycle, returning how many keys it removed plus how many hashes it removed expired fields from.
+    // The keys are swept first, and the hashes get what's left of the time budget.
+    pub fn run(&mut self, store: &mut Store) -> usize {
+        let start = Instant::now();
+        let removed = sweep(
+            &self.config,
+            start,
+            &mut self.cursor,
+            store,
+            Store::expire_step,
+        );
+        removed
+            + sweep(
+                &self.config,
+                start,
+                &mut self.field_cursor,
+                store,
+                Store::expire_fields_step,
+            )
+    }
+}
+
+// Takes steps from `cursor` until few enough of what a step checks had expired, or the time's up
+fn sweep(
+    config: &ActiveExpire,
+    start: Instant,
+    cursor: &mut u64,
+    store: &mut Store,
+    step: Step,
+) -> usize {
+    let mut removed = 0;
+    loop {
+        let (next, checked, expired) = step(store, *cursor, config.keys_per_loop);
+        *cursor = next;
+        removed += expired;
+        // few enough had expired that another loop isn't worth it (or there was nothing to check)
+        if expired * 100 <= checked * config.acceptable_stale || checked == 0 {
+            break;
+        }
+        if start.elapsed() > config.time_limit {
+            break;
+        }
+    }
+    removed
+}
+
+impl Store {
+    // Checks about `count` keys with an expiry from `cursor`, removing the ones whose time is up. Returns the
+    // cursor to carry on from, how many keys were checked and how many were removed.
+    pub fn expire_step(&mut self, cursor: u64, count: usize) -> (u64, usize, usize) {
+        let now = now_ms();
+        let (next, batch) = self.expires.scan(cursor, count);
+        let mut checked = 0;
+        let expired = batch
+            .inspect(|_| checked += 1)
+            .filter(|(_, at)| **at < now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<Bytes>>();
+        for key in &expired {
+            self.remove(key);
+        }
+        (next, checked, expired.len())
+    }
+
+    // Like expire_step, for about `count` hashes with fields that expire: removes the fields whose time is up,
+    // and the hash if that empties it. Returns how many hashes had expired fields, rather than keys removed.
+    pub fn expire_fields_step(&mut self, cursor: u64, count: usize) -> (u64, usize, usize) {
+        let now = now_ms();
+        let (next, batch) = self.hash_expires.scan(cursor, count);
+        let mut checked = 0;
+        let due = batch
+            .inspect(|_| checked += 1)
+            .filter(|(_, at)| **at < now)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<Bytes>>();
+        let mut expired = 0;
+        for key in &due {
+            // the entry can be early (-> Store::hash_expires), in which case this only brings it up to date
+            let removed = self
+                .hash_store
+                .get_mut(key)
+                .map_or(0, |hash| hash.remove_expired(now));
+            if removed > 0 {
+                expired += 1;
+            }
+            self.track_field_expiry(key);
+            if self.hash_store.get(key).is_some_and(Hash::is_empty) {
+                self.remove(key);
+            }
+        }
+        (next, checked, expired)
+    }
+}
+
+// Runs an expiry cycle every tick, for as long as the server's up
+pub async fn active_expire(server: Arc<RwLock<Server>>, config: ActiveExpire) {
+    let mut cycle = ExpireCycle::new(config);
+    let mut interval = tokio::time::interval(config.interval());
+    loop {
+        interval.tick().await;
+        cycle.run(&mut server.write().await.store);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::{ActiveExpire, ExpireCycle};
+    use crate::server::store::{now_ms, Store};
+
+    fn fill(store: &mut Store, prefix: &str, n: usize, at: i64) {
+        for i in 0..n {
+            let key = Bytes::from(format!("{}:{}", prefix, i));
+            store.kv_store.try_write(key.clone(), Bytes::new()).unwrap();
+            store.set_expire_at(key, at);
+        }
+    }
+
+    #[test]
+    fn test_config() {
+        let config = ActiveExpire::new(10, 1);
+        assert_eq!(20, config.keys_per_loop);
+        assert_eq!(10, config.acceptable_stale);
+        assert_eq!(25_000, config.time_limit.as_micros());
+        assert_eq!(100, config.interval().as_millis());
+        let config = ActiveExpire::new(0, 100);
+        assert_eq!(1, config.hz);
+        assert_eq!(65, config.keys_per_loop);
+        assert_eq!(1, config.acceptable_stale);
+    }
+
+    #[test]
+    fn test_cycle_removes_expired_keys() {
+        let mut store = Store::new();
+        fill(&mut store, "old", 1000, now_ms() - 1);
+        fill(&mut store, "new", 100, now_ms() + 60_000);
+        store
+            .kv_store
+            .try_write(Bytes::from("forever"), Bytes::new())
+            .unwrap();
+        // keys that aren't touched still count until a cycle gets to them
+        assert_eq!(1101, store.kv_store.inner.len());
+
+        let mut cycle = ExpireCycle::new(ActiveExpire::default());
+        let mut removed = 0;
+        while removed < 1000 {
+            removed += cycle.run(&mut store);
+        }
+        assert_eq!(1000, removed);
+        assert_eq!(101, store.kv_store.inner.len());
+        assert_eq!(100, store.expires.len());
+        assert_eq!(0, cycle.run(&mut store));
+    }
+
+    #[test]
+    fn test_cycle_stops_when_few_keys_expired() {
+        let mut store = Store::new();
+        fill(&mut store, "new", 1000, now_ms() + 60_000);
+        fill(&mut store, "old", 1, now_ms() - 1);
+        // one loop of keys_per_loop keys finds (at most) 1 expired -> the cycle doesn't go on to check the rest
+        let mut cycle = ExpireCycle::new(ActiveExpire::default());
+        cycle.run(&mut store);
+        assert_ne!(0, cycle.cursor);
+    }
+
+    #[test]
+    fn test_cycle_removes_expired_fields() {
+        let mut store = Store::new();
+        for i in 0..100 {
+            let key = Bytes::from(format!("h:{}", i));
+            let hash = store.hash_store.get_or_create(key.clone());
+            hash.insert(Bytes::from("old"), Bytes::new());
+            hash.set_expire_at(Bytes::from("old"), now_ms() - 1);
+            // every other hash has a field that outlives the one that's expired
+            if i % 2 == 0 {
+                hash.insert(Bytes::from("new"), Bytes::new());
+                hash.set_expire_at(Bytes::from("new"), now_ms() + 60_000);
+            }
+            store.track_field_expiry(&key);
+        }
+
+        let mut cycle = ExpireCycle::new(ActiveExpire::default());
+        let mut removed = 0;
+        while removed < 100 {
+            removed += cycle.run(&mut store);
+        }
+        assert_eq!(100, removed);
+        assert_eq!(50, store.hash_store.inner.len());
+        assert!(store.hash_store.inner.values().all(|hash| hash.len() == 1));
+        // the hashes left are only tracked until their other field expires
+        assert_eq!(50, store.hash_expires.len());
+        assert_eq!(0, cycle.run(&mut store));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeSet;
+
+use bytes::Bytes;
+
+use super::dict::Dict;
+
+// A hash's fields, each of which can have an expiry of its own. Expired fields are removed when the key is
+// next touched, the same way expired keys are (-> Store::expire_if_needed), or when the active expiry cycle
+// gets to the hash (-> Store::hash_expires).
+#[derive(Debug, Default)]
+pub struct Hash {
+    fields: Dict<Bytes>,
+    // When fields expire, in ms since the epoch -> a field without an entry never does
+    expires: Dict<i64>,
+    // The same expiries ordered by time, so the next one (and the ones that are due) are found without
+    // walking every field
+    by_time: BTreeSet<(i64, Bytes)>,
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&Bytes> {
+        self.fields.get(field)
+    }
+
+    pub fn contains_key(&self, field: &[u8]) -> bool {
+        self.fields.contains_key(field)
+    }
+
+    // Sets a field, dropping any expiry it had. Returns true if it's a new field.
+    pub fn insert(&mut self, field: Bytes, val: Bytes) -> bool {
+        self.persist(&field);
+        self.fields.insert(field, val).is_none()
+    }
+
+    // Changes the value of a field, keeping its expiry (-> HINCRBY)
+    pub fn update(&mut self, field: Bytes, val: Bytes) {
+        self.fields.insert(field, val);
+    }
+
+    pub fn remove(&mut self, field: &[u8]) -> bool {
+        self.persist(field);
+        self.fields.remove(field).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.fields.iter()
+    }
+
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, impl Iterator<Item = (&Bytes, &Bytes)>) {
+        self.fields.scan(cursor, count)
+    }
+
+    // When the field expires. None if there's no such field, or it has no expiry.
+    pub fn expire_at(&self, field: &[u8]) -> Option<i64> {
+        self.expires.get(field).copied()
+    }
+
+    // Sets when an existing field expires. Returns false if there's no such field.
+    pub fn set_expire_at(&mut self, field: Bytes, at: i64) -> bool {
+        if !self.fields.contains_key(&field) {
+            return false;
+        }
+        if let Some(old) = self.expires.insert(field.clone(), at) {
+            self.by_time.remove(&(old, field.clone()));
+        }
+        self.by_time.insert((at, field));
+        true
+    }
+
+    // Makes the field persistent. Returns whether it had an expiry to remove.
+    pub fn persist(&mut self, field: &[u8]) -> bool {
+        match self.expires.remove_entry(field) {
+            Some((field, at)) => {
+                self.by_time.remove(&(at, field));
+                true
+            }
+            None => false,
+        }
+    }
+
+    // When the first of the fields with an expiry expires
+    pub fn next_expiry(&self) -> Option<i64> {
+        self.by_time.first().map(|(at, _)| *at)
+    }
+
+    // Whether every field has expired, i.e. the hash is as good as gone
+    pub fn all_expired(&self, now: i64) -> bool {
+        self.expires.len() == self.fields.len()
+            && self.by_time.last().is_some_and(|(at, _)| *at < now)
+    }
+
+    // Removes the fields whose time is up, returning how many there were
+    pub fn remove_expired(&mut self, now: i64) -> usize {
+        let mut removed = 0;
+        while self.by_time.first().is_some_and(|(at, _)| *at < now) {
+            let (_, field) = self.by_time.pop_first().unwrap();
+            self.expires.remove(&field);
+            self.fields.remove(&field);
+            removed += 1;
+        }
+        removed
+    }
+}
+
+#[derive(Debug)]
+pub struct HashStore {
+    pub inner: Dict<Hash>,
+}
+
+impl Default for HashStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashStore {
+    pub fn new() -> Self {
+        let inner = Dict::new();
+        Self { inner }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&Hash> {
+        self.inner.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Hash> {
+        self.inner.get_mut(key)
+    }
+
+    // The hash at `key`, creating an empty one if there isn't one. The caller has to make sure it isn't left
+    // empty.
+    pub fn get_or_create(&mut self, key: Bytes) -> &mut Hash {
+        if !self.inner.contains_key(&key) {
+            self.inner.insert(key.clone(), Hash::new());
+        }
+        self.inner.get_mut(&key).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::Hash;
+
+    #[test]
+    fn test_field_expiry() {
+        let mut hash = Hash::new();
+        assert!(hash.insert(Bytes::from("a"), Bytes::from("1")));
+        assert!(hash.insert(Bytes::from("b"), Bytes::from("2")));
+        assert!(!hash.set_expire_at(Bytes::from("nope"), 10));
+        assert!(hash.set_expire_at(Bytes::from("a"), 10));
+        assert!(hash.set_expire_at(Bytes::from("b"), 30));
+        // a change of value keeps the expiry, setting the field again drops it
+        hash.update(Bytes::from("a"), Bytes::from("3"));
+        assert_eq!(Some(10), hash.expire_at(b"a"));
+        assert!(!hash.insert(Bytes::from("b"), Bytes::from("4")));
+        assert_eq!(None, hash.expire_at(b"b"));
+
+        assert_eq!(Some(10), hash.next_expiry());
+        assert!(!hash.all_expired(100));
+        assert_eq!(0, hash.remove_expired(10));
+        assert_eq!(1, hash.remove_expired(11));
+        assert!(!hash.contains_key(b"a"));
+        assert_eq!(None, hash.expire_at(b"a"));
+        assert_eq!(1, hash.len());
+        assert_eq!(None, hash.next_expiry());
+        hash.set_expire_at(Bytes::from("b"), 30);
+        assert!(!hash.all_expired(30));
+        assert!(hash.all_expired(31));
+        // a new expiry replaces the old one in the time order too
+        hash.set_expire_at(Bytes::from("b"), 50);
+        assert_eq!(Some(50), hash.next_expiry());
+        assert_eq!(0, hash.remove_expired(31));
+        assert!(hash.persist(b"b"));
+        assert_eq!(None, hash.next_expiry());
+        assert!(!hash.all_expired(100));
+    }
+}
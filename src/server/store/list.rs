@@ -0,0 +1,259 @@
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use super::dict::Dict;
+
+// The most elements a chunk holds
+const CHUNK_SIZE: usize = 128;
+
+// A list, kept like redis' quicklist: a deque of small chunks rather than one deque of every element. A push
+// or pop at either end only touches the chunk at that end, a long list grows a chunk at a time instead of
+// reallocating all of it, and an insert or remove in the middle only shifts the elements of one chunk.
+//
+// There are no empty chunks, so the first and last elements are always in the first and last chunks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuickList {
+    chunks: VecDeque<VecDeque<Bytes>>,
+    len: usize,
+}
+
+impl QuickList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, val: Bytes) {
+        match self.chunks.front_mut() {
+            Some(chunk) if chunk.len() < CHUNK_SIZE => chunk.push_front(val),
+            _ => self.chunks.push_front(VecDeque::from([val])),
+        }
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, val: Bytes) {
+        match self.chunks.back_mut() {
+            Some(chunk) if chunk.len() < CHUNK_SIZE => chunk.push_back(val),
+            _ => self.chunks.push_back(VecDeque::from([val])),
+        }
+        self.len += 1;
+    }
+
+    pub fn pop_front(&mut self) -> Option<Bytes> {
+        let chunk = self.chunks.front_mut()?;
+        let val = chunk.pop_front();
+        if chunk.is_empty() {
+            self.chunks.pop_front();
+        }
+        self.len -= 1;
+        val
+    }
+
+    pub fn pop_back(&mut self) -> Option<Bytes> {
+        let chunk = self.chunks.back_mut()?;
+        let val = chunk.pop_back();
+        if chunk.is_empty() {
+            self.chunks.pop_back();
+        }
+        self.len -= 1;
+        val
+    }
+
+    // The chunk holding the element at `index`, and where it is in the chunk. Walks from whichever end is
+    // nearer.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        if index >= self.len {
+            return None;
+        }
+        if index < self.len / 2 {
+            let mut index = index;
+            for (i, chunk) in self.chunks.iter().enumerate() {
+                if index < chunk.len() {
+                    return Some((i, index));
+                }
+                index -= chunk.len();
+            }
+        } else {
+            // how far from the end
+            let mut back = self.len - index;
+            for (i, chunk) in self.chunks.iter().enumerate().rev() {
+                if back <= chunk.len() {
+                    return Some((i, chunk.len() - back));
+                }
+                back -= chunk.len();
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Bytes> {
+        let (chunk, i) = self.locate(index)?;
+        self.chunks[chunk].get(i)
+    }
+
+    // Replaces the element at `index`, returning false if there isn't one
+    pub fn set(&mut self, index: usize, val: Bytes) -> bool {
+        match self.locate(index) {
+            Some((chunk, i)) => {
+                self.chunks[chunk][i] = val;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Inserts `val` so it ends up at `index`, which can be up to the length of the list. A chunk that grows
+    // past the chunk size is split in two.
+    pub fn insert(&mut self, index: usize, val: Bytes) {
+        if index == 0 {
+            return self.push_front(val);
+        }
+        if index >= self.len {
+            return self.push_back(val);
+        }
+        let (c, i) = self.locate(index).unwrap();
+        let chunk = &mut self.chunks[c];
+        chunk.insert(i, val);
+        if chunk.len() > CHUNK_SIZE {
+            let tail = chunk.split_off(chunk.len() / 2);
+            self.chunks.insert(c + 1, tail);
+        }
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Bytes> {
+        let (c, i) = self.locate(index)?;
+        let val = self.chunks[c].remove(i);
+        if self.chunks[c].is_empty() {
+            self.chunks.remove(c);
+        }
+        self.len -= 1;
+        val
+    }
+
+    // Keeps only the elements for which `keep` is true. Rebuilds the chunks, so they're full again after
+    // removing from the middle.
+    pub fn retain(&mut self, keep: impl FnMut(&Bytes) -> bool) {
+        let chunks = std::mem::take(&mut self.chunks);
+        self.len = 0;
+        for val in chunks.into_iter().flatten().filter(keep) {
+            self.push_back(val);
+        }
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Bytes> {
+        self.chunks.iter().flatten()
+    }
+}
+
+#[derive(Debug)]
+pub struct ListStore {
+    pub inner: Dict<QuickList>,
+}
+
+impl Default for ListStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListStore {
+    pub fn new() -> Self {
+        let inner = Dict::new();
+        Self { inner }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&QuickList> {
+        self.inner.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut QuickList> {
+        self.inner.get_mut(key)
+    }
+
+    // The list at `key`, creating an empty one if there isn't one. The caller has to make sure it isn't left
+    // empty.
+    pub fn get_or_create(&mut self, key: Bytes) -> &mut QuickList {
+        if !self.inner.contains_key(&key) {
+            self.inner.insert(key.clone(), QuickList::new());
+        }
+        self.inner.get_mut(&key).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::{QuickList, CHUNK_SIZE};
+
+    fn val(i: usize) -> Bytes {
+        Bytes::from(i.to_string())
+    }
+
+    fn check(list: &QuickList, expected: &[usize]) {
+        assert_eq!(expected.len(), list.len());
+        let vals = list.iter().cloned().collect::<Vec<_>>();
+        assert_eq!(expected.iter().map(|i| val(*i)).collect::<Vec<_>>(), vals);
+        for (i, v) in expected.iter().enumerate() {
+            assert_eq!(Some(&val(*v)), list.get(i));
+        }
+        assert!(list
+            .chunks
+            .iter()
+            .all(|c| !c.is_empty() && c.len() <= CHUNK_SIZE));
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut list = QuickList::new();
+        let n = CHUNK_SIZE * 3 + 5;
+        for i in 0..n {
+            list.push_back(val(i));
+        }
+        check(&list, &(0..n).collect::<Vec<_>>());
+        assert_eq!(4, list.chunks.len());
+        assert_eq!(Some(val(0)), list.pop_front());
+        assert_eq!(Some(val(n - 1)), list.pop_back());
+        list.push_front(val(0));
+        check(&list, &(0..n - 1).collect::<Vec<_>>());
+        while list.pop_back().is_some() {}
+        assert!(list.is_empty());
+        assert!(list.chunks.is_empty());
+        assert_eq!(None, list.pop_front());
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut list = QuickList::new();
+        let mut expected = Vec::new();
+        // inserting into the middle over and over splits chunks
+        for i in 0..CHUNK_SIZE * 4 {
+            let at = expected.len() / 2;
+            list.insert(at, val(i));
+            expected.insert(at, i);
+        }
+        check(&list, &expected);
+        assert!(list.chunks.len() > 4);
+        for at in [0, 7, CHUNK_SIZE * 4 - 3, CHUNK_SIZE * 2] {
+            assert_eq!(Some(val(expected.remove(at))), list.remove(at));
+        }
+        assert_eq!(None, list.remove(expected.len()));
+        assert!(list.set(3, val(9999)));
+        expected[3] = 9999;
+        assert!(!list.set(expected.len(), val(0)));
+        check(&list, &expected);
+        list.retain(|v| v.len() > 2);
+        expected.retain(|v| *v > 99);
+        check(&list, &expected);
+    }
+}
@@ -1,47 +1,84 @@
+pub mod blocked;
+pub mod dict;
 pub mod errors;
+pub mod expire;
 pub mod file;
+pub mod hash;
+pub mod list;
+pub mod set;
 
-use std::time::{Duration, Instant};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use hashbrown::HashMap;
+use bytes::Bytes;
+use rand::{thread_rng, Rng};
 
-use crate::stream::store::StreamStore;
+use crate::stream::store::{Stream, StreamStore};
 
+use self::blocked::Blocked;
+use self::dict::Dict;
 use self::errors::StoreError;
+use self::hash::{Hash, HashStore};
+use self::list::{ListStore, QuickList};
+use self::set::{Set, SetStore};
 
 type R<T> = anyhow::Result<T, StoreError>;
 
-#[derive(Debug)]
-pub struct KVStoreValue {
-    val: String,
-    expiry: Option<Instant>,
+// Milliseconds since the unix epoch. Expiry times are kept as wall-clock times, so they mean the same thing to
+// EXPIREAT and EXPIRETIME as to the client.
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+// A string value. Like redis' int encoding, a string that's the canonical form of a 64-bit integer is kept as
+// the integer, so counters don't get parsed and formatted on every INCR.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringValue {
+    Raw(Bytes),
+    Int(i64),
 }
 
-impl KVStoreValue {
-    fn new(val: String, exp: Option<Duration>) -> Self {
-        let expiry: Option<Instant> = exp.map(|exp| Instant::now() + exp);
-        Self { val, expiry }
+impl StringValue {
+    pub fn new(val: Bytes) -> Self {
+        match parse_canonical_int(&val) {
+            Some(n) => Self::Int(n),
+            None => Self::Raw(val),
+        }
     }
 
-    #[inline]
-    fn is_expired(&self) -> bool {
-        match self.expiry {
-            Some(exp) => exp < Instant::now(),
-            None => false,
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            Self::Raw(val) => val.clone(),
+            Self::Int(n) => Bytes::from(n.to_string()),
         }
     }
 
-    fn to_val(&self) -> Option<&String> {
-        match self.is_expired() {
-            false => Some(&self.val),
-            true => None,
+    // The value as an integer, if it's the canonical form of one
+    pub fn to_int(&self) -> Option<i64> {
+        match self {
+            Self::Raw(val) => parse_canonical_int(val),
+            Self::Int(n) => Some(*n),
         }
     }
 }
 
+// Only strings that would be formatted the same way again, e.g. not '+1', '007' or ' 1'
+fn parse_canonical_int(val: &[u8]) -> Option<i64> {
+    // i64::MIN is 20 bytes long
+    if val.is_empty() || val.len() > 20 {
+        return None;
+    }
+    let n = std::str::from_utf8(val).ok()?.parse::<i64>().ok()?;
+    match n.to_string().as_bytes() == val {
+        true => Some(n),
+        false => None,
+    }
+}
+
 #[derive(Debug)]
 pub struct KVStore {
-    pub inner: HashMap<String, KVStoreValue>,
+    pub inner: Dict<StringValue>,
 }
 
 impl Default for KVStore {
@@ -52,29 +89,98 @@ impl Default for KVStore {
 
 impl KVStore {
     fn new() -> Self {
-        let inner = HashMap::new();
+        let inner = Dict::new();
         Self { inner }
     }
 
-    pub fn try_read(&self, key: String) -> Option<&String> {
-        match self.inner.get(&key) {
-            Some(store_val) => store_val.to_val(),
-            None => None,
-        }
+    pub fn try_read(&self, key: &[u8]) -> Option<Bytes> {
+        self.inner.get(key).map(StringValue::to_bytes)
     }
 
-    pub fn try_write(&mut self, key: String, val: String, exp: Option<Duration>) -> R<()> {
-        let store_val = KVStoreValue::new(val, exp);
-        let _ = self.inner.remove(&key);
-        self.inner.insert(key, store_val);
+    pub fn get(&self, key: &[u8]) -> Option<&StringValue> {
+        self.inner.get(key)
+    }
+
+    pub fn try_write(&mut self, key: Bytes, val: Bytes) -> R<()> {
+        self.inner.insert(key, StringValue::new(val));
         Ok(())
     }
+
+    pub fn write_int(&mut self, key: Bytes, n: i64) {
+        self.inner.insert(key, StringValue::Int(n));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    String,
+    List,
+    Hash,
+    Set,
+    Stream,
+}
+
+impl KeyType {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::List => "list",
+            Self::Hash => "hash",
+            Self::Set => "set",
+            Self::Stream => "stream",
+        }
+    }
+}
+
+// A value taken out of the store
+#[derive(Debug)]
+pub enum StoreValue {
+    String(StringValue),
+    List(QuickList),
+    Hash(Hash),
+    Set(Set),
+    Stream(Stream),
+}
+
+impl StoreValue {
+    // Roughly how many allocations dropping the value frees
+    fn free_effort(&self) -> usize {
+        match self {
+            Self::String(_) => 1,
+            Self::List(list) => list.len(),
+            Self::Hash(hash) => hash.len(),
+            Self::Set(set) => set.len(),
+            Self::Stream(stream) => stream.len(),
+        }
+    }
+}
+
+// Values that take more work than this to free are dropped off the connection's task (-> UNLINK)
+const LAZYFREE_THRESHOLD: usize = 64;
+
+// Drops `value` on a blocking thread, so freeing a large value doesn't hold up the server
+pub fn free_in_background<T: Send + 'static>(value: T) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(move || drop(value));
+        }
+        Err(_) => drop(value),
+    }
 }
 
 #[derive(Debug)]
 pub struct Store {
     pub kv_store: KVStore,
+    pub list_store: ListStore,
+    pub hash_store: HashStore,
+    pub set_store: SetStore,
     pub stream_store: StreamStore,
+    // When keys (of any type) expire, in ms since the epoch -> a key without an entry never does
+    pub expires: Dict<i64>,
+    // The hashes with fields that expire, and when the first of them does. It can be earlier than the hash's
+    // real next expiry (e.g. once that field's been set again), which only means the hash is checked early.
+    pub hash_expires: Dict<i64>,
+    pub blocked: Blocked,
 }
 
 impl Default for Store {
@@ -83,13 +189,379 @@ impl Default for Store {
     }
 }
 
+// How many typed stores there are, and where a SCAN cursor keeps which one it's in (-> Store::scan)
+const TYPED_STORES: usize = 5;
+const STORE_CURSOR_SHIFT: u32 = 61;
+
+fn scan_keys<V>(
+    dict: &Dict<V>,
+    cursor: u64,
+    count: usize,
+    key_type: KeyType,
+) -> (u64, Vec<(&Bytes, KeyType)>) {
+    let (next, entries) = dict.scan(cursor, count);
+    (next, entries.map(|(key, _)| (key, key_type)).collect())
+}
+
 impl Store {
     pub fn new() -> Self {
-        let kv_store = KVStore::new();
-        let stream_store = StreamStore::new();
         Self {
-            kv_store,
-            stream_store,
+            kv_store: KVStore::new(),
+            list_store: ListStore::new(),
+            hash_store: HashStore::new(),
+            set_store: SetStore::new(),
+            stream_store: StreamStore::new(),
+            expires: Dict::new(),
+            hash_expires: Dict::new(),
+            blocked: Blocked::default(),
+        }
+    }
+
+    // A key lives in at most one of the typed stores
+    pub fn key_type(&self, key: &[u8]) -> Option<KeyType> {
+        if self.is_expired(key) {
+            None
+        } else if self.kv_store.get(key).is_some() {
+            Some(KeyType::String)
+        } else if self.list_store.get(key).is_some() {
+            Some(KeyType::List)
+        } else if self.hash_store.get(key).is_some() {
+            Some(KeyType::Hash)
+        } else if self.set_store.get(key).is_some() {
+            Some(KeyType::Set)
+        } else if self.stream_store.try_read(key).is_some() {
+            Some(KeyType::Stream)
+        } else {
+            None
+        }
+    }
+
+    // Ok if the key doesn't exist, or holds a value of the expected type
+    pub fn check_type(&self, key: &[u8], expected: KeyType) -> R<()> {
+        match self.key_type(key) {
+            Some(key_type) if key_type != expected => Err(StoreError::WrongType),
+            _ => Ok(()),
         }
     }
+
+    // Removes the key, returning its value. An expired value is removed too, but isn't returned.
+    pub fn take(&mut self, key: &[u8]) -> Option<StoreValue> {
+        let expired = self.is_expired(key);
+        self.expires.remove(key);
+        self.hash_expires.remove(key);
+        let val = if let Some(v) = self.kv_store.inner.remove(key) {
+            Some(StoreValue::String(v))
+        } else if let Some(list) = self.list_store.inner.remove(key) {
+            Some(StoreValue::List(list))
+        } else if let Some(hash) = self.hash_store.inner.remove(key) {
+            Some(StoreValue::Hash(hash))
+        } else if let Some(set) = self.set_store.inner.remove(key) {
+            Some(StoreValue::Set(set))
+        } else {
+            self.stream_store.inner.remove(key).map(StoreValue::Stream)
+        };
+        val.filter(|_| !expired)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        self.take(key).is_some()
+    }
+
+    // Like remove, but a large value is freed in the background
+    pub fn unlink(&mut self, key: &[u8]) -> bool {
+        match self.take(key) {
+            Some(v) if v.free_effort() > LAZYFREE_THRESHOLD => {
+                free_in_background(v);
+                true
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.key_type(key).is_some()
+    }
+
+    // Every key that hasn't expired, whatever its type
+    pub fn keys(&self) -> impl Iterator<Item = &Bytes> {
+        self.kv_store
+            .inner
+            .keys()
+            .chain(self.list_store.inner.keys())
+            .chain(self.hash_store.inner.keys())
+            .chain(self.set_store.inner.keys())
+            .chain(self.stream_store.inner.keys())
+            .filter(|key| !self.is_expired(key))
+    }
+
+    // When the key expires, in ms since the epoch. None if it doesn't exist or has no expiry.
+    pub fn expire_at(&self, key: &[u8]) -> Option<i64> {
+        self.expires.get(key).copied()
+    }
+
+    // Whether the key's time is up, or it's a hash whose fields all are
+    pub fn is_expired(&self, key: &[u8]) -> bool {
+        let now = now_ms();
+        if self.expires.get(key).is_some_and(|at| *at < now) {
+            return true;
+        }
+        match self.hash_expires.get(key) {
+            Some(at) if *at < now => self
+                .hash_store
+                .get(key)
+                .is_some_and(|hash| hash.all_expired(now)),
+            _ => false,
+        }
+    }
+
+    // Brings the hash's entry in `hash_expires` up to date, after the expiries of its fields have changed
+    pub fn track_field_expiry(&mut self, key: &[u8]) {
+        match self.hash_store.get(key).and_then(Hash::next_expiry) {
+            Some(at) => {
+                self.hash_expires.insert(Bytes::copy_from_slice(key), at);
+            }
+            None => {
+                self.hash_expires.remove(key);
+            }
+        }
+    }
+
+    // Removes the key if its time is up, or the expired fields of a hash (and the hash, if that empties it).
+    // Commands don't check expiry themselves -> this runs on the keys a command is about to touch, before it
+    // runs.
+    pub fn expire_if_needed(&mut self, key: &[u8]) -> bool {
+        if self.is_expired(key) {
+            self.remove(key);
+            return true;
+        }
+        // only a hash whose first field expiry is due has any fields to remove
+        let now = now_ms();
+        match self.hash_expires.get(key) {
+            Some(at) if *at < now => {}
+            _ => return false,
+        }
+        if let Some(hash) = self.hash_store.get_mut(key) {
+            hash.remove_expired(now);
+        }
+        // the entry could have been early -> either way it's brought up to date
+        self.track_field_expiry(key);
+        // the fields that had expired were all it had
+        let emptied = self.hash_store.get(key).is_some_and(Hash::is_empty);
+        if emptied {
+            self.remove(key);
+        }
+        emptied
+    }
+
+    // Sets when an existing key expires. Returns false if there's no such key.
+    pub fn set_expire_at(&mut self, key: Bytes, at: i64) -> bool {
+        match self.contains_key(&key) {
+            true => {
+                self.expires.insert(key, at);
+                true
+            }
+            false => false,
+        }
+    }
+
+    // Makes the key persistent. Returns whether it had an expiry to remove.
+    pub fn persist(&mut self, key: &[u8]) -> bool {
+        !self.is_expired(key) && self.expires.remove(key).is_some()
+    }
+
+    // The number of keys, without walking them -> like redis' DBSIZE, keys that have expired but haven't been
+    // removed yet are counted. Hashes whose fields have all expired aren't, so this agrees with KEYS and SCAN
+    // about hashes. Only hashes with a field that's overdue need checking, and the active expiry cycle keeps
+    // those few.
+    pub fn len(&self) -> usize {
+        let now = now_ms();
+        let dead_hashes = self
+            .hash_expires
+            .iter()
+            .filter(|(key, at)| {
+                **at < now
+                    && self
+                        .hash_store
+                        .get(key)
+                        .is_some_and(|hash| hash.all_expired(now))
+            })
+            .count();
+        self.kv_store.inner.len()
+            + self.list_store.inner.len()
+            + self.hash_store.inner.len()
+            + self.set_store.inner.len()
+            + self.stream_store.inner.len()
+            - dead_hashes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // A key picked at random. The typed store to pick from is picked by how many keys it holds, then a key is
+    // picked from it (-> Dict::random). Like redis, an expired key is skipped by picking again, a few times.
+    pub fn random_key(&self) -> Option<&Bytes> {
+        const TRIES: usize = 100;
+        let lens = [
+            self.kv_store.inner.len(),
+            self.list_store.inner.len(),
+            self.hash_store.inner.len(),
+            self.set_store.inner.len(),
+            self.stream_store.inner.len(),
+        ];
+        let len = lens.iter().sum::<usize>();
+        if len == 0 {
+            return None;
+        }
+        let mut rng = thread_rng();
+        for _ in 0..TRIES {
+            let (mut at, mut store) = (rng.gen_range(0..len), 0);
+            while at >= lens[store] {
+                at -= lens[store];
+                store += 1;
+            }
+            let key = match store {
+                0 => self.kv_store.inner.random().map(|(k, _)| k),
+                1 => self.list_store.inner.random().map(|(k, _)| k),
+                2 => self.hash_store.inner.random().map(|(k, _)| k),
+                3 => self.set_store.inner.random().map(|(k, _)| k),
+                _ => self.stream_store.inner.random().map(|(k, _)| k),
+            };
+            if let Some(key) = key.filter(|key| !self.is_expired(key)) {
+                return Some(key);
+            }
+        }
+        // (nearly) everything's expired
+        self.keys().next()
+    }
+
+    // Returns the keys (and their types) in about `count` slots from `cursor`, and the cursor to carry on
+    // from. The typed stores are walked one after the other -> the top bits of the cursor say which one it's
+    // in, the rest are the cursor into that store's dict (-> Dict::scan). Expired keys take up a slot, but
+    // aren't returned.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<(&Bytes, KeyType)>) {
+        let mut store = (cursor >> STORE_CURSOR_SHIFT) as usize;
+        let mut cursor = cursor & ((1 << STORE_CURSOR_SHIFT) - 1);
+        let (mut seen, mut keys) = (0, Vec::new());
+        while seen < count {
+            let left = count - seen;
+            let (next, batch) = match store {
+                0 => scan_keys(&self.kv_store.inner, cursor, left, KeyType::String),
+                1 => scan_keys(&self.list_store.inner, cursor, left, KeyType::List),
+                2 => scan_keys(&self.hash_store.inner, cursor, left, KeyType::Hash),
+                3 => scan_keys(&self.set_store.inner, cursor, left, KeyType::Set),
+                4 => scan_keys(&self.stream_store.inner, cursor, left, KeyType::Stream),
+                _ => return (0, keys),
+            };
+            seen += batch.len();
+            keys.extend(batch.into_iter().filter(|(key, _)| !self.is_expired(key)));
+            if next != 0 {
+                // the dict stopped short of `count` (-> Dict::scan) or got to it
+                return ((store as u64) << STORE_CURSOR_SHIFT | next, keys);
+            }
+            store += 1;
+            cursor = 0;
+        }
+        match store {
+            TYPED_STORES.. => (0, keys),
+            store => ((store as u64) << STORE_CURSOR_SHIFT, keys),
+        }
+    }
+
+    // The list at `key` to push onto, created if there isn't one. Clients blocked on the key are served once
+    // the command is done.
+    pub fn list_for_push(&mut self, key: Bytes) -> &mut QuickList {
+        self.blocked.signal(&key);
+        self.list_store.get_or_create(key)
+    }
+
+    // Empties the store, returning what it held so the caller decides how it's freed. Blocked clients stay
+    // blocked.
+    pub fn flush(&mut self) -> Store {
+        let blocked = std::mem::take(&mut self.blocked);
+        let flushed = std::mem::take(self);
+        self.blocked = blocked;
+        flushed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::{now_ms, Store, StringValue};
+
+    #[test]
+    fn test_int_encoding() {
+        for (val, int) in [
+            ("42", Some(42)),
+            ("-42", Some(-42)),
+            ("0", Some(0)),
+            ("-9223372036854775808", Some(i64::MIN)),
+            ("9223372036854775808", None),
+            ("+1", None),
+            ("007", None),
+            ("-0", None),
+            (" 1", None),
+            ("", None),
+        ] {
+            let encoded = StringValue::new(Bytes::from(val));
+            assert_eq!(
+                int.is_some(),
+                matches!(encoded, StringValue::Int(_)),
+                "{}",
+                val
+            );
+            assert_eq!(int, encoded.to_int());
+            assert_eq!(val, encoded.to_bytes());
+        }
+    }
+
+    #[test]
+    fn test_len_and_random_key() {
+        let mut store = Store::new();
+        assert_eq!(None, store.random_key());
+        for i in 0..10 {
+            let key = Bytes::from(format!("gone:{}", i));
+            store
+                .kv_store
+                .try_write(key.clone(), Bytes::from("v"))
+                .unwrap();
+            store.set_expire_at(key, now_ms() - 1);
+        }
+        store
+            .list_for_push(Bytes::from("list"))
+            .push_back(Bytes::from("v"));
+        // keys that have expired but are still there count, but aren't picked
+        assert_eq!(11, store.len());
+        for _ in 0..20 {
+            assert_eq!(Some(&Bytes::from("list")), store.random_key());
+        }
+    }
+
+    #[test]
+    fn test_hash_with_all_fields_expired() {
+        let mut store = Store::new();
+        let key = Bytes::from("h");
+        let hash = store.hash_store.get_or_create(key.clone());
+        hash.insert(Bytes::from("a"), Bytes::from("1"));
+        hash.set_expire_at(Bytes::from("a"), now_ms() - 1);
+        store.track_field_expiry(&key);
+        // it's still there, but as good as gone
+        assert_eq!(1, store.hash_store.inner.len());
+        assert_eq!(0, store.len());
+        assert_eq!(0, store.keys().count());
+        assert!(store.scan(0, 10).1.is_empty());
+        assert_eq!(None, store.key_type(&key));
+
+        // a field that hasn't expired keeps it alive
+        let hash = store.hash_store.get_mut(&key).unwrap();
+        hash.insert(Bytes::from("b"), Bytes::from("2"));
+        assert_eq!(1, store.len());
+        assert_eq!(vec![&key], store.keys().collect::<Vec<_>>());
+        store.remove(&key);
+        assert!(store.hash_expires.is_empty());
+    }
 }
@@ -0,0 +1,296 @@
+use bytes::Bytes;
+use rand::seq::{index, SliceRandom};
+use rand::thread_rng;
+
+use super::dict::Dict;
+use super::parse_canonical_int;
+
+// The most members a set is kept as an intset for
+const MAX_INTSET_ENTRIES: usize = 512;
+
+// A set. Like redis, a small set whose members are all integers is kept as an intset: a sorted array of the
+// integers, which takes a fraction of the memory of a table and is still quick to search at that size. It's
+// converted to a table for good once it grows too large, or something that isn't an integer is added.
+#[derive(Debug)]
+pub enum Set {
+    Ints(Vec<i64>),
+    Members(Dict<()>),
+}
+
+impl Default for Set {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Set {
+    pub fn new() -> Self {
+        Self::Ints(Vec::new())
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Ints(ints) => ints.len(),
+            Self::Members(members) => members.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_intset(&self) -> bool {
+        matches!(self, Self::Ints(_))
+    }
+
+    pub fn contains(&self, member: &[u8]) -> bool {
+        match self {
+            Self::Ints(ints) => {
+                parse_canonical_int(member).is_some_and(|n| ints.binary_search(&n).is_ok())
+            }
+            Self::Members(members) => members.contains_key(member),
+        }
+    }
+
+    // Returns true if it's a new member
+    pub fn insert(&mut self, member: Bytes) -> bool {
+        if let Self::Ints(ints) = self {
+            match parse_canonical_int(&member).map(|n| (n, ints.binary_search(&n))) {
+                Some((_, Ok(_))) => return false,
+                Some((n, Err(at))) if ints.len() < MAX_INTSET_ENTRIES => {
+                    ints.insert(at, n);
+                    return true;
+                }
+                _ => self.convert(),
+            }
+        }
+        match self {
+            Self::Members(members) => members.insert(member, ()).is_none(),
+            Self::Ints(_) => unreachable!(),
+        }
+    }
+
+    // Moves the integers into a table
+    fn convert(&mut self) {
+        if let Self::Ints(ints) = self {
+            let mut members = Dict::new();
+            for n in ints.iter() {
+                members.insert(Bytes::from(n.to_string()), ());
+            }
+            *self = Self::Members(members);
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self {
+            Self::Ints(ints) => match parse_canonical_int(member).map(|n| ints.binary_search(&n)) {
+                Some(Ok(at)) => {
+                    ints.remove(at);
+                    true
+                }
+                _ => false,
+            },
+            Self::Members(members) => members.remove(member).is_some(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Bytes> + '_ {
+        let (ints, members) = match self {
+            Self::Ints(ints) => (Some(ints.iter()), None),
+            Self::Members(members) => (None, Some(members.keys())),
+        };
+        ints.into_iter()
+            .flatten()
+            .map(|n| Bytes::from(n.to_string()))
+            .chain(members.into_iter().flatten().cloned())
+    }
+
+    // A member picked at random, without walking the set. Every member of an intset is as likely as the next,
+    // but not of a table -> see Dict::random.
+    pub fn random(&self) -> Option<Bytes> {
+        match self {
+            Self::Ints(ints) => ints
+                .choose(&mut thread_rng())
+                .map(|n| Bytes::from(n.to_string())),
+            Self::Members(members) => members.random().map(|(member, _)| member.clone()),
+        }
+    }
+
+    // Removes a member picked at random
+    pub fn pop_random(&mut self) -> Option<Bytes> {
+        let member = self.random()?;
+        self.remove(&member);
+        Some(member)
+    }
+
+    // Removes `count` distinct members picked at random, or all of them if there aren't that many. An intset is
+    // shuffled and the members popped split off its end, a table has the members picked by their position in
+    // it, and is walked once to take them out.
+    pub fn pop_random_many(&mut self, count: usize) -> Vec<Bytes> {
+        let mut rng = thread_rng();
+        let len = self.len();
+        if count >= len {
+            return std::mem::take(self).iter().collect();
+        }
+        match self {
+            Self::Ints(ints) => {
+                ints.shuffle(&mut rng);
+                let popped = ints.split_off(len - count);
+                ints.sort_unstable();
+                popped
+                    .into_iter()
+                    .map(|n| Bytes::from(n.to_string()))
+                    .collect()
+            }
+            Self::Members(members) => {
+                let mut pop = vec![false; len];
+                for at in index::sample(&mut rng, len, count) {
+                    pop[at] = true;
+                }
+                let mut popped = Vec::with_capacity(count);
+                let mut at = 0;
+                members.retain(|member, _| {
+                    let keep = !pop[at];
+                    if !keep {
+                        popped.push(member.clone());
+                    }
+                    at += 1;
+                    keep
+                });
+                popped
+            }
+        }
+    }
+
+    // Returns about `count` members starting at `cursor`, and the cursor to carry on from, see Dict::scan. An
+    // intset is small, so like redis it's returned whole.
+    pub fn scan(&self, cursor: u64, count: usize) -> (u64, Vec<Bytes>) {
+        match self {
+            Self::Ints(_) => (0, self.iter().collect()),
+            Self::Members(members) => {
+                let (next, members) = members.scan(cursor, count);
+                (next, members.map(|(m, _)| m.clone()).collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SetStore {
+    pub inner: Dict<Set>,
+}
+
+impl Default for SetStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SetStore {
+    pub fn new() -> Self {
+        let inner = Dict::new();
+        Self { inner }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&Set> {
+        self.inner.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Set> {
+        self.inner.get_mut(key)
+    }
+
+    // The set at `key`, creating an empty one if there isn't one. The caller has to make sure it isn't left
+    // empty.
+    pub fn get_or_create(&mut self, key: Bytes) -> &mut Set {
+        if !self.inner.contains_key(&key) {
+            self.inner.insert(key.clone(), Set::new());
+        }
+        self.inner.get_mut(&key).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bytes::Bytes;
+
+    use super::{Set, MAX_INTSET_ENTRIES};
+
+    fn sorted(set: &Set) -> Vec<Bytes> {
+        let mut members = set.iter().collect::<Vec<_>>();
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn test_intset() {
+        let mut set = Set::new();
+        for n in ["3", "-1", "2", "3"] {
+            set.insert(Bytes::from(n));
+        }
+        assert!(set.is_intset());
+        assert!(matches!(&set, Set::Ints(ints) if ints == &[-1, 2, 3]));
+        assert!(set.contains(b"2"));
+        // only the canonical form of an integer is the same member
+        assert!(!set.contains(b"02"));
+        assert!(set.remove(b"-1"));
+        assert!(!set.remove(b"-1"));
+
+        assert!(set.insert(Bytes::from("02")));
+        assert!(!set.is_intset());
+        assert_eq!(
+            vec![Bytes::from("02"), Bytes::from("2"), Bytes::from("3")],
+            sorted(&set)
+        );
+        assert!(set.contains(b"3"));
+        // it stays a table even once the member that wasn't an integer is gone
+        set.remove(b"02");
+        assert!(!set.is_intset());
+    }
+
+    #[test]
+    fn test_intset_grows_into_table() {
+        let mut set = Set::new();
+        for n in 0..MAX_INTSET_ENTRIES {
+            set.insert(Bytes::from(n.to_string()));
+        }
+        assert!(set.is_intset());
+        assert_eq!((0, set.iter().collect()), set.scan(0, 10));
+        set.insert(Bytes::from(MAX_INTSET_ENTRIES.to_string()));
+        assert!(!set.is_intset());
+        assert_eq!(MAX_INTSET_ENTRIES + 1, set.len());
+        assert!((0..=MAX_INTSET_ENTRIES).all(|n| set.contains(n.to_string().as_bytes())));
+        while set.pop_random().is_some() {}
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_pop_random_many() {
+        for (len, count) in [(100, 10), (100, 90), (10, 10), (10, 20), (1000, 999)] {
+            let mut set = Set::new();
+            for n in 0..len {
+                set.insert(Bytes::from(format!("m{}", n)));
+            }
+            let mut popped = set.pop_random_many(count);
+            popped.sort();
+            popped.dedup();
+            assert_eq!(count.min(len), popped.len());
+            assert_eq!(len - popped.len(), set.len());
+            assert!(popped.iter().all(|member| !set.contains(member)));
+        }
+
+        let mut set = Set::new();
+        for n in 0..100 {
+            set.insert(Bytes::from(n.to_string()));
+        }
+        let popped = set.pop_random_many(60);
+        assert_eq!(60, popped.len());
+        assert!(popped.iter().all(|member| !set.contains(member)));
+        // what's left is still a sorted intset
+        assert!(
+            matches!(&set, Set::Ints(ints) if ints.len() == 40 && ints.windows(2).all(|w| w[0] < w[1]))
+        );
+        assert!(set.contains(set.random().unwrap().as_ref()));
+    }
+}
@@ -85,7 +85,10 @@ impl StreamID {
         let id = get_current_time();
         match id == last.id {
             true => {
-                let seq = last.seq + 1;
+                let seq = last
+                    .seq
+                    .checked_add(1)
+                    .ok_or(StreamError::InvalidStreamID)?;
                 Ok(Self::new(id, seq))
             }
             false => Ok(Self::new(id, 0)),
@@ -105,7 +108,10 @@ impl StreamID {
 
     fn seq_wc(id: usize, last: Self) -> R<Self> {
         match id == last.id {
-            true => Ok(Self::new(id, last.seq + 1)),
+            true => match last.seq.checked_add(1) {
+                Some(seq) => Ok(Self::new(id, seq)),
+                None => Err(StreamError::InvalidStreamID),
+            },
             false => match id > last.id {
                 true => Ok(Self::new(id, 0)),
                 false => Err(StreamError::InvalidStreamID),
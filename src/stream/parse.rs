@@ -11,7 +11,7 @@ impl StreamIDParser {
         match v.as_str() {
             WC_STR => Ok(None),
             RANGE_LT => Ok(Some(0)),
-            RANGE_GT => Ok(Some(std::usize::MAX)),
+            RANGE_GT => Ok(Some(usize::MAX)),
             _ => match v.parse::<usize>() {
                 Ok(v) => Ok(Some(v)),
                 Err(_) => Err(StreamError::InvalidStreamID),
@@ -58,7 +58,8 @@ impl StreamIDParser {
         match Self::convert_to_usize_opt(id, seq)? {
             (Some(id), Some(seq)) => Ok(StreamID::new(id, seq)),
             (Some(id), None) => Ok(StreamID::new(id, id)), // either 0-0 or MAX-MAX
-            _ => panic!("Called in the wrong context!"),
+            // wildcards only make sense when adding an entry
+            _ => Err(StreamError::InvalidStreamID),
         }
     }
 }
@@ -1,40 +1,40 @@
-use crate::resp::serialize::Serializer;
+use std::collections::VecDeque;
+
+use bytes::Bytes;
+
+use crate::resp::data::DataType;
 
 use super::StreamID;
 
-type StreamValues<'stream> = &'stream Vec<(String, String)>;
-type StreamRange<'stream> = Vec<(&'stream StreamID, &'stream Vec<(String, String)>)>;
+type StreamValues<'stream> = &'stream Vec<(Bytes, Bytes)>;
+type StreamRange<'stream> = Vec<(&'stream StreamID, &'stream Vec<(Bytes, Bytes)>)>;
 
 pub struct StreamSerializer {}
 
 impl StreamSerializer {
-    fn stream_id(stream_id: &StreamID) -> String {
-        let s = format!("{}-{}", stream_id.id, stream_id.seq);
-        Serializer::to_bulk_str(s.as_str())
+    fn stream_id(stream_id: &StreamID) -> DataType {
+        DataType::bulk_str(&format!("{}-{}", stream_id.id, stream_id.seq))
     }
 
-    fn v_to_arr(v: StreamValues) -> String {
-        let mut buffer = Vec::with_capacity(v.len());
-        for (k, v) in v.iter() {
-            buffer.push(k.as_str());
-            buffer.push(v.as_str());
-        }
-        Serializer::to_arr(buffer)
+    // A map for RESP3 clients -> RESP2 clients get the usual flat list of fields and values
+    fn values(v: StreamValues) -> DataType {
+        let pairs = v
+            .iter()
+            .map(|(k, v)| {
+                (
+                    DataType::BulkString(k.clone()),
+                    DataType::BulkString(v.clone()),
+                )
+            })
+            .collect();
+        DataType::Map(pairs)
     }
 
-    pub fn to_arr(stream_range: &StreamRange) -> String {
-        // extremely inefficient, allocations for days. FIXME
-        let mut buffer = String::with_capacity(1024);
-        buffer.push('*');
-        buffer.push_str(&stream_range.len().to_string());
-        buffer.push_str("\r\n");
-        for (id, v) in stream_range {
-            buffer.push('*');
-            buffer.push_str(&2.to_string());
-            buffer.push_str("\r\n");
-            buffer.push_str(&Self::stream_id(id));
-            buffer.push_str(&Self::v_to_arr(v));
-        }
-        buffer
+    pub fn to_data(stream_range: &StreamRange) -> DataType {
+        let entries = stream_range
+            .iter()
+            .map(|(id, v)| DataType::Array(VecDeque::from([Self::stream_id(id), Self::values(v)])))
+            .collect();
+        DataType::Array(entries)
     }
 }
@@ -1,12 +1,16 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+
+use crate::server::store::dict::Dict;
 
 use super::{StreamID, R};
 
-pub type Stream = BTreeMap<StreamID, Vec<(String, String)>>;
+pub type Stream = BTreeMap<StreamID, Vec<(Bytes, Bytes)>>;
 
 #[derive(Debug)]
 pub struct StreamStore {
-    pub inner: HashMap<String, Stream>,
+    pub inner: Dict<Stream>,
 }
 
 impl Default for StreamStore {
@@ -17,24 +21,24 @@ impl Default for StreamStore {
 
 impl StreamStore {
     pub fn new() -> Self {
-        let inner = HashMap::new();
+        let inner = Dict::new();
         Self { inner }
     }
 
-    pub fn try_read(&self, key: String) -> Option<&Stream> {
-        self.inner.get(&key)
+    pub fn try_read(&self, key: &[u8]) -> Option<&Stream> {
+        self.inner.get(key)
     }
 
     pub fn try_write(
         &mut self,
-        key: String,
-        values: Vec<(String, String)>,
+        key: Bytes,
+        values: Vec<(Bytes, Bytes)>,
         stream_id: (String, Option<String>),
     ) -> R<(usize, usize)> {
         let (id, seq) = stream_id;
         let added_id = if let Some(get) = self.inner.get(&key) {
             // .get() - to borrow the stream + check the id
-            let uid = StreamID::checked_new(id, seq, Some(&get))?;
+            let uid = StreamID::checked_new(id, seq, Some(get))?;
             // .remove() to take ownership so it can be modified without cloning
             let mut stream = self.inner.remove(&key).unwrap();
             stream.insert(uid, values);